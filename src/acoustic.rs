@@ -0,0 +1,47 @@
+//! Automatic Acoustic Management feature set (documentation of ATA8-ACS chapter 4.3), trading
+//! seek speed for lower noise. Obsolete in newer standards, but still honored by many drives.
+
+use std::io;
+
+use crate::Device;
+
+const SETFEATURES_AAM_ON: u8 = 0x42;
+const SETFEATURES_AAM_OFF: u8 = 0xC2;
+
+impl Device {
+    /// Return current Automatic Acoustic Management level from a fresh identification record,
+    /// `None` if disabled. See
+    /// [`IdentifyDeviceData::get_aam_level`](crate::IdentifyDeviceData::get_aam_level).
+    pub fn get_aam_level(&mut self) -> io::Result<Option<u8>> {
+        Ok(self.info()?.get_aam_level())
+    }
+
+    /// Set Automatic Acoustic Management level using `SET FEATURES` (ATA cmd 0xEF, subcommand
+    /// 0x42), or disable it with `None` (subcommand 0xC2).
+    ///
+    /// `level` ranges from 0x80 (quietest) to 0xFE (fastest). Drives not supporting the feature
+    /// set (identify word 83 bit 9) are reported with an error. Cached identification is
+    /// refreshed afterwards.
+    pub fn set_aam_level(&mut self, level: Option<u8>) -> io::Result<()> {
+        if self.ident.is_some_and(|id| !id.supports_aam()) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Automatic Acoustic Management not supported",
+            ));
+        }
+
+        match level {
+            Some(level @ 0x80..=0xFE) => self.set_features(SETFEATURES_AAM_ON, level as u16)?,
+            Some(level) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid AAM level {:#04x}", level),
+                ))
+            }
+            None => self.set_features(SETFEATURES_AAM_OFF, 0)?,
+        }
+
+        self.ident = self.info().ok();
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+//! Detection of writes not aligned to physical sectors of Advanced Format (512e) drives, which
+//! the drive serves with a slow read-modify-write cycle.
+
+use std::{error::Error, fmt, io};
+
+use crate::Device;
+
+/// Error of a write not covering whole physical sectors, returned in strict alignment mode (see
+/// [`Device::set_strict_alignment`]).
+///
+/// It is carried inside [`io::Error`] of kind [`io::ErrorKind::InvalidInput`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MisalignedWrite {
+    /// First sector of the write
+    pub lba: u64,
+    /// Number of sectors of the write
+    pub sectors: u64,
+    /// Number of logical sectors per physical sector
+    pub logical_per_physical: u16,
+}
+
+impl MisalignedWrite {
+    /// Check if a write of `sectors` starting at `lba` covers whole physical sectors of
+    /// `logical_per_physical` logical ones, logical sector 0 being at `offset` within the first
+    /// one
+    fn check(lba: u64, sectors: u64, logical_per_physical: u16, offset: u16) -> Option<Self> {
+        let lps = logical_per_physical as u64;
+        let aligned = |lba: u64| (lba + offset as u64).is_multiple_of(lps);
+
+        match aligned(lba) && aligned(lba + sectors) {
+            true => None,
+            false => Some(MisalignedWrite {
+                lba,
+                sectors,
+                logical_per_physical,
+            }),
+        }
+    }
+}
+
+impl fmt::Display for MisalignedWrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "write of {} sectors at LBA {} is not aligned to physical sectors of {} logical ones",
+            self.sectors, self.lba, self.logical_per_physical
+        )
+    }
+}
+
+impl Error for MisalignedWrite {}
+
+impl Device {
+    /// Enable or disable strict alignment mode.
+    ///
+    /// On drives with physical sectors larger than logical ones, writes not starting or ending
+    /// on a physical sector boundary force the drive to read-modify-write. They are logged at
+    /// `warn` level, in strict mode they fail with [`MisalignedWrite`] instead, before reaching
+    /// the drive. Default is disabled.
+    pub fn set_strict_alignment(&mut self, enabled: bool) {
+        self.strict_alignment = enabled;
+    }
+
+    /// Report a write of `sectors` starting at `lba` not aligned to physical sectors
+    pub(crate) fn check_alignment(&self, lba: u64, sectors: u64) -> io::Result<()> {
+        let id = match self.ident {
+            Some(id) if id.get_logical_per_physical() > 1 => id,
+            _ => return Ok(()),
+        };
+
+        let misaligned = match MisalignedWrite::check(
+            lba,
+            sectors,
+            id.get_logical_per_physical(),
+            id.get_alignment_offset(),
+        ) {
+            Some(misaligned) => misaligned,
+            None => return Ok(()),
+        };
+
+        if self.strict_alignment {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, misaligned));
+        }
+        log::warn!("{}: {}", self.path.display(), misaligned);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_misaligned_write() {
+        assert_eq!(MisalignedWrite::check(8, 16, 8, 0), None);
+        assert_eq!(MisalignedWrite::check(7, 8, 8, 1), None);
+        assert!(MisalignedWrite::check(1, 8, 8, 0).is_some());
+        assert!(MisalignedWrite::check(8, 1, 8, 0).is_some());
+        assert!(MisalignedWrite::check(8, 8, 8, 1).is_some());
+    }
+}
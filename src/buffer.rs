@@ -0,0 +1,59 @@
+//! Exchange of data with the sector buffer of the drive, without touching media.
+
+use std::io;
+
+use crate::{os, AtaFeature, Device, RawAta, TaskFile, Transfer, ATA_DEV_LBA};
+
+const ATA_READ_BUFFER: u8 = 0xE4;
+const ATA_WRITE_BUFFER: u8 = 0xE8;
+
+impl Device {
+    /// Read the 512-byte sector buffer of the drive using `READ BUFFER` (ATA cmd 0xE4).
+    ///
+    /// Together with [`Device::write_buffer`] it tests the data path between host and drive,
+    /// like cables and bridges: a pattern written to the buffer should be read back unchanged.
+    /// Drives not supporting it (identify word 82 bit 13) are reported with an error.
+    pub fn read_buffer(&mut self) -> io::Result<[u8; 512]> {
+        self.check_feature(AtaFeature::ReadBuffer, "READ BUFFER")?;
+
+        let tf = TaskFile {
+            command: ATA_READ_BUFFER,
+            count: 1,
+            device: ATA_DEV_LBA,
+            ..Default::default()
+        };
+        let mut data = [0u8; os::SECTOR_BYTES];
+
+        self.retry(|ata| ata.raw_command(&tf, Transfer::PioIn(&mut data)))?;
+        Ok(data)
+    }
+
+    /// Write the 512-byte sector buffer of the drive using `WRITE BUFFER` (ATA cmd 0xE8).
+    ///
+    /// Media is not written, so it is allowed in dry-run mode. Drives not supporting it
+    /// (identify word 82 bit 12) are reported with an error.
+    pub fn write_buffer(&mut self, data: &[u8; 512]) -> io::Result<()> {
+        self.check_feature(AtaFeature::WriteBuffer, "WRITE BUFFER")?;
+
+        let tf = TaskFile {
+            command: ATA_WRITE_BUFFER,
+            count: 1,
+            device: ATA_DEV_LBA,
+            ..Default::default()
+        };
+
+        self.retry(|ata| ata.raw_command(&tf, Transfer::PioOut(data)))?;
+        Ok(())
+    }
+
+    /// Report `name` as unsupported if the cached identification says so
+    fn check_feature(&self, feature: AtaFeature, name: &str) -> io::Result<()> {
+        match self.ident {
+            Some(id) if !id.supports(feature) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{} not supported", name),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
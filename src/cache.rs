@@ -0,0 +1,170 @@
+//! Opt-in sector cache for consumers doing many small, overlapping reads.
+//!
+//! [`Device`] itself never caches anything, [`CachedReader`] has to be created explicitly.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+
+use crate::{os, Device, Lba};
+
+type Sector = [u8; os::SECTOR_BYTES];
+
+/// [`Device`] wrapper keeping an LRU cache of recently read sectors.
+///
+/// Useful when the same sectors are read over and over, like superblocks and bitmaps when walking
+/// filesystem metadata. Each read is served from the cache where possible, and the missing sectors
+/// are read from the drive in as few commands as possible. Any write through the wrapper drops the
+/// whole cache.
+///
+/// Data written to the drive by other means (another handle, [`Device::execute`]) is not noticed,
+/// call [`CachedReader::invalidate`] then.
+pub struct CachedReader {
+    device: Device,
+    cache: Lru,
+}
+
+impl CachedReader {
+    /// Wrap `device` with a cache holding at most `capacity` sectors
+    pub fn new(device: Device, capacity: usize) -> Self {
+        CachedReader {
+            device,
+            cache: Lru::new(capacity),
+        }
+    }
+
+    /// Read sector(s), using cached copies where available.
+    ///
+    /// Buffer size **must** be multiple of sector size.
+    pub fn read(&mut self, sector: impl Into<Lba>, buffer: &mut [u8]) -> io::Result<()> {
+        let sector = sector.into().sector();
+        let count = (buffer.len() / os::SECTOR_BYTES) as u64;
+        self.device.validate_range(sector, count)?;
+
+        let mut i = 0;
+        while i < count {
+            let offset = i as usize * os::SECTOR_BYTES;
+            if let Some(cached) = self.cache.get(sector + i) {
+                buffer[offset..offset + os::SECTOR_BYTES].copy_from_slice(cached);
+                i += 1;
+                continue;
+            }
+
+            // Read the whole run of consecutive missing sectors with one command
+            let mut end = i + 1;
+            while end < count && !self.cache.contains(sector + end) {
+                end += 1;
+            }
+            let run = &mut buffer[offset..end as usize * os::SECTOR_BYTES];
+            self.device.read(sector + i, run)?;
+
+            for (j, data) in run.chunks_exact(os::SECTOR_BYTES).enumerate() {
+                let mut copy = [0u8; os::SECTOR_BYTES];
+                copy.copy_from_slice(data);
+                self.cache.insert(sector + i + j as u64, copy);
+            }
+            i = end;
+        }
+        Ok(())
+    }
+
+    /// Write sector(s) with [`Device::write`], invalidating the whole cache first
+    pub fn write(&mut self, sector: impl Into<Lba>, buffer: &[u8]) -> io::Result<()> {
+        self.invalidate();
+        self.device.write(sector, buffer)
+    }
+
+    /// Drop all cached sectors
+    pub fn invalidate(&mut self) {
+        self.cache.clear();
+    }
+
+    /// Return the wrapped device
+    pub fn get_ref(&self) -> &Device {
+        &self.device
+    }
+
+    /// Unwrap the device, dropping the cache
+    pub fn into_inner(self) -> Device {
+        self.device
+    }
+}
+
+/// Least recently used sector map. Recency is tracked with a monotonic counter, `order` maps it
+/// back to the sector so the oldest entry is found in logarithmic time.
+struct Lru {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<u64, (u64, Box<Sector>)>,
+    order: BTreeMap<u64, u64>,
+}
+
+impl Lru {
+    fn new(capacity: usize) -> Self {
+        Lru {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+        }
+    }
+
+    fn contains(&self, lba: u64) -> bool {
+        self.entries.contains_key(&lba)
+    }
+
+    fn get(&mut self, lba: u64) -> Option<&Sector> {
+        let (used, data) = self.entries.get_mut(&lba)?;
+        self.order.remove(used);
+        self.tick += 1;
+        *used = self.tick;
+        self.order.insert(self.tick, lba);
+        Some(data)
+    }
+
+    fn insert(&mut self, lba: u64, data: Sector) {
+        if self.capacity == 0 {
+            return;
+        }
+        if let Some((used, _)) = self.entries.remove(&lba) {
+            self.order.remove(&used);
+        }
+        while self.entries.len() >= self.capacity {
+            match self.order.pop_first() {
+                Some((_, oldest)) => self.entries.remove(&oldest),
+                None => break,
+            };
+        }
+        self.tick += 1;
+        self.entries.insert(lba, (self.tick, Box::new(data)));
+        self.order.insert(self.tick, lba);
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_lru_eviction() {
+        let sector = |b| [b; os::SECTOR_BYTES];
+        let mut lru = Lru::new(2);
+
+        lru.insert(1, sector(1));
+        lru.insert(2, sector(2));
+        assert!(lru.get(1).is_some());
+
+        // Sector 2 is the least recently used one now
+        lru.insert(3, sector(3));
+        assert!(!lru.contains(2));
+        assert_eq!(lru.get(1).map(|s| s[0]), Some(1));
+        assert_eq!(lru.get(3).map(|s| s[0]), Some(3));
+
+        lru.clear();
+        assert!(!lru.contains(1));
+    }
+}
@@ -0,0 +1,318 @@
+//! Generic ATA command machinery shared by all the named helpers of [`Device`](crate::Device).
+
+use std::{error::Error, fmt, io, time::Duration};
+
+use crate::{os, ATA_DEV_LBA};
+
+/// Input registers of a single ATA command.
+///
+/// For 28-bit commands (`ext == false`) bits 24..27 of `lba` are moved to the `device` register
+/// by the backend. Commands without `timeout` use the backend default, suitable for regular
+/// reads and writes. `multiple` is the number of sectors per data block of `READ/WRITE MULTIPLE`
+/// commands, zero for all other ones.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct TaskFile {
+    pub command: u8,
+    pub features: u16,
+    pub count: u16,
+    pub lba: u64,
+    pub device: u8,
+    pub ext: bool,
+    pub multiple: u8,
+    pub timeout: Option<Duration>,
+}
+
+/// Data phase of a single ATA command, including transfer protocol and direction.
+///
+/// Buffer size **must** match sector count given in [`TaskFile`].
+pub(crate) enum Transfer<'a> {
+    NonData,
+    PioIn(&'a mut [u8]),
+    PioOut(&'a [u8]),
+    DmaIn(&'a mut [u8]),
+    DmaOut(&'a [u8]),
+}
+
+/// Output registers of a completed ATA command.
+///
+/// For 48-bit commands `count` and `lba` contain both current and previous (`_exp`) register
+/// contents, for 28-bit commands only lower bits are meaningful.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct AtaTaskfileOut {
+    /// Status register
+    pub status: u8,
+    /// Error register
+    pub error: u8,
+    /// Sector count register
+    pub count: u16,
+    /// LBA registers
+    pub lba: u64,
+    /// Device register
+    pub device: u8,
+}
+
+/// Data transfer protocol of an ATA command.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    /// Command without data phase
+    NonData,
+    /// Data transferred using PIO
+    Pio,
+    /// Data transferred using DMA
+    Dma,
+}
+
+/// Direction of data transfer of an ATA command.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// No data transferred
+    None,
+    /// From device to host
+    In,
+    /// From host to device
+    Out,
+}
+
+/// Builder of an arbitrary ATA command, executed with [`Device::execute`](crate::Device::execute).
+///
+/// ```no_run
+/// # use pakr_rawata::{AtaCommand, Device, Direction, Protocol};
+/// # fn main() -> std::io::Result<()> {
+/// let mut dev = Device::open("/dev/sda")?;
+///
+/// // CHECK POWER MODE, answer is returned in sector count register
+/// let ans = dev.execute(AtaCommand::new(0xE5))?;
+/// println!("power mode {:#04x}", ans.registers.count);
+///
+/// // READ LOG EXT of the General Purpose Log directory
+/// let ans = dev.execute(
+///     AtaCommand::new(0x2F)
+///         .ext(true)
+///         .count(1)
+///         .protocol(Protocol::Pio)
+///         .direction(Direction::In)
+///         .data(vec![0; 512]),
+/// )?;
+/// println!("GPL version {}", ans.data[0]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct AtaCommand {
+    pub(crate) tf: TaskFile,
+    protocol: Protocol,
+    direction: Direction,
+    data: Vec<u8>,
+}
+
+impl AtaCommand {
+    /// Start building a 28-bit, non-data command with given command code. Device register is
+    /// preset to LBA mode, all other registers are zero.
+    pub fn new(command: u8) -> Self {
+        AtaCommand {
+            tf: TaskFile {
+                command,
+                device: ATA_DEV_LBA,
+                ..Default::default()
+            },
+            protocol: Protocol::NonData,
+            direction: Direction::None,
+            data: Vec::new(),
+        }
+    }
+
+    /// Set features register
+    pub fn feature(mut self, features: u16) -> Self {
+        self.tf.features = features;
+        self
+    }
+
+    /// Set LBA registers
+    pub fn lba(mut self, lba: u64) -> Self {
+        self.tf.lba = lba;
+        self
+    }
+
+    /// Set sector count register
+    pub fn count(mut self, count: u16) -> Self {
+        self.tf.count = count;
+        self
+    }
+
+    /// Set device register
+    pub fn device(mut self, device: u8) -> Self {
+        self.tf.device = device;
+        self
+    }
+
+    /// Mark command as 48-bit (`_EXT`) one
+    pub fn ext(mut self, ext: bool) -> Self {
+        self.tf.ext = ext;
+        self
+    }
+
+    /// Set command timeout, overriding the default suitable for regular reads and writes
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.tf.timeout = Some(timeout);
+        self
+    }
+
+    /// Set data transfer protocol
+    pub fn protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Set data transfer direction
+    pub fn direction(mut self, direction: Direction) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    /// Set data buffer. For [`Direction::In`] it only needs to be of a proper size, for
+    /// [`Direction::Out`] it contains data sent to device. Size **must** be multiple of sector
+    /// size.
+    pub fn data(mut self, data: Vec<u8>) -> Self {
+        self.data = data;
+        self
+    }
+
+    /// Check consistency of the command and split it into parts understood by backends
+    pub(crate) fn prepare(&mut self) -> io::Result<(TaskFile, Transfer<'_>)> {
+        let len = self.data.len();
+
+        if (self.direction == Direction::None) != (len == 0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "data buffer does not match data direction",
+            ));
+        }
+
+        if !len.is_multiple_of(os::SECTOR_BYTES) || len > os::MAX_TRANSFER_BYTES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "data buffer size is not a multiple of sector size or is too large",
+            ));
+        }
+
+        let transfer = match (self.protocol, self.direction) {
+            (Protocol::NonData, Direction::None) => Transfer::NonData,
+            (Protocol::Pio, Direction::In) => Transfer::PioIn(&mut self.data),
+            (Protocol::Pio, Direction::Out) => Transfer::PioOut(&self.data),
+            (Protocol::Dma, Direction::In) => Transfer::DmaIn(&mut self.data),
+            (Protocol::Dma, Direction::Out) => Transfer::DmaOut(&self.data),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "protocol does not match data direction",
+                ))
+            }
+        };
+
+        Ok((self.tf, transfer))
+    }
+
+    /// Extract data buffer of executed command
+    pub(crate) fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// Result of a command executed with [`Device::execute`](crate::Device::execute).
+#[derive(Clone, Debug)]
+pub struct AtaResult {
+    /// Output registers
+    pub registers: AtaTaskfileOut,
+    /// Data buffer, filled by device for [`Direction::In`]
+    pub data: Vec<u8>,
+}
+
+/// Error of a sector read or write, naming the failed command and its range.
+///
+/// It is carried inside [`io::Error`] of the same kind as the underlying one, which is available
+/// as its [`Error::source`]. Callers get it with
+/// `err.get_ref().and_then(|e| e.downcast_ref::<CommandError>())`.
+#[derive(Debug)]
+pub struct CommandError {
+    /// Name of the command, like `READ DMA EXT`
+    pub name: &'static str,
+    /// Command code
+    pub command: u8,
+    /// First sector of the transfer
+    pub lba: u64,
+    /// Number of sectors of the transfer
+    pub sectors: u32,
+    source: io::Error,
+}
+
+impl CommandError {
+    /// Wrap error of the command described by `tf` in an [`io::Error`] of the same kind
+    pub(crate) fn wrap(source: io::Error, name: &'static str, tf: &TaskFile) -> io::Error {
+        let kind = source.kind();
+        let sectors = match tf.count {
+            0 => 65_536,
+            count => count as u32,
+        };
+
+        io::Error::new(
+            kind,
+            CommandError {
+                name,
+                command: tf.command,
+                lba: tf.lba,
+                sectors,
+                source,
+            },
+        )
+    }
+
+    /// Return the underlying error if `err` carries a [`CommandError`], `err` itself otherwise
+    pub(crate) fn peel(err: &io::Error) -> &io::Error {
+        match err.get_ref().and_then(|e| e.downcast_ref::<CommandError>()) {
+            Some(ctx) => &ctx.source,
+            None => err,
+        }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({:#04x}) of {} sectors at LBA {} failed: {}",
+            self.name, self.command, self.sectors, self.lba, self.source
+        )
+    }
+}
+
+impl Error for CommandError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_command_error() {
+        let tf = TaskFile {
+            command: 0x25,
+            lba: 1234,
+            ..Default::default()
+        };
+        let err = CommandError::wrap(
+            io::Error::new(io::ErrorKind::TimedOut, "timeout"),
+            "READ DMA EXT",
+            &tf,
+        );
+
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(
+            err.to_string(),
+            "READ DMA EXT (0x25) of 65536 sectors at LBA 1234 failed: timeout"
+        );
+        assert_eq!(CommandError::peel(&err).to_string(), "timeout");
+    }
+}
@@ -0,0 +1,48 @@
+//! Adoption of devices opened elsewhere through raw file descriptors, on Linux.
+//!
+//! There is no FreeBSD equivalent: commands go through a CAM `pass` device opened by libcam,
+//! not through the descriptor of the disk, so a descriptor received from a broker grants no
+//! access by itself.
+
+use std::{
+    fs,
+    os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd},
+    path::PathBuf,
+};
+
+use crate::{os, Device};
+
+impl FromRawFd for Device {
+    /// Wrap a device already opened by the caller, like one received from a privileged broker
+    /// over a unix socket.
+    ///
+    /// Path is recovered from `/proc/self/fd`, it is used by [`Device::reopen`] and by queries
+    /// of `/sys`. Device is treated as opened without `O_EXCL`. Identification record is read
+    /// like when opening by path. The descriptor is owned by the device from now on and closed
+    /// with it.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be an open descriptor of an `sd` or `sg` device not owned by anything else.
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        let link = PathBuf::from(format!("/proc/self/fd/{}", fd));
+        let path = fs::read_link(&link).unwrap_or(link);
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+        let direct = flags >= 0 && flags & libc::O_DIRECT != 0;
+
+        Device::with_ata(os::ATA::from_fd(fd), path, false, direct)
+    }
+}
+
+impl AsRawFd for Device {
+    fn as_raw_fd(&self) -> RawFd {
+        self.ata.as_raw_fd()
+    }
+}
+
+impl IntoRawFd for Device {
+    /// Release the descriptor without closing it
+    fn into_raw_fd(mut self) -> RawFd {
+        self.ata.release_fd()
+    }
+}
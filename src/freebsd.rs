@@ -3,14 +3,15 @@
 
 use std::{
     ffi::CString,
-    io::{self, Error, ErrorKind},
+    fs,
+    io::{self, Error, ErrorKind, IoSliceMut},
     mem,
     os::raw::c_char,
-    path::{self, Path},
+    path::{self, Path, PathBuf},
     ptr,
 };
 
-use crate::RawAta;
+use crate::{AtaTaskfileOut, CommandError, Direction, RawAta, TaskFile, Transfer, Transport};
 
 mod camlib {
     #![allow(clippy::unreadable_literal)]
@@ -37,13 +38,199 @@ mod camlib {
 pub const SECTOR_BYTES: usize = 512;
 pub const MAX_TRANSFER_SECTORS: u64 = 65_536;
 pub const MAX_TRANSFER_BYTES: usize = MAX_TRANSFER_SECTORS as usize * SECTOR_BYTES;
+pub const LOCK_DIR: &str = "/var/run";
+
+/// Komunikat kolejkowania `SIMPLE QUEUE TAG` dla `XPT_SCSI_IO`
+const MSG_SIMPLE_Q_TAG: u8 = 0x20;
 
 pub(super) struct ATA {
     cam: *mut camlib::cam_device,
     ccb: *mut camlib::ccb,
+    retries: u8,
+    /// Deskryptor urządzenia GEOM otwartego z `O_EXCL`, trzymany dla blokady
+    /// wyłącznego dostępu, lub -1
+    geom: libc::c_int,
 }
 
+// Urządzenie i CCB należą wyłącznie do tego uchwytu, więc może on zostać
+// przeniesiony do innego wątku
+unsafe impl Send for ATA {}
+
 impl ATA {
+    /// Otwarcie urządzenia CAM danej jednostki i przydzielenie dla niego CCB
+    fn open_unit(name: *const c_char, unit: i32) -> io::Result<Self> {
+        let cam = unsafe {
+            camlib::cam_open_spec_device(name, unit, camlib::O_RDWR as i32, ptr::null_mut())
+        };
+        if cam.is_null() {
+            return Err(Error::last_os_error());
+        }
+
+        let ccb = unsafe { camlib::cam_getccb(cam) };
+        if ccb.is_null() {
+            unsafe { camlib::cam_close_spec_device(cam) };
+            return Err(Error::last_os_error());
+        }
+
+        Ok(ATA {
+            cam,
+            ccb,
+            retries: 1,
+            geom: -1,
+        })
+    }
+
+    /// Zgłoszenie błędu przy próbie użycia zamkniętego urządzenia
+    #[inline]
+    fn check_open(&self) -> io::Result<()> {
+        if self.cam.is_null() || self.ccb.is_null() {
+            return Err(Error::new(ErrorKind::NotConnected, "device is closed"));
+        }
+        Ok(())
+    }
+
+    /// Odczyt rejestrów wyjściowych ostatnio wykonanej komendy
+    #[inline]
+    fn result_registers(&self) -> AtaTaskfileOut {
+        let res = unsafe { &(*self.ccb).ataio.res };
+        AtaTaskfileOut {
+            status: res.status,
+            error: res.error,
+            count: (res.sector_count_exp as u16) << 8 | res.sector_count as u16,
+            lba: (res.lba_low as u64) << 0
+                | (res.lba_mid as u64) << 8
+                | (res.lba_high as u64) << 16
+                | (res.lba_low_exp as u64) << 24
+                | (res.lba_mid_exp as u64) << 32
+                | (res.lba_high_exp as u64) << 40,
+            device: res.device,
+        }
+    }
+
+    /// Komendy ATA w CAM zwracają rejestry w CCB, a nie w danych sense
+    pub(super) fn set_sense_len(&mut self, _len: u8) {}
+
+    /// CAM nie używa buforów jądra, nie ma czego zrzucać
+    pub(super) fn sync(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Magistrala, przez którą podłączony jest dysk, odczytana z CCB
+    /// `XPT_PATH_INQ` ścieżki CAM
+    pub(super) fn transport(&mut self, _path: &Path) -> Option<Transport> {
+        self.check_open().ok()?;
+        self.ccb_clear_all_except_hdr();
+
+        unsafe {
+            (*self.ccb).ccb_h.func_code = camlib::xpt_opcode_XPT_PATH_INQ;
+        }
+        let rc = unsafe { camlib::cam_send_ccb(self.cam, self.ccb) };
+        if rc < 0 {
+            return None;
+        }
+
+        match unsafe { (*self.ccb).cpi.transport } {
+            camlib::cam_xport_XPORT_ATA => Some(Transport::Pata),
+            camlib::cam_xport_XPORT_SATA => Some(Transport::Sata),
+            camlib::cam_xport_XPORT_USB => Some(Transport::Usb),
+            _ => None,
+        }
+    }
+
+    /// Reset urządzenia przez CCB `XPT_RESET_DEV` ścieżki CAM
+    pub(super) fn reset(&mut self) -> io::Result<()> {
+        self.check_open()?;
+        self.ccb_clear_all_except_hdr();
+
+        unsafe {
+            (*self.ccb).ccb_h.func_code = camlib::xpt_opcode_XPT_RESET_DEV;
+            (*self.ccb).ccb_h.flags = camlib::ccb_flags_CAM_DIR_NONE;
+            (*self.ccb).ccb_h.timeout = 30_000;
+        }
+        let rc = unsafe { camlib::cam_send_ccb(self.cam, self.ccb) };
+        if rc < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        let status = unsafe { (*self.ccb).ccb_h.status } & camlib::cam_status_CAM_STATUS_MASK;
+        if status != camlib::cam_status_CAM_REQ_CMP {
+            return Err(Error::other(format!(
+                "device reset failed, CAM status {:#x}",
+                status
+            )));
+        }
+        Ok(())
+    }
+
+    /// Wykonanie dowolnej komendy SCSI przez CCB `XPT_SCSI_IO`, bez ponowień.
+    /// Zwracany jest bufor sense (wyzerowany, jeśli komenda zakończyła się bez
+    /// niego), błędem są tylko niepowodzenia wywołania systemowego i CAM.
+    pub(super) fn scsi_command(
+        &mut self,
+        cdb: &[u8],
+        direction: Direction,
+        buffer: &mut [u8],
+        timeout_ms: u32,
+    ) -> io::Result<[u8; 32]> {
+        let mut sb = [0u8; 32];
+
+        let (dir, data_ptr, len) = match direction {
+            Direction::None => (camlib::ccb_flags_CAM_DIR_NONE, ptr::null_mut(), 0),
+            Direction::In => (
+                camlib::ccb_flags_CAM_DIR_IN,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+            ),
+            Direction::Out => (
+                camlib::ccb_flags_CAM_DIR_OUT,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+            ),
+        };
+
+        self.check_open()?;
+        self.ccb_clear_all_except_hdr();
+
+        unsafe {
+            let csio = &mut (*self.ccb).csio;
+            csio.ccb_h.func_code = camlib::xpt_opcode_XPT_SCSI_IO;
+            csio.ccb_h.flags = dir | camlib::ccb_flags_CAM_DEV_QFRZDIS;
+            csio.ccb_h.retry_count = 0;
+            csio.ccb_h.cbfcnp = None;
+            csio.ccb_h.timeout = timeout_ms;
+
+            csio.data_ptr = data_ptr;
+            csio.dxfer_len = len as u32;
+            csio.sense_len = mem::size_of_val(&csio.sense_data) as u8;
+            csio.cdb_len = cdb.len() as u8;
+            csio.cdb_io.cdb_bytes[..cdb.len()].copy_from_slice(cdb);
+            csio.tag_action = MSG_SIMPLE_Q_TAG;
+        }
+        let rc = unsafe { camlib::cam_send_ccb(self.cam, self.ccb) };
+        if rc < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        let status = unsafe { (*self.ccb).ccb_h.status };
+        match status & camlib::cam_status_CAM_STATUS_MASK {
+            camlib::cam_status_CAM_REQ_CMP => Ok(sb),
+            camlib::cam_status_CAM_SCSI_STATUS_ERROR
+                if status & camlib::cam_status_CAM_AUTOSNS_VALID != 0 =>
+            {
+                // Długość sense to zażądana minus niewypełniona reszta
+                let csio = unsafe { &(*self.ccb).csio };
+                let valid = csio.sense_len.saturating_sub(csio.sense_resid) as usize;
+                let n = valid.min(sb.len());
+                sb[..n].copy_from_slice(&csio.sense_data.data[..n]);
+                Ok(sb)
+            }
+            other => Err(Error::other(format!(
+                "SCSI command failed, CAM status {:#x}",
+                other
+            ))),
+        }
+    }
+
     #[inline]
     fn ccb_clear_all_except_hdr(&mut self) {
         const CCB_S: usize = mem::size_of::<camlib::ccb>();
@@ -58,7 +245,7 @@ impl ATA {
 }
 
 impl RawAta for ATA {
-    fn open<P>(dev: P) -> io::Result<Self>
+    fn open<P>(dev: P, exclusive: bool, _direct: bool) -> io::Result<Self>
     where
         P: AsRef<Path>,
     {
@@ -72,63 +259,138 @@ impl RawAta for ATA {
             return Err(Error::last_os_error());
         }
 
-        let cam = unsafe {
-            camlib::cam_open_spec_device(
-                name.as_ptr(),
-                unit,
-                camlib::O_RDWR as i32,
-                ptr::null_mut(),
-            )
+        // CAM nie zna wyłącznego dostępu, a GEOM ignoruje O_EXCL. Otwarcie
+        // dysku do zapisu zajmuje jednak licznik zapisu dostawcy GEOM: nie uda
+        // się, jeśli dysk jest zamontowany lub należy do gmirror/ZFS (licznik
+        // wyłączności), a do zamknięcia blokuje montowanie
+        let geom = if exclusive {
+            geom_open_rw(&cdev).map_err(|err| match err.raw_os_error() {
+                Some(libc::EPERM) | Some(libc::EBUSY) => Error::new(
+                    ErrorKind::ResourceBusy,
+                    format!("{}: disk is in use", dev.as_ref().display()),
+                ),
+                _ => err,
+            })?
+        } else {
+            -1
         };
-        if cam.is_null() {
-            return Err(Error::last_os_error());
-        }
 
-        let ccb = unsafe { camlib::cam_getccb(cam) };
-        if ccb.is_null() {
-            return Err(Error::last_os_error());
+        match Self::open_unit(name.as_ptr(), unit) {
+            Ok(mut ata) => {
+                ata.geom = geom;
+                Ok(ata)
+            }
+            Err(err) => {
+                if geom >= 0 {
+                    unsafe { libc::close(geom) };
+                }
+                Err(err)
+            }
         }
+    }
+
+    fn try_clone(&self) -> io::Result<Self> {
+        self.check_open()?;
+
+        // Nowe urządzenie CAM i CCB dla tej samej jednostki, niezależne od
+        // bieżącego. Blokadę GEOM trzyma tylko oryginał.
+        let (name, unit) = unsafe { ((*self.cam).given_dev_name, (*self.cam).given_unit_number) };
+        Self::open_unit(name.as_ptr(), unit as i32)
+    }
 
-        Ok(ATA { cam, ccb })
+    fn set_retries(&mut self, n: u8) {
+        self.retries = n;
     }
 
-    fn close(&mut self) {
+    fn close(&mut self) -> io::Result<()> {
         if !self.ccb.is_null() {
             unsafe {
                 camlib::cam_freeccb(self.ccb);
             }
+            self.ccb = ptr::null_mut();
         }
 
         if !self.cam.is_null() {
             unsafe {
                 camlib::cam_close_spec_device(self.cam);
             }
+            self.cam = ptr::null_mut();
         }
+
+        if self.geom >= 0 {
+            let ans = unsafe { libc::close(self.geom) };
+            self.geom = -1;
+            if ans < 0 {
+                return Err(Error::last_os_error());
+            }
+        }
+
+        // Ani `cam_freeccb`, ani `cam_close_spec_device` nie zgłaszają błędów
+        Ok(())
     }
 
-    fn raw_read(&mut self, sector: u64, buffer: &mut [u8]) -> io::Result<()> {
-        #![allow(unused_parens)]
+    fn raw_read(&mut self, sector: u64, buffer: &mut [u8]) -> io::Result<AtaTaskfileOut> {
+        let tf = TaskFile {
+            command: camlib::ATA_READ_DMA48 as u8,
+            count: (buffer.len() / SECTOR_BYTES) as u16,
+            lba: sector,
+            device: camlib::ATA_DEV_LBA as u8,
+            ext: true,
+            ..Default::default()
+        };
 
-        let len = buffer.len();
+        self.raw_command(&tf, Transfer::DmaIn(buffer))
+            .map_err(|err| CommandError::wrap(err, "READ DMA EXT", &tf))
+    }
 
-        debug_assert!(len >= SECTOR_BYTES && len <= MAX_TRANSFER_BYTES);
-        debug_assert!(len % SECTOR_BYTES == 0);
+    fn raw_write(&mut self, sector: u64, buffer: &[u8]) -> io::Result<AtaTaskfileOut> {
+        let tf = TaskFile {
+            command: camlib::ATA_WRITE_DMA48 as u8,
+            count: (buffer.len() / SECTOR_BYTES) as u16,
+            lba: sector,
+            device: camlib::ATA_DEV_LBA as u8,
+            ext: true,
+            ..Default::default()
+        };
 
+        self.raw_command(&tf, Transfer::DmaOut(buffer))
+            .map_err(|err| CommandError::wrap(err, "WRITE DMA EXT", &tf))
+    }
+
+    fn raw_read_vectored(
+        &mut self,
+        sector: u64,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> io::Result<AtaTaskfileOut> {
+        // Przekazanie listy segmentów (`CAM_DATA_SG`) przez pass(4) nie jest
+        // obsługiwane przez wszystkie kontrolery, dane są więc czytane do
+        // jednego bufora i rozdzielane po odczycie
+        let len = bufs.iter().map(|buf| buf.len()).sum();
+        let mut buffer = vec![0u8; len];
+        let out = self.raw_read(sector, &mut buffer)?;
+
+        let mut rest = &buffer[..];
+        for buf in bufs.iter_mut() {
+            let (head, tail) = rest.split_at(buf.len());
+            buf.copy_from_slice(head);
+            rest = tail;
+        }
+
+        Ok(out)
+    }
+
+    fn raw_info(&mut self, ident: *mut super::IdentifyDeviceData) -> io::Result<AtaTaskfileOut> {
+        #![allow(unused_parens)]
+
+        self.check_open()?;
         self.ccb_clear_all_except_hdr();
 
         unsafe {
-            (*self.ccb).ataio.cmd.command = camlib::ATA_READ_DMA48 as u8;
-            (*self.ccb).ataio.cmd.flags = (camlib::CAM_ATAIO_NEEDRESULT
-                | camlib::CAM_ATAIO_DMA
-                | camlib::CAM_ATAIO_48BIT) as u8;
-            (*self.ccb).ataio.cmd.sector_count = (len / 512) as u8;
-            (*self.ccb).ataio.cmd.sector_count_exp = ((len / 512) >> 8) as u8;
-            (*self.ccb).ataio.cmd.lba_low = (sector) as u8;
-            (*self.ccb).ataio.cmd.lba_mid = (sector >> 8) as u8;
-            (*self.ccb).ataio.cmd.lba_high = (sector >> 16) as u8;
-            (*self.ccb).ataio.cmd.lba_low_exp = (sector >> 24) as u8;
-            (*self.ccb).ataio.cmd.lba_mid_exp = (sector >> 32) as u8;
-            (*self.ccb).ataio.cmd.lba_high_exp = (sector >> 40) as u8;
+            (*self.ccb).ataio.cmd.command = camlib::ATA_ATA_IDENTIFY as u8;
+            (*self.ccb).ataio.cmd.flags =
+                (camlib::CAM_ATAIO_NEEDRESULT | camlib::CAM_ATAIO_DMA) as u8;
+            (*self.ccb).ataio.cmd.sector_count = 1;
+            (*self.ccb).ataio.cmd.sector_count_exp = 0;
             (*self.ccb).ataio.cmd.device = camlib::ATA_DEV_LBA as u8;
             (*self.ccb).ataio.cmd.control = 0;
             (*self.ccb).ataio.cmd.features_exp = 0;
@@ -137,12 +399,12 @@ impl RawAta for ATA {
             (*self.ccb).ataio.ccb_h.func_code = camlib::xpt_opcode_XPT_ATA_IO;
             (*self.ccb).ataio.ccb_h.flags =
                 camlib::ccb_flags_CAM_DIR_IN | camlib::ccb_flags_CAM_DEV_QFRZDIS;
-            (*self.ccb).ataio.ccb_h.retry_count = 1;
+            (*self.ccb).ataio.ccb_h.retry_count = self.retries as u32;
             (*self.ccb).ataio.ccb_h.cbfcnp = None;
             (*self.ccb).ataio.ccb_h.timeout = 5000;
 
-            (*self.ccb).ataio.data_ptr = buffer.as_mut_ptr();
-            (*self.ccb).ataio.dxfer_len = len as u32;
+            (*self.ccb).ataio.data_ptr = ident as *mut super::IdentifyDeviceData as *mut u8;
+            (*self.ccb).ataio.dxfer_len = 512;
             (*self.ccb).ataio.ata_flags = 0;
         }
         let rc = unsafe { camlib::cam_send_ccb(self.cam, self.ccb) };
@@ -154,45 +416,82 @@ impl RawAta for ATA {
             return Err(Error::new(ErrorKind::InvalidData, "CCB execute failed"));
         }
 
-        Ok(())
+        Ok(self.result_registers())
     }
 
-    fn raw_write(&mut self, sector: u64, buffer: &[u8]) -> io::Result<()> {
+    fn raw_command(&mut self, tf: &TaskFile, data: Transfer<'_>) -> io::Result<AtaTaskfileOut> {
         #![allow(unused_parens)]
 
-        let len = buffer.len();
+        let (dma, dir, data_ptr, len) = match data {
+            Transfer::NonData => (false, camlib::ccb_flags_CAM_DIR_NONE, ptr::null_mut(), 0),
+            Transfer::PioIn(buffer) => (
+                false,
+                camlib::ccb_flags_CAM_DIR_IN,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+            ),
+            Transfer::PioOut(buffer) => (
+                false,
+                camlib::ccb_flags_CAM_DIR_OUT,
+                buffer.as_ptr() as *mut u8,
+                buffer.len(),
+            ),
+            Transfer::DmaIn(buffer) => (
+                true,
+                camlib::ccb_flags_CAM_DIR_IN,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+            ),
+            Transfer::DmaOut(buffer) => (
+                true,
+                camlib::ccb_flags_CAM_DIR_OUT,
+                buffer.as_ptr() as *mut u8,
+                buffer.len(),
+            ),
+        };
 
-        debug_assert!(len >= SECTOR_BYTES && len <= MAX_TRANSFER_BYTES);
+        debug_assert!(len <= MAX_TRANSFER_BYTES);
         debug_assert!(len % SECTOR_BYTES == 0);
 
+        let mut flags = camlib::CAM_ATAIO_NEEDRESULT;
+        if dma {
+            flags |= camlib::CAM_ATAIO_DMA;
+        }
+
+        // Komendy 28-bitowe przenoszą bity 24..27 LBA w rejestrze urządzenia
+        let device = if tf.ext {
+            flags |= camlib::CAM_ATAIO_48BIT;
+            tf.device
+        } else {
+            tf.device | ((tf.lba >> 24) & 0x0F) as u8
+        };
+
+        self.check_open()?;
         self.ccb_clear_all_except_hdr();
 
         unsafe {
-            (*self.ccb).ataio.cmd.command = camlib::ATA_WRITE_DMA48 as u8;
-            (*self.ccb).ataio.cmd.flags = (camlib::CAM_ATAIO_NEEDRESULT
-                | camlib::CAM_ATAIO_DMA
-                | camlib::CAM_ATAIO_48BIT) as u8;
-            (*self.ccb).ataio.cmd.sector_count = (len / 512) as u8;
-            (*self.ccb).ataio.cmd.sector_count_exp = ((len / 512) >> 8) as u8;
-            (*self.ccb).ataio.cmd.lba_low = (sector) as u8;
-            (*self.ccb).ataio.cmd.lba_mid = (sector >> 8) as u8;
-            (*self.ccb).ataio.cmd.lba_high = (sector >> 16) as u8;
-            (*self.ccb).ataio.cmd.lba_low_exp = (sector >> 24) as u8;
-            (*self.ccb).ataio.cmd.lba_mid_exp = (sector >> 32) as u8;
-            (*self.ccb).ataio.cmd.lba_high_exp = (sector >> 40) as u8;
-            (*self.ccb).ataio.cmd.device = camlib::ATA_DEV_LBA as u8;
+            (*self.ccb).ataio.cmd.command = tf.command;
+            (*self.ccb).ataio.cmd.flags = flags as u8;
+            (*self.ccb).ataio.cmd.sector_count = (tf.count) as u8;
+            (*self.ccb).ataio.cmd.sector_count_exp = (tf.count >> 8) as u8;
+            (*self.ccb).ataio.cmd.lba_low = (tf.lba) as u8;
+            (*self.ccb).ataio.cmd.lba_mid = (tf.lba >> 8) as u8;
+            (*self.ccb).ataio.cmd.lba_high = (tf.lba >> 16) as u8;
+            (*self.ccb).ataio.cmd.lba_low_exp = (tf.lba >> 24) as u8;
+            (*self.ccb).ataio.cmd.lba_mid_exp = (tf.lba >> 32) as u8;
+            (*self.ccb).ataio.cmd.lba_high_exp = (tf.lba >> 40) as u8;
+            (*self.ccb).ataio.cmd.device = device;
             (*self.ccb).ataio.cmd.control = 0;
-            (*self.ccb).ataio.cmd.features_exp = 0;
-            (*self.ccb).ataio.cmd.features = 0;
+            (*self.ccb).ataio.cmd.features_exp = (tf.features >> 8) as u8;
+            (*self.ccb).ataio.cmd.features = (tf.features) as u8;
 
             (*self.ccb).ataio.ccb_h.func_code = camlib::xpt_opcode_XPT_ATA_IO;
-            (*self.ccb).ataio.ccb_h.flags =
-                camlib::ccb_flags_CAM_DIR_OUT | camlib::ccb_flags_CAM_DEV_QFRZDIS;
-            (*self.ccb).ataio.ccb_h.retry_count = 1;
+            (*self.ccb).ataio.ccb_h.flags = dir | camlib::ccb_flags_CAM_DEV_QFRZDIS;
+            (*self.ccb).ataio.ccb_h.retry_count = self.retries as u32;
             (*self.ccb).ataio.ccb_h.cbfcnp = None;
-            (*self.ccb).ataio.ccb_h.timeout = 5000;
+            (*self.ccb).ataio.ccb_h.timeout = tf.timeout.map_or(5000, |t| t.as_millis() as u32);
 
-            (*self.ccb).ataio.data_ptr = buffer.as_ptr() as *mut u8;
+            (*self.ccb).ataio.data_ptr = data_ptr;
             (*self.ccb).ataio.dxfer_len = len as u32;
             (*self.ccb).ataio.ata_flags = 0;
         }
@@ -205,51 +504,108 @@ impl RawAta for ATA {
             return Err(Error::new(ErrorKind::InvalidData, "CCB execute failed"));
         }
 
-        Ok(())
+        Ok(self.result_registers())
     }
+}
 
-    fn raw_info(&mut self, ident: *mut super::IdentifyDeviceData) -> io::Result<()> {
-        #![allow(unused_parens)]
-
-        self.ccb_clear_all_except_hdr();
+/// Maksymalny rozmiar pojedynczego transferu akceptowany przez jądro, czyli
+/// `MAXPHYS` odczytany z `kern.maxphys`
+pub(super) fn max_transfer_bytes(_path: &Path) -> Option<usize> {
+    let name = CString::new("kern.maxphys").ok()?;
+    let mut value: libc::c_ulong = 0;
+    let mut len = mem::size_of_val(&value);
+
+    let rc = unsafe {
+        libc::sysctlbyname(
+            name.as_ptr(),
+            &mut value as *mut libc::c_ulong as *mut libc::c_void,
+            &mut len,
+            ptr::null(),
+            0,
+        )
+    };
+    if rc < 0 {
+        return None;
+    }
 
-        unsafe {
-            (*self.ccb).ataio.cmd.command = camlib::ATA_ATA_IDENTIFY as u8;
-            (*self.ccb).ataio.cmd.flags =
-                (camlib::CAM_ATAIO_NEEDRESULT | camlib::CAM_ATAIO_DMA) as u8;
-            (*self.ccb).ataio.cmd.sector_count = 1;
-            (*self.ccb).ataio.cmd.sector_count_exp = 0;
-            (*self.ccb).ataio.cmd.device = camlib::ATA_DEV_LBA as u8;
-            (*self.ccb).ataio.cmd.control = 0;
-            (*self.ccb).ataio.cmd.features_exp = 0;
-            (*self.ccb).ataio.cmd.features = 0;
+    Some((value as usize).min(MAX_TRANSFER_BYTES))
+}
 
-            (*self.ccb).ataio.ccb_h.func_code = camlib::xpt_opcode_XPT_ATA_IO;
-            (*self.ccb).ataio.ccb_h.flags =
-                camlib::ccb_flags_CAM_DIR_IN | camlib::ccb_flags_CAM_DEV_QFRZDIS;
-            (*self.ccb).ataio.ccb_h.retry_count = 1;
-            (*self.ccb).ataio.ccb_h.cbfcnp = None;
-            (*self.ccb).ataio.ccb_h.timeout = 5000;
+/// GEOM nie udostępnia parametrów BIO_DELETE w prosty sposób, więc nie są
+/// raportowane
+pub(super) fn discard_limits(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
 
-            (*self.ccb).ataio.data_ptr = ident as *mut super::IdentifyDeviceData as *mut u8;
-            (*self.ccb).ataio.dxfer_len = 512;
-            (*self.ccb).ataio.ata_flags = 0;
-        }
-        let rc = unsafe { camlib::cam_send_ccb(self.cam, self.ccb) };
-        if rc < 0 {
-            return Err(Error::last_os_error());
+/// Ścieżki dysków, czyli urządzeń `adaN` (ATA) i `daN` (SCSI, w tym dyski
+/// SATA za kontrolerami SAS i mostkami USB) z `/dev`, bez partycji
+pub(super) fn disk_paths() -> io::Result<Vec<PathBuf>> {
+    let is_disk = |name: &str| {
+        ["ada", "da"].iter().any(|prefix| {
+            name.strip_prefix(prefix)
+                .is_some_and(|unit| !unit.is_empty() && unit.bytes().all(|b| b.is_ascii_digit()))
+        })
+    };
+
+    let mut paths = Vec::new();
+    for entry in fs::read_dir("/dev")? {
+        if let Ok(name) = entry?.file_name().into_string() {
+            if is_disk(&name) {
+                paths.push(Path::new("/dev").join(name));
+            }
         }
+    }
 
-        if unsafe { (*self.ccb).ataio.res.status & 0x01 != 0 } {
-            return Err(Error::new(ErrorKind::InvalidData, "CCB execute failed"));
+    paths.sort();
+    Ok(paths)
+}
+
+/// Czy dysk jest używany przez GEOM, np. zamontowany albo należący do
+/// `gmirror`/ZFS. Trzymają one licznik wyłączności dostawcy, więc GEOM
+/// odmawia wtedy otwarcia do zapisu (`EPERM`).
+pub(super) fn is_in_use(path: &Path) -> io::Result<bool> {
+    use std::os::unix::ffi::OsStrExt;
+    let name = CString::new(path.as_os_str().as_bytes())?;
+    match geom_open_rw(&name) {
+        Ok(fd) => {
+            unsafe { libc::close(fd) };
+            Ok(false)
         }
+        Err(err) => match err.raw_os_error() {
+            Some(libc::EPERM) | Some(libc::EBUSY) => Ok(true),
+            _ => Err(err),
+        },
+    }
+}
 
-        Ok(())
+/// Otwarcie węzła dysku GEOM do zapisu, zwiększające licznik zapisu dostawcy.
+/// GEOM odmawia go (`EPERM`), gdy ktoś trzyma licznik wyłączności, np.
+/// zamontowany system plików.
+fn geom_open_rw(name: &CString) -> io::Result<libc::c_int> {
+    let fd = unsafe { libc::open(name.as_ptr(), libc::O_RDWR) };
+    if fd < 0 {
+        return Err(Error::last_os_error());
     }
+    Ok(fd)
+}
+
+/// Czy błąd zgłosił napęd dla samej komendy (jak nieczytelny sektor), a nie
+/// system operacyjny lub biblioteka. Komendy zakończone statusem ERR zgłaszane
+/// są jako `InvalidData` bez kodu błędu systemowego.
+pub(super) fn is_command_error(err: &io::Error) -> bool {
+    let err = CommandError::peel(err);
+    err.kind() == ErrorKind::InvalidData && err.raw_os_error().is_none()
+}
+
+/// Czy błąd jest przejściowy i warto ponowić komendę. CAM sam ponawia
+/// komendy zgodnie z `retry_count`, więc tu zostają tylko przerwane wywołania
+/// systemowe.
+pub(super) fn is_transient(err: &io::Error) -> bool {
+    matches!(err.kind(), ErrorKind::Interrupted | ErrorKind::WouldBlock)
 }
 
 impl Drop for ATA {
     fn drop(&mut self) {
-        self.close();
+        let _ = self.close();
     }
 }
@@ -0,0 +1,241 @@
+//! Host Protected Area feature set (documentation of ATA8-ACS chapter 4.11) and Device
+//! Configuration Overlay (chapter 4.8).
+//!
+//! HPA hides sectors past the max address set by the host. The area may additionally be locked
+//! with a password using SET MAX security extensions. DCO hides sectors past the max address
+//! set by `DEVICE CONFIGURATION SET`, below which HPA operates.
+
+use std::io::{self, Write};
+
+use crate::{os, Device, RawAta, SectorMap, TaskFile, Transfer, ATA_DEV_LBA};
+
+const ATA_READ_NATIVE_MAX_ADDRESS: u8 = 0xF8;
+const ATA_READ_NATIVE_MAX_ADDRESS_EXT: u8 = 0x27;
+const ATA_SET_MAX: u8 = 0xF9;
+const ATA_SET_MAX_ADDRESS_EXT: u8 = 0x37;
+const ATA_DEVICE_CONFIGURATION: u8 = 0xB1;
+
+const DCO_IDENTIFY: u16 = 0xC2;
+
+const SET_MAX_ADDRESS: u16 = 0x00;
+const SET_MAX_SET_PASSWORD: u16 = 0x01;
+const SET_MAX_LOCK: u16 = 0x02;
+const SET_MAX_UNLOCK: u16 = 0x03;
+const SET_MAX_FREEZE_LOCK: u16 = 0x04;
+
+/// Bit of sector count of `SET MAX ADDRESS` making the new max address survive power cycles
+const SET_MAX_NON_VOLATILE: u16 = 1 << 0;
+
+impl Device {
+    /// Return native max address of the drive, that is its last sector regardless of HPA, using
+    /// `READ NATIVE MAX ADDRESS EXT` (ATA cmd 0x27) or `READ NATIVE MAX ADDRESS` (ATA cmd 0xF8)
+    /// for drives without 48-bit addressing.
+    pub fn read_native_max_address(&mut self) -> io::Result<u64> {
+        let lba48 = self.lba48();
+        let tf = TaskFile {
+            command: if lba48 {
+                ATA_READ_NATIVE_MAX_ADDRESS_EXT
+            } else {
+                ATA_READ_NATIVE_MAX_ADDRESS
+            },
+            device: ATA_DEV_LBA,
+            ext: lba48,
+            ..Default::default()
+        };
+
+        let out = self.retry(|ata| ata.raw_command(&tf, Transfer::NonData))?;
+        Ok(if lba48 {
+            out.lba
+        } else {
+            // Bits 24..27 are returned in the device register
+            out.lba & 0x00FF_FFFF | ((out.device & 0x0F) as u64) << 24
+        })
+    }
+
+    /// Set max address of the drive to `max_lba`, hiding sectors past it in HPA, using `SET MAX
+    /// ADDRESS EXT` (ATA cmd 0x37) or `SET MAX ADDRESS` (ATA cmd 0xF9).
+    ///
+    /// `READ NATIVE MAX ADDRESS` required right before is issued first. If `volatile` is set, the
+    /// setting is lost at the next power cycle. Cached identification is refreshed afterwards, so
+    /// that [`Device::validate_range`] follows the new capacity. **Data past `max_lba` becomes
+    /// inaccessible.**
+    pub fn set_max_address(&mut self, max_lba: u64, volatile: bool) -> io::Result<()> {
+        if self.skip_dry_run(format_args!("set max address to {}", max_lba))? {
+            return Ok(());
+        }
+
+        let lba48 = self.lba48();
+        if !lba48 {
+            Self::check_lba28(max_lba, os::SECTOR_BYTES)?;
+        }
+        let tf = TaskFile {
+            command: if lba48 {
+                ATA_SET_MAX_ADDRESS_EXT
+            } else {
+                ATA_SET_MAX
+            },
+            features: SET_MAX_ADDRESS,
+            count: if volatile { 0 } else { SET_MAX_NON_VOLATILE },
+            lba: max_lba,
+            device: ATA_DEV_LBA,
+            ext: lba48,
+            ..Default::default()
+        };
+
+        self.read_native_max_address()?;
+        self.ata.raw_command(&tf, Transfer::NonData)?;
+
+        self.ident = self.info().ok();
+        Ok(())
+    }
+
+    /// Read the `DEVICE CONFIGURATION IDENTIFY` data structure (ATA cmd 0xB1, feature 0xC2),
+    /// describing the factory configuration limited by DCO.
+    ///
+    /// Drives not supporting Device Configuration Overlay (identify word 83 bit 11) are reported
+    /// with an error.
+    pub fn dco_identify(&mut self) -> io::Result<[u8; 512]> {
+        if let Some(id) = self.ident {
+            if !id.supports_dco() {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "Device Configuration Overlay not supported",
+                ));
+            }
+        }
+
+        let tf = TaskFile {
+            command: ATA_DEVICE_CONFIGURATION,
+            features: DCO_IDENTIFY,
+            count: 1,
+            device: ATA_DEV_LBA,
+            ..Default::default()
+        };
+        let mut data = [0u8; os::SECTOR_BYTES];
+
+        self.retry(|ata| ata.raw_command(&tf, Transfer::PioIn(&mut data)))?;
+        Ok(data)
+    }
+
+    /// Return the last sector of the drive allowed by Device Configuration Overlay (words 3..6
+    /// of [`Device::dco_identify`]), that is the factory native max address.
+    pub fn dco_max_address(&mut self) -> io::Result<u64> {
+        let data = self.dco_identify()?;
+        let mut max = [0u8; 8];
+        max.copy_from_slice(&data[6..14]);
+        Ok(u64::from_le_bytes(max))
+    }
+
+    /// Image the whole drive to `out`, including sectors hidden in Host Protected Area.
+    ///
+    /// HPA is removed with a volatile [`Device::set_max_address`] to the native max address, then
+    /// sectors up to it are copied with [`Device::image_with_map`]. The original max address is
+    /// restored afterwards, also if imaging fails or panics. Sectors hidden with Device
+    /// Configuration Overlay cannot be revealed without permanently changing the drive, so they
+    /// are only reported with a warning.
+    pub fn full_capacity_image<W: Write>(&mut self, out: &mut W) -> io::Result<SectorMap> {
+        let current = self.capacity_sectors()?;
+        let native = self.read_native_max_address()? + 1;
+
+        if self.ident.is_some_and(|id| id.supports_dco()) {
+            match self.dco_max_address() {
+                Ok(dco) if dco + 1 > native => log::warn!(
+                    "{}: sectors {}..{} hidden by DCO are not imaged",
+                    self.path.display(),
+                    native,
+                    dco + 1
+                ),
+                Ok(_) => {}
+                Err(err) => log::warn!("{}: DCO IDENTIFY failed: {}", self.path.display(), err),
+            }
+        }
+
+        if native <= current {
+            return self.image_with_map(0, current, out);
+        }
+
+        let guard = HpaGuard {
+            device: self,
+            max_lba: current.saturating_sub(1),
+        };
+        guard.device.set_max_address(native - 1, true)?;
+        let end = guard.device.capacity_sectors()?;
+        guard.device.image_with_map(0, end, out)
+    }
+
+    /// Set password protecting the max address using `SET MAX SET PASSWORD` (ATA cmd 0xF9,
+    /// feature 0x01).
+    pub fn set_max_set_password(&mut self, password: &[u8; 32]) -> io::Result<()> {
+        self.set_max_password_command(SET_MAX_SET_PASSWORD, password)
+    }
+
+    /// Lock the max address using `SET MAX LOCK` (ATA cmd 0xF9, feature 0x02). Until unlocked
+    /// or power cycled, all `SET MAX` commands except `UNLOCK` and `FREEZE LOCK` are aborted.
+    pub fn set_max_lock(&mut self) -> io::Result<()> {
+        self.set_max_command(SET_MAX_LOCK)
+    }
+
+    /// Unlock the max address locked with [`Device::set_max_lock`] using `SET MAX UNLOCK` (ATA
+    /// cmd 0xF9, feature 0x03). `password` is the one set with [`Device::set_max_set_password`].
+    ///
+    /// Drive allows only 5 failed attempts until the next power cycle.
+    pub fn set_max_unlock(&mut self, password: &[u8; 32]) -> io::Result<()> {
+        self.set_max_password_command(SET_MAX_UNLOCK, password)
+    }
+
+    /// Freeze the max address using `SET MAX FREEZE LOCK` (ATA cmd 0xF9, feature 0x04). Until the
+    /// next power cycle all `SET MAX` commands are aborted.
+    pub fn set_max_freeze_lock(&mut self) -> io::Result<()> {
+        self.set_max_command(SET_MAX_FREEZE_LOCK)
+    }
+
+    /// Issue a non-data `SET MAX` security extension
+    fn set_max_command(&mut self, feature: u16) -> io::Result<()> {
+        let tf = TaskFile {
+            command: ATA_SET_MAX,
+            features: feature,
+            device: ATA_DEV_LBA,
+            ..Default::default()
+        };
+
+        self.retry(|ata| ata.raw_command(&tf, Transfer::NonData))?;
+        Ok(())
+    }
+
+    /// Issue a `SET MAX` security extension transferring a password in words 1..16 of a sector
+    fn set_max_password_command(&mut self, feature: u16, password: &[u8; 32]) -> io::Result<()> {
+        let mut data = [0u8; os::SECTOR_BYTES];
+        data[2..34].copy_from_slice(password);
+
+        let tf = TaskFile {
+            command: ATA_SET_MAX,
+            features: feature,
+            count: 1,
+            device: ATA_DEV_LBA,
+            ..Default::default()
+        };
+
+        self.retry(|ata| ata.raw_command(&tf, Transfer::PioOut(&data)))?;
+        Ok(())
+    }
+}
+
+/// Restores the max address when dropped, so that HPA removed for imaging comes back even on
+/// error or panic
+struct HpaGuard<'a> {
+    device: &'a mut Device,
+    max_lba: u64,
+}
+
+impl Drop for HpaGuard<'_> {
+    fn drop(&mut self) {
+        if let Err(err) = self.device.set_max_address(self.max_lba, true) {
+            log::error!(
+                "{}: failed to restore max address {}: {}",
+                self.device.path.display(),
+                self.max_lba,
+                err
+            );
+        }
+    }
+}
@@ -0,0 +1,1040 @@
+//! Decoding of IDENTIFY DEVICE data.
+//!
+//! It has no OS dependency and only needs `core` and `alloc`, so it is also available with the
+//! `std` feature disabled, like on bare-metal ATA controllers. Model, serial number and firmware
+//! revision can also be decoded into a caller supplied buffer, without allocation.
+
+use alloc::{string::String, vec::Vec};
+use core::{
+    fmt,
+    hash::{Hash, Hasher},
+    str,
+    time::Duration,
+};
+
+/// Number of sectors addressable by 28-bit commands
+pub(crate) const LBA28_LIMIT: u64 = 1 << 28;
+
+/// Classic capabilities of words 49 and 50, returned by [`IdentifyDeviceData::get_capabilities`].
+///
+/// They predate the command set/feature words and are the only capability source on pre ATA-6
+/// drives.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// DMA supported (word 49 bit 8)
+    pub dma: bool,
+    /// LBA addressing supported (word 49 bit 9)
+    pub lba: bool,
+    /// IORDY may be disabled (word 49 bit 10)
+    pub iordy_disable: bool,
+    /// IORDY supported (word 49 bit 11)
+    pub iordy: bool,
+    /// Standby timer values are as specified by the standard (word 49 bit 13), otherwise they are
+    /// vendor specific
+    pub standard_standby_timer: bool,
+    /// Drive has a vendor specific minimum standby timer value (word 50 bit 0)
+    pub standby_timer_minimum: bool,
+}
+
+/// Serial ATA features of word 78 (supported) or word 79 (enabled), see [`SataFeatures`]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SataFeatureSet {
+    /// Non-zero buffer offsets (bit 1)
+    pub nonzero_buffer_offsets: bool,
+    /// DMA Setup FIS auto-activation (bit 2)
+    pub dma_setup_auto_activation: bool,
+    /// Device initiated interface power management, DIPM (bit 3)
+    pub dipm: bool,
+    /// In-order data delivery (bit 4)
+    pub in_order_delivery: bool,
+    /// Hardware feature control (bit 5)
+    pub hardware_feature_control: bool,
+    /// Software settings preservation across COMRESET (bit 6)
+    pub software_settings_preservation: bool,
+    /// Device sleep, DEVSLP (bit 8)
+    pub devslp: bool,
+    /// Hybrid information (bit 9)
+    pub hybrid_information: bool,
+}
+
+impl SataFeatureSet {
+    fn from_word(word: u16) -> Self {
+        SataFeatureSet {
+            nonzero_buffer_offsets: word & (1 << 1) != 0,
+            dma_setup_auto_activation: word & (1 << 2) != 0,
+            dipm: word & (1 << 3) != 0,
+            in_order_delivery: word & (1 << 4) != 0,
+            hardware_feature_control: word & (1 << 5) != 0,
+            software_settings_preservation: word & (1 << 6) != 0,
+            devslp: word & (1 << 8) != 0,
+            hybrid_information: word & (1 << 9) != 0,
+        }
+    }
+}
+
+/// Serial ATA capabilities and features, returned by [`IdentifyDeviceData::get_sata_features`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SataFeatures {
+    /// Host initiated interface power management, HIPM, is supported (word 76 bit 9)
+    pub hipm: bool,
+    /// Features supported by the drive (word 78)
+    pub supported: SataFeatureSet,
+    /// Features currently enabled (word 79)
+    pub enabled: SataFeatureSet,
+}
+
+/// Feature sets and commands queried with [`IdentifyDeviceData::supports`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AtaFeature {
+    /// Volatile write cache (word 82 bit 5)
+    WriteCache,
+    /// Read look-ahead (word 82 bit 6)
+    ReadLookAhead,
+    /// SMART feature set (word 82 bit 0)
+    Smart,
+    /// Security feature set (word 82 bit 1)
+    Security,
+    /// `WRITE BUFFER` command (word 82 bit 12)
+    WriteBuffer,
+    /// `READ BUFFER` command (word 82 bit 13)
+    ReadBuffer,
+    /// Advanced Power Management feature set (word 83 bit 3)
+    Apm,
+    /// Automatic Acoustic Management feature set (word 83 bit 9)
+    Aam,
+    /// 48-bit addressing, supported and enabled (words 83 and 86 bit 10)
+    Lba48,
+    /// General Purpose Logging feature set (word 84 or 87 bit 5)
+    Gpl,
+    /// Streaming feature set (word 84 bit 4)
+    Streaming,
+    /// `WRITE DMA FUA EXT` command (word 84 bit 6)
+    Fua,
+    /// Sanitize Device feature set (word 59 bit 12)
+    Sanitize,
+    /// Trusted Computing feature set (word 48 bit 0)
+    Tcg,
+    /// TRIM bit of `DATA SET MANAGEMENT` (word 169 bit 0)
+    Trim,
+}
+
+/// Transfer mode currently selected on the drive, returned by
+/// [`IdentifyDeviceData::get_transfer_mode`].
+///
+/// Mode numbers are the ones of the standard, like `UltraDma(6)` for UDMA/133.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TransferMode {
+    /// No DMA mode selected, data is transferred with PIO. Drives do not report the selected PIO
+    /// mode, it is the fastest supported one.
+    Pio(u8),
+    /// Single-word DMA mode selected (word 62 bits 10..8, obsolete)
+    SingleWordDma(u8),
+    /// Multiword DMA mode selected (word 63 bits 10..8)
+    MultiwordDma(u8),
+    /// Ultra DMA mode selected (word 88 bits 14..8)
+    UltraDma(u8),
+}
+
+impl fmt::Display for TransferMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransferMode::Pio(mode) => write!(f, "PIO{}", mode),
+            TransferMode::SingleWordDma(mode) => write!(f, "SWDMA{}", mode),
+            TransferMode::MultiwordDma(mode) => write!(f, "MWDMA{}", mode),
+            TransferMode::UltraDma(mode) => write!(f, "UDMA{}", mode),
+        }
+    }
+}
+
+/// ATA standard IDENTIFY_DEVICE structure.
+///
+/// It is described in the table 55 of [ATA/ATAPI Command Set](http://t13.org/Documents/UploadedDocuments/docs2017/di529r18-ATAATAPI_Command_Set_-_4.pdf).
+///
+/// Due to a 16-bit bus architecture of ATA, that structure contains 256 16-bit words, not 512
+/// bytes. Side effect of this layout is that all strings have pairwise swapped letters. String
+/// "Abcdef" is stored in memory as "bAdcfe",
+///
+/// Numeric values are stored as LE-LE, that is bytes within word are little-endian and for
+/// multi-word values words themselves are also little-endian. This is demonstrated in
+/// [`IdentifyDeviceData::get_sector_count`].
+#[derive(Copy, Clone)]
+pub struct IdentifyDeviceData(pub(crate) [u16; 256]);
+
+impl IdentifyDeviceData {
+    /// Wrap identification record given as 256 words, like decoded by a bare-metal ATA driver
+    pub fn from_words(words: [u16; 256]) -> Self {
+        IdentifyDeviceData(words)
+    }
+
+    /// Decode identification record from 512 bytes read from the drive, that is with
+    /// little-endian words
+    pub fn from_bytes(bytes: &[u8; 512]) -> Self {
+        let mut words = [0u16; 256];
+        for (word, pair) in words.iter_mut().zip(bytes.chunks_exact(2)) {
+            *word = u16::from_le_bytes([pair[0], pair[1]]);
+        }
+        IdentifyDeviceData(words)
+    }
+
+    /// Return total sector count of disk: the extended count of words 230..233 if reported (see
+    /// [`IdentifyDeviceData::get_sector_count_ext`]), words 100..103 otherwise
+    pub fn get_sector_count(&self) -> u64 {
+        self.get_sector_count_ext()
+            .unwrap_or_else(|| self.read_u64_lele(100))
+    }
+
+    /// Return extended number of user addressable sectors (words 230..233), `None` if not
+    /// supported (word 69 bit 3) or zero
+    pub fn get_sector_count_ext(&self) -> Option<u64> {
+        match self.read_u64_lele(230) {
+            count if self.0[69] & (1 << 3) != 0 && count != 0 => Some(count),
+            _ => None,
+        }
+    }
+
+    /// Return model info of disk
+    pub fn get_model(&self) -> String {
+        Self::swap_string(&self.0[27..=46])
+    }
+
+    /// Return serial number of disk
+    pub fn get_serial(&self) -> String {
+        Self::swap_string(&self.0[10..=19])
+    }
+
+    /// Return firmware revision of disk
+    pub fn get_firmware(&self) -> String {
+        Self::swap_string(&self.0[23..=26])
+    }
+
+    /// Return model info of disk without allocation, decoded into `buffer`
+    pub fn get_model_into<'a>(&self, buffer: &'a mut [u8; 40]) -> &'a str {
+        Self::swap_str_into(&self.0[27..=46], buffer)
+    }
+
+    /// Return serial number of disk without allocation, decoded into `buffer`
+    pub fn get_serial_into<'a>(&self, buffer: &'a mut [u8; 20]) -> &'a str {
+        Self::swap_str_into(&self.0[10..=19], buffer)
+    }
+
+    /// Return firmware revision of disk without allocation, decoded into `buffer`
+    pub fn get_firmware_into<'a>(&self, buffer: &'a mut [u8; 8]) -> &'a str {
+        Self::swap_str_into(&self.0[23..=26], buffer)
+    }
+
+    /// Return World Wide Name of disk (words 108..111), `None` if not reported
+    pub fn get_wwn(&self) -> Option<u64> {
+        // Unlike other numeric values, the most significant word comes first
+        let wwn = self.0[108..=111]
+            .iter()
+            .fold(0u64, |wwn, &word| wwn << 16 | word as u64);
+
+        match wwn {
+            0 => None,
+            _ => Some(wwn),
+        }
+    }
+
+    /// Return additional product identifier of disk (words 170..173), empty if not reported
+    pub fn get_additional_product_id(&self) -> String {
+        Self::swap_string(&self.0[170..=173])
+    }
+
+    /// Return current media serial number of disk (words 176..205), empty if not reported
+    pub fn get_media_serial_number(&self) -> String {
+        Self::swap_string(&self.0[176..=205])
+    }
+
+    /// Return number of sectors addressable with the supported addressing mode: words 100..103
+    /// for 48-bit drives, words 60..61 otherwise
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn addressable_sectors(&self) -> u64 {
+        if self.supports_lba48() {
+            self.get_sector_count()
+        } else {
+            self.read_u32_lele(60) as u64
+        }
+    }
+
+    /// Check if 48-bit capacity (words 100..103) exceeds 2^28 sectors, so that 28-bit commands
+    /// cannot reach the whole drive.
+    ///
+    /// On such drives only sectors below 2^28 may be transferred with 28-bit commands, like
+    /// those of [`Device::read_28`](crate::Device::read_28).
+    pub fn requires_lba48(&self) -> bool {
+        self.get_sector_count() > LBA28_LIMIT
+    }
+
+    /// Check if 48-bit addressing is supported (word 83 bit 10) and enabled (word 86 bit 10).
+    ///
+    /// Drives without it (pre ATA-6) abort all `_EXT` commands and need 28-bit ones instead.
+    pub fn supports_lba48(&self) -> bool {
+        self.feature_word(83) & (1 << 10) != 0 && self.0[86] & (1 << 10) != 0
+    }
+
+    /// Check if Device Configuration Overlay feature set is supported (word 83 bit 11)
+    pub fn supports_dco(&self) -> bool {
+        self.feature_word(83) & (1 << 11) != 0
+    }
+
+    /// Check if Automatic Acoustic Management feature set is supported (word 83 bit 9)
+    pub fn supports_aam(&self) -> bool {
+        self.feature_word(83) & (1 << 9) != 0
+    }
+
+    /// Check if Power-Up In Standby feature set is supported (word 83 bit 5)
+    pub fn supports_puis(&self) -> bool {
+        self.feature_word(83) & (1 << 5) != 0
+    }
+
+    /// Check if Power-Up In Standby feature set is enabled (word 86 bit 5)
+    pub fn puis_enabled(&self) -> bool {
+        self.0[86] & (1 << 5) != 0
+    }
+
+    /// Check if a drive powered up in Standby needs `SET FEATURES` to spin up (word 83 bit 6),
+    /// otherwise any media access spins it up
+    pub fn puis_requires_spin_up(&self) -> bool {
+        self.feature_word(83) & (1 << 6) != 0
+    }
+
+    /// Return current Automatic Acoustic Management level (word 94 bits 7..0), from 0x80 (quietest)
+    /// to 0xFE (fastest), `None` if the feature set is not enabled (word 86 bit 9)
+    pub fn get_aam_level(&self) -> Option<u8> {
+        match self.0[86] & (1 << 9) {
+            0 => None,
+            _ => Some((self.0[94] & 0x00FF) as u8),
+        }
+    }
+
+    /// Check if General Purpose Logging feature set is supported (word 84 or word 87 bit 5).
+    ///
+    /// It is required by all commands reading logs with `READ_LOG_EXT`, otherwise logs are only
+    /// available through `SMART READ LOG`.
+    pub fn supports_gpl(&self) -> bool {
+        self.feature_word(84) & (1 << 5) != 0 || self.feature_word(87) & (1 << 5) != 0
+    }
+
+    /// Check if SMART error logging is supported (word 84 bit 0)
+    pub fn supports_smart_error_log(&self) -> bool {
+        self.feature_word(84) & (1 << 0) != 0
+    }
+
+    /// Check if SMART self-test is supported (word 84 bit 1)
+    pub fn supports_smart_self_test_log(&self) -> bool {
+        self.feature_word(84) & (1 << 1) != 0
+    }
+
+    /// Check if Trusted Computing feature set is supported (word 48 bit 0), which is required by
+    /// `TRUSTED SEND/RECEIVE` and TCG protocols like Opal. Word 48 is used only if its bits 15..14
+    /// are 01.
+    pub fn supports_tcg(&self) -> bool {
+        self.0[48] & 0xC000 == 0x4000 && self.0[48] & (1 << 0) != 0
+    }
+
+    /// Check if TRIM bit of `DATA SET MANAGEMENT` is supported (word 169 bit 0)
+    pub fn supports_trim(&self) -> bool {
+        self.0[169] & (1 << 0) != 0
+    }
+
+    /// Check if trimmed sectors deterministically read as zeros: TRIM supported, Deterministic
+    /// read After TRIM (word 69 bit 14) and Read Zeroes After TRIM (word 69 bit 5)
+    pub fn supports_trim_read_zero(&self) -> bool {
+        self.supports_trim() && self.0[69] & (1 << 14) != 0 && self.0[69] & (1 << 5) != 0
+    }
+
+    /// Check if `WRITE DMA FUA EXT` is supported (word 84 bit 6)
+    pub fn supports_fua(&self) -> bool {
+        self.feature_word(84) & (1 << 6) != 0
+    }
+
+    /// Check if Streaming feature set is supported (word 84 bit 4)
+    pub fn supports_streaming(&self) -> bool {
+        self.feature_word(84) & (1 << 4) != 0
+    }
+
+    /// Check if feature set or command `feature` is supported, consolidating checks of
+    /// scattered identify bits listed in [`AtaFeature`]
+    pub fn supports(&self, feature: AtaFeature) -> bool {
+        match feature {
+            AtaFeature::WriteCache => self.feature_word(82) & (1 << 5) != 0,
+            AtaFeature::ReadLookAhead => self.feature_word(82) & (1 << 6) != 0,
+            AtaFeature::Smart => self.feature_word(82) & (1 << 0) != 0,
+            AtaFeature::Security => self.feature_word(82) & (1 << 1) != 0,
+            AtaFeature::WriteBuffer => self.feature_word(82) & (1 << 12) != 0,
+            AtaFeature::ReadBuffer => self.feature_word(82) & (1 << 13) != 0,
+            AtaFeature::Apm => self.feature_word(83) & (1 << 3) != 0,
+            AtaFeature::Aam => self.supports_aam(),
+            AtaFeature::Lba48 => self.supports_lba48(),
+            AtaFeature::Gpl => self.supports_gpl(),
+            AtaFeature::Streaming => self.supports_streaming(),
+            AtaFeature::Fua => self.supports_fua(),
+            AtaFeature::Sanitize => self.supports_sanitize(),
+            AtaFeature::Tcg => self.supports_tcg(),
+            AtaFeature::Trim => self.supports_trim(),
+        }
+    }
+
+    /// Return minimum number of sectors of a streaming request (word 95), the stream
+    /// performance unit. `0` if Streaming is not supported.
+    pub fn get_stream_min_request_size(&self) -> u16 {
+        self.0[95]
+    }
+
+    /// Return streaming transfer time of a minimum request with DMA (word 96) and PIO (word 104),
+    /// in units of stream granularity
+    pub fn get_stream_transfer_time(&self) -> (u16, u16) {
+        (self.0[96], self.0[104])
+    }
+
+    /// Return streaming access latency for DMA and PIO (word 97), in units of stream granularity
+    pub fn get_stream_access_latency(&self) -> u16 {
+        self.0[97]
+    }
+
+    /// Return stream performance granularity in microseconds (words 98..99), the unit of
+    /// streaming times and of `READ/WRITE STREAM` command time limits
+    pub fn get_stream_granularity(&self) -> u32 {
+        self.read_u32_lele(98)
+    }
+
+    /// Return one of command set/feature words (82..87), or `0` if it is not valid. Words 83, 84
+    /// and 87 are valid if their bits 15..14 are 01, other words have no such signature (bit 14
+    /// of word 82 is NOP support) and are valid unless 0x0000 or 0xFFFF.
+    #[inline]
+    pub(crate) fn feature_word(&self, word: usize) -> u16 {
+        let value = self.0[word];
+        match word {
+            83 | 84 | 87 if value & 0xC000 != 0x4000 => 0,
+            _ if value == 0xFFFF => 0,
+            _ => value,
+        }
+    }
+
+    /// Return classic capabilities of words 49 and 50. Word 50 is used only if its bits 15..14 are
+    /// 01.
+    pub fn get_capabilities(&self) -> Capabilities {
+        let word49 = self.0[49];
+        let word50 = match self.0[50] & 0xC000 {
+            0x4000 => self.0[50],
+            _ => 0,
+        };
+
+        Capabilities {
+            dma: word49 & (1 << 8) != 0,
+            lba: word49 & (1 << 9) != 0,
+            iordy_disable: word49 & (1 << 10) != 0,
+            iordy: word49 & (1 << 11) != 0,
+            standard_standby_timer: word49 & (1 << 13) != 0,
+            standby_timer_minimum: word50 & (1 << 0) != 0,
+        }
+    }
+
+    /// Return transfer mode currently selected on the drive, as opposed to the supported ones.
+    ///
+    /// Ultra DMA selection (word 88) is valid only if word 53 bit 2 is set. If no DMA mode is
+    /// selected, the fastest PIO mode supported according to words 64 and 51 is returned.
+    pub fn get_transfer_mode(&self) -> TransferMode {
+        let selected = |word: u16, mask: u16| -> Option<u8> {
+            match (word >> 8) & mask {
+                0 => None,
+                bits => Some(15 - bits.leading_zeros() as u8),
+            }
+        };
+
+        if self.0[53] & (1 << 2) != 0 {
+            if let Some(mode) = selected(self.0[88], 0x7F) {
+                return TransferMode::UltraDma(mode);
+            }
+        }
+        if let Some(mode) = selected(self.0[63], 0x07) {
+            return TransferMode::MultiwordDma(mode);
+        }
+        if let Some(mode) = selected(self.0[62], 0x07) {
+            return TransferMode::SingleWordDma(mode);
+        }
+
+        // Word 64 (valid with word 53 bit 1) lists advanced PIO modes 3 and 4, word 51 bits
+        // 15..8 holds the legacy PIO mode number 0..2
+        match self.0[64] & 0x0003 {
+            advanced if self.0[53] & (1 << 1) != 0 && advanced & 0x2 != 0 => TransferMode::Pio(4),
+            advanced if self.0[53] & (1 << 1) != 0 && advanced & 0x1 != 0 => TransferMode::Pio(3),
+            _ => TransferMode::Pio(((self.0[51] >> 8) as u8).min(2)),
+        }
+    }
+
+    /// Return maximum number of sectors transferred per interrupt by `READ/WRITE MULTIPLE`
+    /// (word 47 bits 7..0), `0` if these commands are not supported
+    pub fn get_max_sectors_per_interrupt(&self) -> u8 {
+        (self.0[47] & 0x00FF) as u8
+    }
+
+    /// Return number of sectors transferred per interrupt by `READ/WRITE MULTIPLE` as currently
+    /// set with `SET MULTIPLE MODE` (word 59 bits 7..0), `None` if not set (word 59 bit 8 clear
+    /// or zero setting)
+    pub fn get_multiple_setting(&self) -> Option<u8> {
+        match self.0[59] {
+            word if word & (1 << 8) != 0 && word & 0x00FF != 0 => Some((word & 0x00FF) as u8),
+            _ => None,
+        }
+    }
+
+    /// Check if Sanitize Device feature set is supported (word 59 bit 12)
+    pub fn supports_sanitize(&self) -> bool {
+        self.0[59] & (1 << 12) != 0
+    }
+
+    /// Check if `CRYPTO SCRAMBLE EXT` of Sanitize Device feature set is supported (word 59 bit 13)
+    pub fn supports_crypto_scramble(&self) -> bool {
+        self.supports_sanitize() && self.0[59] & (1 << 13) != 0
+    }
+
+    /// Check if `BLOCK ERASE EXT` of Sanitize Device feature set is supported (word 59 bit 15)
+    pub fn supports_block_erase(&self) -> bool {
+        self.supports_sanitize() && self.0[59] & (1 << 15) != 0
+    }
+
+    /// Return maximum number of 512-byte blocks of LBA range entries accepted by a single `DATA
+    /// SET MANAGEMENT` command (word 105), `0` if not reported
+    pub fn get_max_dsm_blocks(&self) -> u16 {
+        self.0[105]
+    }
+
+    /// Return number of logical sectors per physical sector (word 106 bits 3..0 as a power of 2,
+    /// valid when bit 13 is set), `1` if not reported
+    pub fn get_logical_per_physical(&self) -> u16 {
+        match self.0[106] {
+            word if word & 0xC000 == 0x4000 && word & (1 << 13) != 0 => 1 << (word & 0x000F),
+            _ => 1,
+        }
+    }
+
+    /// Return offset of logical sector 0 within the first physical sector, in logical sectors
+    /// (word 209 bits 13..0), `0` if not reported
+    pub fn get_alignment_offset(&self) -> u16 {
+        match self.0[209] {
+            word if word & 0xC000 == 0x4000 => word & 0x3FFF,
+            _ => 0,
+        }
+    }
+
+    /// Return nominal media rotation rate in RPM (word 217), `None` for non-rotating media or
+    /// when not reported
+    pub fn get_rotation_rate(&self) -> Option<u16> {
+        match self.0[217] {
+            0x0401..=0xFFFE => Some(self.0[217]),
+            _ => None,
+        }
+    }
+
+    /// Check if drive reports non-rotating media (word 217), like SSDs
+    pub fn is_solid_state(&self) -> bool {
+        self.0[217] == 0x0001
+    }
+
+    /// Check if drive is a hybrid one (SSHD), that is rotating media with a flash cache.
+    ///
+    /// Flash cache is detected by either the Hybrid Information feature (word 78 bit 9) or the
+    /// legacy NV Cache feature set (NV Cache Power Mode in word 214 bit 0 or non-zero NV Cache
+    /// size in words 215..216).
+    pub fn is_hybrid(&self) -> bool {
+        let sata_hybrid = self.is_sata() && self.0[78] & (1 << 9) != 0;
+        let nv_cache = self.0[214] & (1 << 0) != 0 || self.0[215] != 0 || self.0[216] != 0;
+
+        self.get_rotation_rate().is_some() && (sata_hybrid || nv_cache)
+    }
+
+    /// Check if drive reports Serial ATA capabilities. Words 76..79 are valid only if word 76 is
+    /// neither 0x0000 nor 0xFFFF, parallel ATA drives leave them cleared.
+    pub fn is_sata(&self) -> bool {
+        !matches!(self.0[76], 0x0000 | 0xFFFF)
+    }
+
+    /// Check if SATA PHY event counters log is supported (word 76 bit 10)
+    pub fn supports_phy_event_counters(&self) -> bool {
+        self.is_sata() && self.0[76] & (1 << 10) != 0
+    }
+
+    /// Return Serial ATA capabilities and features (words 76, 78 and 79), `None` for drives not
+    /// reporting Serial ATA capabilities (see [`IdentifyDeviceData::is_sata`]).
+    ///
+    /// Words 78 and 79 are used only if they are neither 0x0000 nor 0xFFFF.
+    pub fn get_sata_features(&self) -> Option<SataFeatures> {
+        if !self.is_sata() {
+            return None;
+        }
+        let word = |n: usize| match self.0[n] {
+            0x0000 | 0xFFFF => 0,
+            word => word,
+        };
+
+        Some(SataFeatures {
+            hipm: self.0[76] & (1 << 9) != 0,
+            supported: SataFeatureSet::from_word(word(78)),
+            enabled: SataFeatureSet::from_word(word(79)),
+        })
+    }
+
+    /// Check if Sense Data Reporting feature set is supported (word 119 bit 6). Word 119 is used
+    /// only if its bits 15..14 are 01.
+    pub fn supports_sense_data_reporting(&self) -> bool {
+        self.0[119] & 0xC000 == 0x4000 && self.0[119] & (1 << 6) != 0
+    }
+
+    /// Check if Sense Data Reporting feature set is enabled (word 120 bit 6). Word 120 is used
+    /// only if its bits 15..14 are 01.
+    pub fn is_sense_data_reporting_enabled(&self) -> bool {
+        self.0[120] & 0xC000 == 0x4000 && self.0[120] & (1 << 6) != 0
+    }
+
+    /// Check if Security feature set is frozen (word 128 bit 3), so that all commands changing
+    /// security state, including `SECURITY ERASE UNIT`, are aborted until the next power cycle
+    pub fn is_security_frozen(&self) -> bool {
+        self.0[128] & (1 << 3) != 0
+    }
+
+    /// Check if enhanced `SECURITY ERASE UNIT` is supported (word 128 bit 5)
+    pub fn supports_enhanced_erase(&self) -> bool {
+        self.0[128] & (1 << 5) != 0
+    }
+
+    /// Return time estimated by the drive for a normal `SECURITY ERASE UNIT` (word 89), zero if
+    /// not reported.
+    ///
+    /// Values above 508 minutes are only reported as "more than 508 minutes" by drives not
+    /// using the extended format, 508 minutes is returned then.
+    pub fn get_erase_time(&self) -> Duration {
+        self.erase_time(89)
+    }
+
+    /// Return time estimated by the drive for an enhanced `SECURITY ERASE UNIT` (word 90), zero
+    /// if not reported. Encoding is the same as of [`IdentifyDeviceData::get_erase_time`].
+    pub fn get_enhanced_erase_time(&self) -> Duration {
+        self.erase_time(90)
+    }
+
+    /// Decode erase time estimate of word 89 or 90.
+    ///
+    /// In the extended format (bit 15 set) bits 14..0 hold the time in 2-minute units. Otherwise
+    /// bits 7..0 do, with 255 meaning more than 508 minutes.
+    fn erase_time(&self, word: usize) -> Duration {
+        let value = self.0[word];
+        let units = match value & 0x8000 {
+            0 => (value & 0x00FF).min(254),
+            _ => value & 0x7FFF,
+        };
+
+        Duration::from_secs(units as u64 * 120)
+    }
+
+    /// Read range fixing byte order (bytes are always pairwise swapped, regardless of host being
+    /// LE or BE)
+    #[inline]
+    fn swap_bytes(buffer: &[u16]) -> Vec<u8> {
+        let mut ans = Vec::with_capacity(buffer.len() * 2);
+
+        for word in buffer {
+            ans.push(((word >> 8) & 0xFF) as u8);
+            ans.push((word & 0xFF) as u8);
+        }
+        ans
+    }
+
+    /// Convert un-swapped range into a string slice backed by `out`, which must be twice as long
+    /// as `buffer`. Text is cut at the first invalid utf8 sequence.
+    #[inline]
+    fn swap_str_into<'a>(buffer: &[u16], out: &'a mut [u8]) -> &'a str {
+        for (word, pair) in buffer.iter().zip(out.chunks_exact_mut(2)) {
+            pair.copy_from_slice(&word.to_be_bytes());
+        }
+
+        let text = match str::from_utf8(out) {
+            Ok(text) => text,
+            Err(err) => str::from_utf8(&out[..err.valid_up_to()]).unwrap_or_default(),
+        };
+        text.trim_matches(|c: char| c.is_whitespace() || c == '\0')
+    }
+
+    /// Convert un-swapped range into string assuming it is utf8-ish.
+    #[inline]
+    fn swap_string(buffer: &[u16]) -> String {
+        let swapped = Self::swap_bytes(buffer);
+        let text = String::from_utf8_lossy(swapped.as_slice());
+
+        // Unused fields are filled with either spaces or zeros
+        String::from(text.trim_matches(|c: char| c.is_whitespace() || c == '\0'))
+    }
+
+    /// Assemble a value stored in 2 consecutive words starting at `word_start`, least significant
+    /// word first. Words are already in host order, so it works regardless of host endianness
+    /// and alignment.
+    #[inline]
+    fn read_u32_lele(&self, word_start: usize) -> u32 {
+        self.0[word_start..word_start + 2]
+            .iter()
+            .rev()
+            .fold(0u32, |value, &word| value << 16 | word as u32)
+    }
+
+    /// Assemble a value stored in 4 consecutive words starting at `word_start`, least significant
+    /// word first, like [`IdentifyDeviceData::read_u32_lele`].
+    #[inline]
+    fn read_u64_lele(&self, word_start: usize) -> u64 {
+        self.0[word_start..word_start + 4]
+            .iter()
+            .rev()
+            .fold(0u64, |value, &word| value << 16 | word as u64)
+    }
+}
+
+/// Records are equal if they describe the same drive, that is have the same serial number and
+/// WWN. Other fields, like current settings, are ignored.
+impl PartialEq for IdentifyDeviceData {
+    fn eq(&self, other: &Self) -> bool {
+        self.get_serial() == other.get_serial() && self.get_wwn() == other.get_wwn()
+    }
+}
+
+impl Eq for IdentifyDeviceData {}
+
+impl Hash for IdentifyDeviceData {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.get_serial().hash(state);
+        self.get_wwn().hash(state);
+    }
+}
+
+impl fmt::Debug for IdentifyDeviceData {
+    /// Return basic drive information
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let size = self.get_sector_count();
+        let model = self.get_model();
+        let serial = self.get_serial();
+        let firmware = self.get_firmware();
+
+        f.debug_struct("IdentifyDeviceData")
+            .field("sectors", &size)
+            .field("model", &model)
+            .field("firmware", &firmware)
+            .field("serial", &serial)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn check_constructors() {
+        let mut bytes = [0u8; 512];
+        bytes[54..58].copy_from_slice(b"bAdc");
+        bytes[200..208].copy_from_slice(&[0x44, 0x44, 0x33, 0x33, 0x22, 0x22, 0x11, 0x11]);
+
+        let id = IdentifyDeviceData::from_bytes(&bytes);
+        assert_eq!(id.get_model(), "Abcd");
+        assert_eq!(id.get_sector_count(), 0x1111_2222_3333_4444);
+
+        let mut words = [0u16; 256];
+        words[27] = 0x4162;
+        words[28] = 0x6364;
+        words[100..104].copy_from_slice(&[0x4444, 0x3333, 0x2222, 0x1111]);
+        assert_eq!(IdentifyDeviceData::from_words(words), id);
+    }
+
+    #[test]
+    fn check_multi_word_values() {
+        let mut words = [0u16; 256];
+        words[60] = 0x5678;
+        words[61] = 0x1234;
+        words[100] = 0x4444;
+        words[101] = 0x3333;
+        words[102] = 0x2222;
+        words[103] = 0x1111;
+        let id = IdentifyDeviceData(words);
+        assert!(id.requires_lba48());
+        assert!(!IdentifyDeviceData([0u16; 256]).requires_lba48());
+
+        assert_eq!(id.read_u32_lele(60), 0x1234_5678);
+        assert_eq!(id.get_sector_count(), 0x1111_2222_3333_4444);
+        assert_eq!(id.get_sector_count_ext(), None);
+
+        words[230] = 0x0001;
+        words[232] = 0x0002;
+        assert_eq!(IdentifyDeviceData(words).get_sector_count_ext(), None);
+        words[69] = 1 << 3;
+        let id = IdentifyDeviceData(words);
+        assert_eq!(id.get_sector_count_ext(), Some(0x0002_0000_0001));
+        assert_eq!(id.get_sector_count(), 0x0002_0000_0001);
+    }
+
+    #[test]
+    fn check_identify_strings() {
+        let mut words = [0u16; 256];
+        words[170] = u16::from_be_bytes(*b"AB");
+        words[171] = u16::from_be_bytes(*b"C ");
+        let id = IdentifyDeviceData(words);
+
+        assert_eq!(id.get_additional_product_id(), "ABC");
+        assert_eq!(id.get_media_serial_number(), "");
+
+        words[10] = u16::from_be_bytes(*b"S1");
+        words[11] = u16::from_be_bytes(*b"23");
+        let mut serial = [0u8; 20];
+        assert_eq!(
+            IdentifyDeviceData(words).get_serial_into(&mut serial),
+            "S123"
+        );
+    }
+
+    #[test]
+    fn check_capabilities() {
+        let mut words = [0u16; 256];
+        words[49] = 0x2F00;
+        words[50] = 0x0001;
+        let caps = IdentifyDeviceData(words).get_capabilities();
+
+        assert!(caps.dma && caps.lba && caps.iordy && caps.standard_standby_timer);
+        assert!(!caps.standby_timer_minimum);
+
+        words[50] = 0x4001;
+        assert!(
+            IdentifyDeviceData(words)
+                .get_capabilities()
+                .standby_timer_minimum
+        );
+    }
+
+    #[test]
+    fn check_transfer_mode() {
+        let mut words = [0u16; 256];
+        words[51] = 0x0200;
+        assert_eq!(
+            IdentifyDeviceData(words).get_transfer_mode(),
+            TransferMode::Pio(2)
+        );
+
+        words[53] = 0x0006;
+        words[64] = 0x0003;
+        assert_eq!(
+            IdentifyDeviceData(words).get_transfer_mode(),
+            TransferMode::Pio(4)
+        );
+
+        words[63] = 0x0407;
+        assert_eq!(
+            IdentifyDeviceData(words).get_transfer_mode(),
+            TransferMode::MultiwordDma(2)
+        );
+
+        words[63] = 0x0007;
+        words[88] = 0x407F;
+        let mode = IdentifyDeviceData(words).get_transfer_mode();
+        assert_eq!(mode, TransferMode::UltraDma(6));
+        assert_eq!(mode.to_string(), "UDMA6");
+
+        words[53] = 0x0002;
+        assert_eq!(
+            IdentifyDeviceData(words).get_transfer_mode(),
+            TransferMode::Pio(4)
+        );
+    }
+
+    #[test]
+    fn check_features() {
+        let mut words = [0u16; 256];
+        words[82] = 0x0063;
+        assert!(IdentifyDeviceData(words).supports(AtaFeature::WriteCache));
+        words[82] = 0xFFFF;
+        assert!(!IdentifyDeviceData(words).supports(AtaFeature::WriteCache));
+
+        words[82] = 0x4063;
+        words[83] = 0x4008;
+        words[59] = 1 << 12;
+        let id = IdentifyDeviceData(words);
+        for feature in [
+            AtaFeature::WriteCache,
+            AtaFeature::ReadLookAhead,
+            AtaFeature::Smart,
+            AtaFeature::Security,
+            AtaFeature::Apm,
+            AtaFeature::Sanitize,
+        ] {
+            assert!(id.supports(feature), "{:?}", feature);
+        }
+        assert!(!id.supports(AtaFeature::Aam));
+        assert!(!id.supports_dco());
+
+        words[98] = 0x86A0;
+        words[99] = 0x0001;
+        assert_eq!(IdentifyDeviceData(words).get_stream_granularity(), 100_000);
+    }
+
+    #[test]
+    fn check_physical_sectors() {
+        let mut words = [0u16; 256];
+        words[106] = 0x0003;
+        assert_eq!(IdentifyDeviceData(words).get_logical_per_physical(), 1);
+
+        words[106] = 0x6003;
+        words[209] = 0x4001;
+        let id = IdentifyDeviceData(words);
+        assert_eq!(id.get_logical_per_physical(), 8);
+        assert_eq!(id.get_alignment_offset(), 1);
+    }
+
+    #[test]
+    fn check_aam() {
+        let mut words = [0u16; 256];
+        words[83] = 0x4200;
+        words[94] = 0x80FE;
+        assert!(IdentifyDeviceData(words).supports_aam());
+        assert_eq!(IdentifyDeviceData(words).get_aam_level(), None);
+
+        words[86] = 1 << 9;
+        assert_eq!(IdentifyDeviceData(words).get_aam_level(), Some(0xFE));
+    }
+
+    #[test]
+    fn check_puis() {
+        let mut words = [0u16; 256];
+        words[83] = 0x4060;
+        assert!(IdentifyDeviceData(words).supports_puis());
+        assert!(IdentifyDeviceData(words).puis_requires_spin_up());
+        assert!(!IdentifyDeviceData(words).puis_enabled());
+
+        words[86] = 1 << 5;
+        assert!(IdentifyDeviceData(words).puis_enabled());
+    }
+
+    #[test]
+    fn check_tcg_support() {
+        let mut words = [0u16; 256];
+        words[48] = 0x0001;
+        assert!(!IdentifyDeviceData(words).supports_tcg());
+
+        words[48] = 0x4001;
+        assert!(IdentifyDeviceData(words).supports_tcg());
+    }
+
+    #[test]
+    fn check_transfer_limits() {
+        let mut words = [0u16; 256];
+        words[47] = 0x8010;
+        words[105] = 8;
+        let id = IdentifyDeviceData(words);
+
+        assert_eq!(id.get_max_sectors_per_interrupt(), 16);
+        assert_eq!(id.get_max_dsm_blocks(), 8);
+        assert!(!id.supports_trim());
+        assert_eq!(id.get_multiple_setting(), None);
+
+        words[59] = 0x0110;
+        words[169] = 0x0001;
+        assert_eq!(IdentifyDeviceData(words).get_multiple_setting(), Some(16));
+        assert!(IdentifyDeviceData(words).supports_trim());
+        assert!(!IdentifyDeviceData(words).supports_trim_read_zero());
+
+        words[69] = 0x4020;
+        assert!(IdentifyDeviceData(words).supports_trim_read_zero());
+    }
+
+    #[test]
+    fn check_sata_features() {
+        let mut words = [0u16; 256];
+        words[78] = 0x0048;
+        assert_eq!(IdentifyDeviceData(words).get_sata_features(), None);
+
+        words[76] = 0x0200;
+        assert!(!IdentifyDeviceData(words).supports_phy_event_counters());
+        words[79] = 0x0040;
+        let features = IdentifyDeviceData(words).get_sata_features().unwrap();
+        assert!(features.hipm);
+        assert!(features.supported.dipm && features.supported.software_settings_preservation);
+        assert!(!features.enabled.dipm && features.enabled.software_settings_preservation);
+
+        words[119] = 0x0040;
+        assert!(!IdentifyDeviceData(words).supports_sense_data_reporting());
+        words[119] = 0x4040;
+        words[120] = 0x4040;
+        assert!(IdentifyDeviceData(words).supports_sense_data_reporting());
+        assert!(IdentifyDeviceData(words).is_sense_data_reporting_enabled());
+
+        words[79] = 0xFFFF;
+        let features = IdentifyDeviceData(words).get_sata_features().unwrap();
+        assert_eq!(features.enabled, SataFeatureSet::default());
+    }
+
+    #[test]
+    fn check_hybrid_detection() {
+        let mut words = [0u16; 256];
+        words[217] = 7200;
+        assert!(!IdentifyDeviceData(words).is_hybrid());
+
+        assert!(!IdentifyDeviceData(words).is_sata());
+        words[76] = 0x0100;
+        words[78] = 1 << 9;
+        assert!(IdentifyDeviceData(words).is_sata());
+        assert!(IdentifyDeviceData(words).is_hybrid());
+
+        words[217] = 0x0001;
+        assert!(IdentifyDeviceData(words).is_solid_state());
+        assert!(!IdentifyDeviceData(words).is_hybrid());
+    }
+
+    #[test]
+    fn check_identify_equality() {
+        let mut words = [0u16; 256];
+        words[10] = u16::from_be_bytes(*b"S1");
+        words[108..=111].copy_from_slice(&[0x5000, 0xC500, 0x1234, 0x5678]);
+        let first = IdentifyDeviceData(words);
+
+        words[217] = 7200;
+        let second = IdentifyDeviceData(words);
+
+        assert_eq!(first.get_wwn(), Some(0x5000_C500_1234_5678));
+        assert!(first == second);
+
+        words[10] = u16::from_be_bytes(*b"S2");
+        assert!(first != IdentifyDeviceData(words));
+    }
+
+    #[test]
+    fn check_erase_time() {
+        let mut words = [0u16; 256];
+        words[89] = 30;
+        words[90] = 0x8000 | 600;
+
+        let id = IdentifyDeviceData(words);
+        assert_eq!(id.get_erase_time(), Duration::from_secs(60 * 60));
+        assert_eq!(id.get_enhanced_erase_time(), Duration::from_secs(1200 * 60));
+
+        words[89] = 255;
+        assert_eq!(
+            IdentifyDeviceData(words).get_erase_time(),
+            Duration::from_secs(508 * 60)
+        );
+
+        words[89] = 0;
+        assert_eq!(IdentifyDeviceData(words).get_erase_time(), Duration::ZERO);
+        assert!(!IdentifyDeviceData(words).is_security_frozen());
+
+        words[128] = 0x0029;
+        assert!(IdentifyDeviceData(words).is_security_frozen());
+        assert!(IdentifyDeviceData(words).supports_enhanced_erase());
+    }
+
+    #[test]
+    fn check_sanitize() {
+        let mut words = [0u16; 256];
+        words[59] = 0xA000;
+        assert!(!IdentifyDeviceData(words).supports_crypto_scramble());
+
+        words[59] = 0xB000;
+        let id = IdentifyDeviceData(words);
+        assert!(id.supports_sanitize());
+        assert!(id.supports_crypto_scramble());
+        assert!(id.supports_block_erase());
+    }
+}
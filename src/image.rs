@@ -0,0 +1,137 @@
+//! Imaging of failing drives, recording readability of every sector like `ddrescue` map files.
+
+use std::io::{self, Write};
+
+use crate::{os, Device, STREAM_CHUNK_SECTORS};
+
+/// Readability of a sector, as recorded in [`SectorMap`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SectorState {
+    /// Read at the first attempt
+    Good,
+    /// Read only when retried on its own, after the read of the whole chunk failed
+    Recovered,
+    /// Unreadable, zeros were written instead
+    Bad,
+}
+
+/// Map of sector states produced by [`Device::image_with_map`].
+///
+/// Consecutive sectors of the same state are stored as a single range, so the map stays small for
+/// mostly healthy drives.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SectorMap {
+    ranges: Vec<(u64, u64, SectorState)>,
+}
+
+impl SectorMap {
+    /// Record state of `count` sectors starting at `start`, which must follow the last recorded
+    /// one
+    fn push(&mut self, start: u64, count: u64, state: SectorState) {
+        if let Some((last_start, last_count, last_state)) = self.ranges.last_mut() {
+            if *last_state == state && *last_start + *last_count == start {
+                *last_count += count;
+                return;
+            }
+        }
+        self.ranges.push((start, count, state));
+    }
+
+    /// Return ranges as `(start, count, state)`, in order of sectors
+    pub fn ranges(&self) -> &[(u64, u64, SectorState)] {
+        &self.ranges
+    }
+
+    /// Return state of sector `lba`, `None` if it is not covered by the map
+    pub fn state(&self, lba: u64) -> Option<SectorState> {
+        let i = self.ranges.partition_point(|&(start, _, _)| start <= lba);
+        match self.ranges.get(i.checked_sub(1)?) {
+            Some(&(start, count, state)) if lba < start + count => Some(state),
+            _ => None,
+        }
+    }
+
+    /// Return ranges of unreadable sectors as `(start, count)`, the input of a second pass
+    pub fn bad_ranges(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.ranges
+            .iter()
+            .filter(|&&(_, _, state)| state == SectorState::Bad)
+            .map(|&(start, count, _)| (start, count))
+    }
+}
+
+impl Device {
+    /// Copy sectors `start..end` to `out`, recording readability of every sector.
+    ///
+    /// Range is read in chunks of [`STREAM_CHUNK_SECTORS`]. If a chunk is rejected by the drive,
+    /// its sectors are read one by one, those that succeed are marked [`SectorState::Recovered`]
+    /// and those that still fail are written as zeros and marked [`SectorState::Bad`]. Ranges
+    /// past capacity, errors not reported by the drive and errors of `out` abort imaging.
+    pub fn image_with_map<W: Write>(
+        &mut self,
+        start: u64,
+        end: u64,
+        out: &mut W,
+    ) -> io::Result<SectorMap> {
+        if start > end {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "start sector past end sector",
+            ));
+        }
+        self.validate_range(start, end - start)?;
+
+        let mut map = SectorMap::default();
+        let mut buffer = vec![0u8; STREAM_CHUNK_SECTORS as usize * os::SECTOR_BYTES];
+        let mut sector = start;
+
+        while sector < end {
+            let count = (end - sector).min(STREAM_CHUNK_SECTORS);
+            let chunk = &mut buffer[..count as usize * os::SECTOR_BYTES];
+
+            let whole = self.transfer_lenient(
+                sector,
+                chunk,
+                |dev, lba, data| dev.read(lba, data),
+                |lba, data, ok| {
+                    if ok {
+                        map.push(lba, 1, SectorState::Recovered);
+                    } else {
+                        data.iter_mut().for_each(|b| *b = 0);
+                        map.push(lba, 1, SectorState::Bad);
+                    }
+                },
+            )?;
+            if whole {
+                map.push(sector, count, SectorState::Good);
+            }
+
+            out.write_all(chunk)?;
+            sector += count;
+        }
+
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_sector_map() {
+        let mut map = SectorMap::default();
+        map.push(0, 256, SectorState::Good);
+        map.push(256, 1, SectorState::Recovered);
+        map.push(257, 1, SectorState::Bad);
+        map.push(258, 1, SectorState::Bad);
+        map.push(259, 253, SectorState::Good);
+
+        assert_eq!(map.ranges().len(), 4);
+        assert_eq!(map.state(255), Some(SectorState::Good));
+        assert_eq!(map.state(256), Some(SectorState::Recovered));
+        assert_eq!(map.state(258), Some(SectorState::Bad));
+        assert_eq!(map.state(512), None);
+        assert_eq!(map.bad_ranges().collect::<Vec<_>>(), vec![(257, 2)]);
+    }
+}
@@ -0,0 +1,86 @@
+//! Empirical measurement of random access latency of the drive.
+
+use std::{
+    io,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{os, Device};
+
+/// Number of random reads timed by [`Device::estimated_random_read_latency`]
+const LATENCY_SAMPLES: usize = 15;
+
+impl Device {
+    /// Estimate random read latency of the drive as the median time of single-sector reads at
+    /// random positions.
+    ///
+    /// SSDs answer in well under a millisecond, rotating drives need several milliseconds to seek
+    /// and wait for the sector to pass under the head. Unlike
+    /// [`IdentifyDeviceData::get_rotation_rate`](crate::IdentifyDeviceData::get_rotation_rate) it
+    /// does not depend on the drive reporting itself correctly. Positions are spread over the
+    /// whole capacity, so reads are unlikely to be served from the drive cache; the median
+    /// discards occasional outliers, like the first read waking the drive up.
+    pub fn estimated_random_read_latency(&mut self) -> io::Result<Duration> {
+        let capacity = self.capacity_sectors()?;
+        if capacity == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "drive reports no sectors",
+            ));
+        }
+
+        let mut rng = seed();
+        let mut sector = [0u8; os::SECTOR_BYTES];
+        let mut samples = Vec::with_capacity(LATENCY_SAMPLES);
+        for _ in 0..LATENCY_SAMPLES {
+            let lba = next_random(&mut rng) % capacity;
+            let started = Instant::now();
+            self.read(lba, &mut sector)?;
+            samples.push(started.elapsed());
+        }
+
+        Ok(median(&mut samples))
+    }
+}
+
+/// Seed of [`next_random`] taken from the clock, never zero
+fn seed() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |t| t.as_nanos() as u64);
+    nanos | 1
+}
+
+/// Advance xorshift64 state and return the next pseudo-random number
+fn next_random(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+/// Median of non-empty `samples`, mean of the middle pair if their number is even
+fn median(samples: &mut [Duration]) -> Duration {
+    samples.sort_unstable();
+    let mid = samples.len() / 2;
+    if samples.len().is_multiple_of(2) {
+        (samples[mid - 1] + samples[mid]) / 2
+    } else {
+        samples[mid]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_median() {
+        let ms = Duration::from_millis;
+        assert_eq!(median(&mut [ms(9), ms(1), ms(40)]), ms(9));
+        assert_eq!(median(&mut [ms(4), ms(1), ms(2), ms(8)]), ms(3));
+
+        let mut rng = 1;
+        assert_ne!(next_random(&mut rng), next_random(&mut rng));
+    }
+}
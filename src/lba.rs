@@ -0,0 +1,75 @@
+//! Sector addresses kept apart from byte offsets at the type level.
+
+use std::fmt;
+
+use crate::os;
+
+/// Logical block address: number of a 512-byte sector, **not** a byte offset.
+///
+/// Sector arguments of [`Device::read`](crate::Device::read),
+/// [`Device::write`](crate::Device::write), [`Device::trim`](crate::Device::trim) and their
+/// relatives accept anything convertible into it. Plain `u64` converts as a sector number, for
+/// convenience; byte offsets must go through [`Lba::from_bytes`], which refuses offsets not on a
+/// sector boundary.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Lba(pub u64);
+
+impl Lba {
+    /// Address of sector number `sector`
+    pub const fn from_sector(sector: u64) -> Self {
+        Lba(sector)
+    }
+
+    /// Address of the sector starting at byte offset `bytes`, `None` if the offset is not a
+    /// multiple of sector size
+    pub const fn from_bytes(bytes: u64) -> Option<Self> {
+        if bytes.is_multiple_of(os::SECTOR_BYTES as u64) {
+            Some(Lba(bytes / os::SECTOR_BYTES as u64))
+        } else {
+            None
+        }
+    }
+
+    /// Return sector number
+    pub const fn sector(self) -> u64 {
+        self.0
+    }
+
+    /// Return byte offset of the start of the sector
+    pub const fn to_bytes(self) -> u64 {
+        self.0 * os::SECTOR_BYTES as u64
+    }
+}
+
+impl From<u64> for Lba {
+    /// Take `sector` as a sector number, not a byte offset
+    fn from(sector: u64) -> Self {
+        Lba(sector)
+    }
+}
+
+impl From<Lba> for u64 {
+    fn from(lba: Lba) -> Self {
+        lba.0
+    }
+}
+
+impl fmt::Display for Lba {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LBA {}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_lba_units() {
+        assert_eq!(Lba::from_bytes(4096), Some(Lba(8)));
+        assert_eq!(Lba::from_bytes(4097), None);
+        assert_eq!(Lba::from(8).to_bytes(), 4096);
+        assert_eq!(u64::from(Lba::from_sector(3)), 3);
+        assert_eq!(Lba(3).to_string(), "LBA 3");
+    }
+}
@@ -11,9 +11,81 @@
 //! # Supported operations
 //!
 //! - read sectors using `READ_DMA_EXT` (ATA cmd 0x25, documentation chapter 7.21),
-//! - write sectors using `WRITE_DMA_EXT` (ATA cmd 0x35, documentation chapter 7.57)
+//! - write sectors using `WRITE_DMA_EXT` (ATA cmd 0x35, documentation chapter 7.57) or, bypassing
+//!   drive write cache, `WRITE_DMA_FUA_EXT` (ATA cmd 0x3D),
+//! - read boot sector and backup GPT header ([`Device::read_boot_sector`],
+//!   [`Device::read_backup_gpt`]),
+//! - read and write sectors of legacy drives using `READ_DMA` (ATA cmd 0xC8) and `WRITE_DMA`
+//!   (ATA cmd 0xCA),
+//! - read and write sectors using PIO block transfers of `READ MULTIPLE EXT` (ATA cmd 0x29) and
+//!   `WRITE MULTIPLE EXT` (ATA cmd 0x39), with block size set by `SET MULTIPLE MODE` (ATA cmd
+//!   0xC6),
+//! - flush write cache using `FLUSH_CACHE_EXT` (ATA cmd 0xEA, documentation chapter 7.11) or,
+//!   for legacy drives, `FLUSH_CACHE` (ATA cmd 0xE7),
 //! - identify drive using `IDENTIFY_DEVICE` (ATA cmd 0xEC, documentation chapter 7.13, including a
 //!   detailed description of returned structure).
+//! - read General Purpose Log directory and IDENTIFY DEVICE DATA log pages using `READ_LOG_EXT`
+//!   (ATA cmd 0x2F, documentation chapter 7.24),
+//! - read SATA PHY event counters log ([`Device::phy_event_counters`]),
+//! - get and set SCT Error Recovery Control timeouts and read temperature history using SCT
+//!   command transport (documentation chapter 8),
+//! - read and write sectors with bounded latency using `READ STREAM DMA EXT` (ATA cmd 0x2A) and
+//!   `WRITE STREAM DMA EXT` (ATA cmd 0x3A),
+//! - test the data path using `WRITE BUFFER` (ATA cmd 0xE8) and `READ BUFFER` (ATA cmd 0xE4),
+//! - read SMART data using `SMART READ DATA` (ATA cmd 0xB0, documentation chapter 7.44) and
+//!   control attribute autosave and automatic off-line data collection,
+//! - control NV Cache of hybrid drives using `NV CACHE` (ATA cmd 0xB6, documentation of ATA8-ACS
+//!   chapter 7.21),
+//! - mark unused sectors using `DATA SET MANAGEMENT` (ATA cmd 0x06) with TRIM bit, and zero
+//!   ranges by trimming or writing ([`Device::zero_range`]),
+//! - report and manage zones of SMR drives using `ZAC MANAGEMENT IN` (ATA cmd 0x4A) and `ZAC
+//!   MANAGEMENT OUT` (ATA cmd 0x9F) described in ZAC standard,
+//! - manage Host Protected Area using `READ NATIVE MAX ADDRESS` (ATA cmd 0xF8/0x27) and `SET
+//!   MAX` (ATA cmd 0xF9/0x37), including password-protected SET MAX security extensions, read
+//!   Device Configuration Overlay using `DEVICE CONFIGURATION IDENTIFY` (ATA cmd 0xB1/0xC2) and
+//!   image sectors hidden by HPA ([`Device::full_capacity_image`]),
+//! - erase the whole drive using `SECURITY ERASE PREPARE` (ATA cmd 0xF3) and `SECURITY ERASE
+//!   UNIT` (ATA cmd 0xF4, documentation chapter 7.36),
+//! - erase the whole drive using `CRYPTO SCRAMBLE EXT` and `BLOCK ERASE EXT` of `SANITIZE
+//!   DEVICE` (ATA cmd 0xB4), or with the strongest supported method and verify the result
+//!   ([`Device::secure_wipe`]),
+//! - exchange security protocol payloads (like TCG Opal) using `TRUSTED SEND` (ATA cmd 0x5E) and
+//!   `TRUSTED RECEIVE` (ATA cmd 0x5C),
+//! - get and set Automatic Acoustic Management level using `SET FEATURES` (ATA cmd 0xEF),
+//! - enable Sense Data Reporting and read sense data using `REQUEST SENSE DATA EXT` (ATA cmd
+//!   0x0B),
+//! - set standby timer using `IDLE` (ATA cmd 0xE3) and `STANDBY` (ATA cmd 0xE2), check power
+//!   mode using `CHECK POWER MODE` (ATA cmd 0xE5) and wait for the drive to spin up,
+//! - control Power-Up In Standby and spin up such drives using `SET FEATURES` (ATA cmd 0xEF),
+//! - tell whether the drive is attached with PATA, SATA or a USB bridge ([`Device::transport`])
+//!   and which transfer mode it negotiated ([`Device::current_transfer_mode`]),
+//! - estimate random read latency to tell SSDs from rotating drives
+//!   ([`Device::estimated_random_read_latency`]),
+//! - address sectors with [`Lba`], keeping sector numbers apart from byte offsets,
+//! - list raw disks present in the system ([`list_devices`]),
+//! - issue arbitrary ATA commands ([`Device::execute`]),
+//! - hash a range of sectors with any [`Digest`] ([`Device::hash_range`]),
+//! - image a failing drive with a map of unreadable sectors ([`Device::image_with_map`]),
+//! - flag writes not aligned to physical sectors of 512e drives
+//!   ([`Device::set_strict_alignment`]),
+//! - tell whether the disk is mounted or used by md/LVM and optionally refuse destructive
+//!   operations then ([`Device::is_in_use`]),
+//! - lock a physical drive against other processes, whatever path they use
+//!   ([`Device::lock_drive`]),
+//! - mirror writes to a pair of drives ([`MirrorDevice`]),
+//! - optionally cache recently read sectors ([`CachedReader`]),
+//! - write a byte stream of any length to sectors, padding the last one
+//!   ([`SectorPaddingWriter`]),
+//! - on Linux, adopt a device opened elsewhere, like by a privileged broker
+//!   ([`Device::from_raw_fd`](std::os::unix::io::FromRawFd::from_raw_fd)),
+//! - issue arbitrary SCSI commands ([`Device::scsi_command`]) and read capacity using SCSI
+//!   `READ CAPACITY (16)` ([`Device::read_capacity`]),
+//! - read sectors of SCSI drives with T10 protection information and check their guards
+//!   ([`Device::read_with_pi`]),
+//! - on Linux, transfer sectors through a memory-mapped `SG` buffer
+//!   ([`Device::enable_mmap_io`]),
+//! - on Linux, with `async` feature, read and write sectors asynchronously through io_uring
+//!   (`AsyncDevice`).
 //!
 //! On Linux uses `SG` subsystem, on FreeBSD uses `CAM` subsystem.
 //!
@@ -24,7 +96,7 @@
 //! 0x0000).
 //!
 //! *In practice* operating system enforces much lower limit, in the range of a few hundred
-//! kilobytes.
+//! kilobytes. It can be queried with [`Device::max_transfer_bytes`].
 //!
 //! On FreeBSD I managed to achieve stable transfers of 8MB at a time by re-compiling
 //! kernel with custom configuration:
@@ -44,149 +116,784 @@
 //! - support sector sizes different than 512 bytes
 //!
 
+#![cfg_attr(not(feature = "std"), no_std)]
 #![allow(clippy::identity_op)]
+#![allow(clippy::upper_case_acronyms)]
 
-use std::fmt;
-use std::mem::MaybeUninit;
-use std::{io, path::Path};
+extern crate alloc;
 
-#[cfg(target_os = "freebsd")]
+#[cfg(feature = "std")]
+use std::{
+    fmt,
+    io::{self, IoSliceMut},
+    mem::MaybeUninit,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+#[cfg(feature = "std")]
+use digest::{consts::U32, Digest, Output};
+
+#[cfg(feature = "std")]
+mod acoustic;
+#[cfg(feature = "std")]
+mod align;
+#[cfg(feature = "std")]
+mod buffer;
+#[cfg(feature = "std")]
+mod cache;
+#[cfg(feature = "std")]
+mod command;
+#[cfg(all(feature = "std", target_os = "linux"))]
+mod fd;
+#[cfg(feature = "std")]
+mod hpa;
+mod identify;
+#[cfg(feature = "std")]
+mod image;
+#[cfg(feature = "std")]
+mod latency;
+#[cfg(feature = "std")]
+mod lba;
+#[cfg(feature = "std")]
+mod lock;
+#[cfg(feature = "std")]
+mod mirror;
+#[cfg(all(feature = "std", target_os = "linux"))]
+mod mmap;
+#[cfg(feature = "std")]
+mod multiple;
+#[cfg(feature = "std")]
+mod nvcache;
+#[cfg(feature = "std")]
+mod phy;
+#[cfg(feature = "std")]
+mod pi;
+#[cfg(feature = "std")]
+mod power;
+#[cfg(feature = "std")]
+mod sanitize;
+#[cfg(feature = "std")]
+mod scsi;
+#[cfg(feature = "std")]
+mod sct;
+#[cfg(feature = "std")]
+mod security;
+#[cfg(feature = "std")]
+mod sense;
+#[cfg(feature = "std")]
+mod smart;
+#[cfg(feature = "std")]
+mod stream;
+#[cfg(feature = "std")]
+mod transport;
+#[cfg(feature = "std")]
+mod trim;
+#[cfg(feature = "std")]
+mod trusted;
+#[cfg(all(feature = "async", target_os = "linux"))]
+mod uring;
+#[cfg(feature = "std")]
+mod wipe;
+#[cfg(feature = "std")]
+mod writer;
+#[cfg(feature = "std")]
+mod zac;
+
+#[cfg(feature = "std")]
+pub use align::MisalignedWrite;
+#[cfg(feature = "std")]
+pub use cache::CachedReader;
+#[cfg(feature = "std")]
+pub use command::{AtaCommand, AtaResult, AtaTaskfileOut, CommandError, Direction, Protocol};
+#[cfg(feature = "std")]
+use command::{TaskFile, Transfer};
+#[cfg(feature = "std")]
+use identify::LBA28_LIMIT;
+pub use identify::{
+    AtaFeature, Capabilities, IdentifyDeviceData, SataFeatureSet, SataFeatures, TransferMode,
+};
+#[cfg(feature = "std")]
+pub use image::{SectorMap, SectorState};
+#[cfg(feature = "std")]
+pub use lba::Lba;
+#[cfg(feature = "std")]
+pub use mirror::{MirrorDevice, MirrorError};
+#[cfg(feature = "std")]
+pub use phy::phy_event_name;
+#[cfg(feature = "std")]
+pub use pi::{ProtectionInfo, PI_BYTES};
+#[cfg(feature = "std")]
+pub use power::{PowerMode, StandbyPeriod};
+#[cfg(feature = "std")]
+pub use sanitize::SanitizeStatus;
+#[cfg(feature = "std")]
+pub use sct::TempHistory;
+#[cfg(feature = "std")]
+pub use sense::SenseData;
+#[cfg(feature = "std")]
+pub use smart::{HealthSummary, SmartAttribute};
+#[cfg(feature = "std")]
+pub use transport::Transport;
+#[cfg(all(feature = "async", target_os = "linux"))]
+pub use uring::AsyncDevice;
+#[cfg(feature = "std")]
+pub use wipe::{WipeMethod, WipeReport, WIPE_PASSWORD};
+#[cfg(feature = "std")]
+pub use writer::SectorPaddingWriter;
+#[cfg(feature = "std")]
+pub use zac::{Zone, ZoneCondition, ZoneType};
+
+#[cfg(all(feature = "std", target_os = "freebsd"))]
 #[path = "freebsd.rs"]
 mod os;
 
-#[cfg(target_os = "linux")]
+#[cfg(all(feature = "std", target_os = "linux"))]
 #[path = "linux.rs"]
 mod os;
 
+/// Number of sectors transferred at once by streaming operations, like
+/// [`Device::hash_range`]. Safely below the transfer limits enforced by operating systems.
+#[cfg(feature = "std")]
+pub const STREAM_CHUNK_SECTORS: u64 = 256;
+
+/// Number of sectors sampled by [`Device::probe_real_capacity`]
+#[cfg(feature = "std")]
+pub const PROBE_SAMPLES: u64 = 64;
+
+/// Marker of sectors written by [`Device::probe_real_capacity`]
+#[cfg(feature = "std")]
+const PROBE_MAGIC: u64 = 0x5041_4b52_5052_4f42;
+
+/// Delay before the first retry of a command failed with a transient error
+#[cfg(feature = "std")]
+const RETRY_BACKOFF: Duration = Duration::from_millis(10);
+
+#[cfg(feature = "std")]
 trait RawAta
 where
     Self: std::marker::Sized,
 {
-    fn open<P: AsRef<Path>>(dev: P) -> io::Result<Self>;
-    fn close(&mut self);
-    fn raw_read(&mut self, sector: u64, buffer: &mut [u8]) -> io::Result<()>;
-    fn raw_write(&mut self, sector: u64, buffer: &[u8]) -> io::Result<()>;
-    fn raw_info(&mut self, ident: *mut IdentifyDeviceData) -> io::Result<()>;
+    fn open<P: AsRef<Path>>(dev: P, exclusive: bool, direct: bool) -> io::Result<Self>;
+    fn try_clone(&self) -> io::Result<Self>;
+    fn set_retries(&mut self, n: u8);
+    fn close(&mut self) -> io::Result<()>;
+    fn raw_read(&mut self, sector: u64, buffer: &mut [u8]) -> io::Result<AtaTaskfileOut>;
+    fn raw_write(&mut self, sector: u64, buffer: &[u8]) -> io::Result<AtaTaskfileOut>;
+    fn raw_read_vectored(
+        &mut self,
+        sector: u64,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> io::Result<AtaTaskfileOut>;
+    fn raw_info(&mut self, ident: *mut IdentifyDeviceData) -> io::Result<AtaTaskfileOut>;
+    fn raw_command(&mut self, tf: &TaskFile, data: Transfer<'_>) -> io::Result<AtaTaskfileOut>;
 }
 
-/// ATA standard IDENTIFY_DEVICE structure.
-///
-/// It is described in the table 55 of [ATA/ATAPI Command Set](http://t13.org/Documents/UploadedDocuments/docs2017/di529r18-ATAATAPI_Command_Set_-_4.pdf).
-///
-/// Due to a 16-bit bus architecture of ATA, that structure contains 256 16-bit words, not 512
-/// bytes. Side effect of this layout is that all strings have pairwise swapped letters. String
-/// "Abcdef" is stored in memory as "bAdcfe",
+#[cfg(feature = "std")]
+const ATA_READ_LOG_EXT: u8 = 0x2F;
+#[cfg(feature = "std")]
+const ATA_READ_DMA: u8 = 0xC8;
+#[cfg(feature = "std")]
+const ATA_FLUSH_CACHE: u8 = 0xE7;
+#[cfg(feature = "std")]
+const ATA_FLUSH_CACHE_EXT: u8 = 0xEA;
+#[cfg(feature = "std")]
+const ATA_WRITE_DMA: u8 = 0xCA;
+#[cfg(feature = "std")]
+const ATA_WRITE_DMA_FUA_EXT: u8 = 0x3D;
+#[cfg(feature = "std")]
+const ATA_WRITE_LOG_EXT: u8 = 0x3F;
+#[cfg(feature = "std")]
+const ATA_SET_FEATURES: u8 = 0xEF;
+
+#[cfg(feature = "std")]
+const ATA_DEV_LBA: u8 = 0x40;
+
+/// Flushing a large write cache of a rotating drive may take a while
+#[cfg(feature = "std")]
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[cfg(feature = "std")]
+const LBA28_MAX_SECTORS: usize = 256;
+
+#[cfg(feature = "std")]
+const LOG_DIRECTORY: u8 = 0x00;
+#[cfg(feature = "std")]
+const LOG_IDENTIFY_DEVICE_DATA: u8 = 0x30;
+
+/// Return paths of raw whole-disk devices present in the system, sorted.
 ///
-/// Numeric values are stored as LE-LE, that is bytes within word are little-endian and for
-/// multi-word values words themselves are also little-endian. This is demonstrated in
-/// [`IdentifyDeviceData::get_sector_count`].
-#[derive(Copy, Clone)]
-pub struct IdentifyDeviceData([u16; 256]);
+/// On Linux these are devices listed in `/sys/block` backed by hardware, skipping partitions and
+/// virtual devices like loop, md or device-mapper. On FreeBSD these are `/dev/ada*` and
+/// `/dev/da*` disks, skipping partitions. Devices not speaking ATA, like NVMe, may be listed; they
+/// fail to identify when opened.
+#[cfg(feature = "std")]
+pub fn list_devices() -> io::Result<Vec<PathBuf>> {
+    os::disk_paths()
+}
 
-impl IdentifyDeviceData {
-    /// Return total sector count of disk
-    pub fn get_sector_count(&self) -> u64 {
-        let ptr = self.0[100..=103].as_ptr() as *const u64;
+/// Attached ATA device
+#[cfg(feature = "std")]
+pub struct Device {
+    ata: os::ATA,
+    path: PathBuf,
+    retries: u32,
+    controller_retries: Option<u8>,
+    sense_len: Option<u8>,
+    ident: Option<IdentifyDeviceData>,
+    dry_run: bool,
+    strict_alignment: bool,
+    exclusive: bool,
+    direct: bool,
+    refuse_in_use: bool,
+    lock: Option<std::fs::File>,
+}
 
-        // Always safe, source is always 4*u16 long
-        let len = unsafe { *ptr };
+#[cfg(feature = "std")]
+impl Device {
+    /// Open device pointed by a specific path.
+    ///
+    /// **DO NOT** use _partition_ references here (like `/dev/sda1` on Linux or `/dev/ada0p1` on
+    /// FreeBSD). Use **only** _raw disk_ references, like  `/dev/sda` on Linux or `/dev/ada0` on
+    /// FreeBSD.
+    ///
+    /// Access is shared, other processes (including mounted filesystems) may use the disk at the
+    /// same time. Use [`Device::open_exclusive`] to refuse that.
+    #[inline]
+    pub fn open<P>(dev: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::open_with(dev, false, true)
+    }
 
-        // Convert for non-LE hosts, no-op for LE-hosts
-        u64::from_le(len)
+    /// Open device like [`Device::open`], but only if the operating system is not using it.
+    ///
+    /// It fails if the disk is mounted or otherwise claimed by the kernel. On Linux the device
+    /// is opened with `O_EXCL`. On FreeBSD, where GEOM ignores `O_EXCL`, the GEOM provider of
+    /// the disk is held open for writing, which fails with [`io::ErrorKind::ResourceBusy`] while
+    /// it is mounted and prevents mounting it until the device is closed.
+    pub fn open_exclusive<P>(dev: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::open_with(dev, true, true)
     }
 
-    /// Return model info of disk
-    pub fn get_model(&self) -> String {
-        Self::swap_string(&self.0[27..=46])
+    /// Open device like [`Device::open`], but without `O_DIRECT`.
+    ///
+    /// Some block layers (like in containers or on virtualized storage) refuse `O_DIRECT` with
+    /// `EINVAL`. Commands bypass the page cache anyway, but [`Device::flush`] additionally calls
+    /// `fsync` on the handle then. On FreeBSD it is the same as [`Device::open`].
+    pub fn open_buffered<P>(dev: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::open_with(dev, false, false)
     }
 
-    /// Return serial number of disk
-    pub fn get_serial(&self) -> String {
-        Self::swap_string(&self.0[10..=19])
+    /// Open device, exclusively or not, with `O_DIRECT` or not, and cache its identification
+    /// record
+    fn open_with<P>(dev: P, exclusive: bool, direct: bool) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let ata = os::ATA::open(&dev, exclusive, direct)?;
+        Ok(Self::with_ata(
+            ata,
+            dev.as_ref().to_path_buf(),
+            exclusive,
+            direct,
+        ))
     }
 
-    /// Return firmware revision of disk
-    pub fn get_firmware(&self) -> String {
-        Self::swap_string(&self.0[23..=26])
+    /// Wrap an opened handle and cache identification record of the drive
+    fn with_ata(ata: os::ATA, path: PathBuf, exclusive: bool, direct: bool) -> Self {
+        let mut device = Device {
+            ata,
+            path,
+            retries: 0,
+            controller_retries: None,
+            sense_len: None,
+            ident: None,
+            dry_run: false,
+            strict_alignment: false,
+            exclusive,
+            direct,
+            refuse_in_use: false,
+            lock: None,
+        };
+
+        // Identification is only cached for range validation and command selection, drive may
+        // still be usable without it
+        device.ident = device.info().ok();
+
+        device
     }
 
-    /// Read range fixing byte order (bytes are always pairwise swapped, regardless of host being
-    /// LE or BE)
-    #[inline]
-    fn swap_bytes(buffer: &[u16]) -> Vec<u8> {
-        let mut ans = Vec::with_capacity(buffer.len() * 2);
+    /// Open the only drive with serial number `serial`, searching all disks returned by
+    /// [`list_devices`].
+    ///
+    /// Unlike device paths, serial numbers do not change between boots. Devices which cannot be
+    /// opened or identified are skipped. It is an error if no drive or more than one drive
+    /// matches.
+    pub fn open_by_serial(serial: &str) -> io::Result<Self> {
+        let serial = serial.trim();
+        let mut found = Vec::new();
+
+        for path in list_devices()? {
+            if let Ok(device) = Device::open(&path) {
+                if device.ident.is_some_and(|id| id.get_serial() == serial) {
+                    found.push(device);
+                }
+            }
+        }
 
-        for word in buffer {
-            ans.push(((word >> 8) & 0xFF) as u8);
-            ans.push((word & 0xFF) as u8);
+        match found.len() {
+            1 => Ok(found.remove(0)),
+            0 => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no drive with serial {:?}", serial),
+            )),
+            n => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} drives with serial {:?}", n, serial),
+            )),
         }
-        ans
     }
 
-    /// Convert un-swapped range into string assuming it is utf8-ish.
-    #[inline]
-    fn swap_string(buffer: &[u16]) -> String {
-        let swapped = Self::swap_bytes(buffer);
-        String::from(String::from_utf8_lossy(swapped.as_slice()).trim())
+    /// Set how many times commands failing with a transient error (like `UNIT_ATTENTION` or
+    /// `NOT_READY` of a spinning-up drive) are retried, with an exponential backoff starting at
+    /// 10ms. Hard errors (like `MEDIUM_ERROR`) are never retried. Default is `0`.
+    pub fn with_retries(&mut self, n: u32) -> &mut Self {
+        self.retries = n;
+        self
     }
-}
 
-impl fmt::Debug for IdentifyDeviceData {
-    /// Return basic drive information
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let size = self.get_sector_count();
-        let model = self.get_model();
-        let serial = self.get_serial();
-        let firmware = self.get_firmware();
-
-        f.debug_struct("IdentifyDeviceData")
-            .field("sectors", &size)
-            .field("model", &model)
-            .field("firmware", &firmware)
-            .field("serial", &serial)
-            .finish()
+    /// Set how many times the operating system retries a failed command on its own, before
+    /// reporting an error. Unlike [`Device::with_retries`] it also retries hard errors, like
+    /// unreadable sectors.
+    ///
+    /// On FreeBSD it is `retry_count` of CAM, default is `1`. Linux `SG` has no such setting, so
+    /// commands rejected by the drive (except invalid ones) are re-issued by the crate, default
+    /// is `0`. Zero makes a fast-failing scanner, more retries help with marginal media.
+    pub fn set_controller_retries(&mut self, n: u8) {
+        self.controller_retries = Some(n);
+        self.ata.set_retries(n);
     }
-}
 
-/// Attached ATA device
-pub struct Device(os::ATA);
+    /// Set size of the sense buffer of ATA PASS-THROUGH commands on Linux, default is `32`.
+    ///
+    /// Sense data of failed commands is returned in full as [`SenseData`] inside the error. Larger
+    /// buffer captures additional descriptors and vendor bytes of descriptor format sense data.
+    /// Sizes below the default are raised to it. It has no effect on FreeBSD, where CAM returns
+    /// ATA registers directly.
+    pub fn set_sense_buffer_len(&mut self, len: u8) {
+        self.sense_len = Some(len);
+        self.ata.set_sense_len(len);
+    }
 
-impl Device {
-    /// Open device pointed by a specific path.
+    /// Enable or disable dry-run mode.
     ///
-    /// **DO NOT** use _partition_ references here (like `/dev/sda1` on Linux or `/dev/ada0p1` on
-    /// FreeBSD). Use **only** _raw disk_ references, like  `/dev/sda` on Linux or `/dev/ada0` on
-    /// FreeBSD.
-    #[inline]
-    pub fn open<P>(dev: P) -> io::Result<Self>
-    where
-        P: AsRef<Path>,
-    {
-        Ok(Device(os::ATA::open(dev)?))
+    /// In dry-run mode commands modifying data on the media (like [`Device::write`]) only log
+    /// their parameters at `info` level and return success without touching the drive. Reads and
+    /// commands issued with [`Device::execute`] still reach the drive.
+    pub fn set_dry_run(&mut self, enabled: bool) {
+        self.dry_run = enabled;
+    }
+
+    /// Tell if the disk or any of its partitions is used by the operating system.
+    ///
+    /// On Linux a disk is in use when it or a partition is listed in `/proc/mounts` or
+    /// `/proc/swaps`, or has holders in `/sys/block` (md arrays, LVM and dm-crypt volumes). On
+    /// FreeBSD it is in use when GEOM refuses to open it for writing. A device opened with
+    /// [`Device::open_exclusive`] is never in use by anything else.
+    pub fn is_in_use(&self) -> io::Result<bool> {
+        if self.exclusive {
+            return Ok(false);
+        }
+        os::is_in_use(&self.path)
+    }
+
+    /// Enable or disable refusing destructive operations on a disk in use.
+    ///
+    /// When enabled, every command modifying the media (writes, TRIM, secure erase, HPA changes
+    /// and alike) first checks [`Device::is_in_use`] and fails with
+    /// [`io::ErrorKind::ResourceBusy`] if the disk is used. The check reads `/proc` and `/sys`
+    /// on every such command. Disabled by default.
+    pub fn set_refuse_in_use(&mut self, enabled: bool) {
+        self.refuse_in_use = enabled;
+    }
+
+    /// Create an independent handle to the same drive.
+    ///
+    /// On Linux the file descriptor is duplicated, on FreeBSD a new CAM device and CCB are opened
+    /// for the same unit. Settings (retries, dry-run) and cached identification are copied. Each
+    /// handle can be used from its own thread, like for reading the disk from both ends at once.
+    /// Drive lock of [`Device::lock_drive`] stays with the original handle.
+    pub fn try_clone(&self) -> io::Result<Device> {
+        let mut ata = self.ata.try_clone()?;
+        self.configure(&mut ata);
+
+        Ok(Device {
+            ata,
+            path: self.path.clone(),
+            retries: self.retries,
+            controller_retries: self.controller_retries,
+            sense_len: self.sense_len,
+            ident: self.ident,
+            dry_run: self.dry_run,
+            strict_alignment: self.strict_alignment,
+            exclusive: self.exclusive,
+            direct: self.direct,
+            refuse_in_use: self.refuse_in_use,
+            lock: None,
+        })
+    }
+
+    /// Close and open again the device, using the path given to [`Device::open`].
+    ///
+    /// It recovers a device whose handle became stale, like after `SLEEP` or a bus reset. Errors
+    /// from closing the stale handle are ignored.
+    pub fn reopen(&mut self) -> io::Result<()> {
+        let _ = self.ata.close();
+        let mut ata = os::ATA::open(&self.path, self.exclusive, self.direct)?;
+        self.configure(&mut ata);
+        self.ata = ata;
+        Ok(())
+    }
+
+    /// Reset the drive without closing the device, like after a failed command left it in a bad
+    /// state or to wake it from `SLEEP`.
+    ///
+    /// On Linux it is `SG_SCSI_RESET` of the device, which for libata drives resets the link
+    /// and reconfigures the drive. On FreeBSD it is the `XPT_RESET_DEV` CAM request. Settings
+    /// not preserved across resets (like volatile HPA) may be lost, so cached identification is
+    /// refreshed afterwards.
+    pub fn device_reset(&mut self) -> io::Result<()> {
+        self.ata.reset()?;
+        self.ident = self.info().ok();
+        Ok(())
     }
 
-    /// Close opened device
+    /// Close opened device.
+    ///
+    /// Errors reported by the operating system while closing (like a deferred `EIO`) are
+    /// returned. Dropping the device also closes it, but such errors are silently ignored then.
     #[inline]
-    pub fn close(&mut self) {
-        self.0.close();
+    pub fn close(&mut self) -> io::Result<()> {
+        self.ata.close()
+    }
+
+    /// Flush drive write cache to the media using `FLUSH_CACHE_EXT` (ATA cmd 0xEA, documentation
+    /// chapter 7.11). Drives without 48-bit addressing are flushed with [`Device::flush_28`].
+    /// Devices opened with [`Device::open_buffered`] are also synced with `fsync` first.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if !self.direct {
+            self.ata.sync()?;
+        }
+        if !self.lba48() {
+            return self.flush_28();
+        }
+
+        let tf = TaskFile {
+            command: ATA_FLUSH_CACHE_EXT,
+            device: ATA_DEV_LBA,
+            ext: true,
+            timeout: Some(FLUSH_TIMEOUT),
+            ..Default::default()
+        };
+
+        self.retry(|ata| ata.raw_command(&tf, Transfer::NonData))?;
+        Ok(())
+    }
+
+    /// Flush drive write cache to the media using 28-bit `FLUSH CACHE` (ATA cmd 0xE7,
+    /// documentation chapter 7.10), for legacy drives not supporting `FLUSH_CACHE_EXT`.
+    pub fn flush_28(&mut self) -> io::Result<()> {
+        let tf = TaskFile {
+            command: ATA_FLUSH_CACHE,
+            device: ATA_DEV_LBA,
+            timeout: Some(FLUSH_TIMEOUT),
+            ..Default::default()
+        };
+
+        self.retry(|ata| ata.raw_command(&tf, Transfer::NonData))?;
+        Ok(())
+    }
+
+    /// Flush drive write cache with [`Device::flush`] and close the device.
+    ///
+    /// Device is closed even if flushing fails. Error of either step is returned, so success
+    /// means all written data reached the media.
+    pub fn flush_and_close(mut self) -> io::Result<()> {
+        let flushed = self.flush();
+        let closed = self.close();
+        flushed.and(closed)
     }
 
     /// Read sector(s) from disk.
     ///
     /// Buffer size **must** be multiple of sector size. **It bypasses all protections and
-    /// caches/buffers.**
+    /// caches/buffers.** Range is checked with [`Device::validate_range`] first. Drives without
+    /// 48-bit addressing are read with [`Device::read_28`].
     #[inline]
-    pub fn read(&mut self, sector: u64, buffer: &mut [u8]) -> io::Result<()> {
-        self.0.raw_read(sector, buffer)
+    pub fn read(&mut self, sector: impl Into<Lba>, buffer: &mut [u8]) -> io::Result<()> {
+        let sector = sector.into().sector();
+        self.validate_range(sector, (buffer.len() / os::SECTOR_BYTES) as u64)?;
+        self.read_unchecked(sector, buffer)
+    }
+
+    /// Read sector(s) from disk like [`Device::read`], but without checking the range against
+    /// capacity reported in identification record.
+    ///
+    /// Out-of-range sectors are rejected by the drive itself. It allows probing drives whose
+    /// reported capacity does not match the sectors they really serve.
+    pub fn read_unchecked(&mut self, sector: impl Into<Lba>, buffer: &mut [u8]) -> io::Result<()> {
+        let sector = sector.into().sector();
+        if !self.lba48() {
+            return self.read_28(sector, buffer);
+        }
+        self.retry(|ata| ata.raw_read(sector, buffer))?;
+        Ok(())
+    }
+
+    /// Read consecutive sectors from disk into multiple buffers with a single command.
+    ///
+    /// Buffers are filled in order, as if they were one contiguous buffer. Only their total size
+    /// **must** be multiple of sector size. On Linux buffers are passed to `SG` as an iovec array,
+    /// avoiding a copy; on FreeBSD, and for drives without 48-bit addressing, data is read into
+    /// a temporary buffer and copied.
+    pub fn read_vectored(
+        &mut self,
+        sector: impl Into<Lba>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> io::Result<()> {
+        let sector = sector.into().sector();
+        let len: usize = bufs.iter().map(|buf| buf.len()).sum();
+        if !len.is_multiple_of(os::SECTOR_BYTES) || len > os::MAX_TRANSFER_BYTES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid vectored transfer of {} bytes", len),
+            ));
+        }
+        self.validate_range(sector, (len / os::SECTOR_BYTES) as u64)?;
+
+        if !self.lba48() {
+            let mut buffer = vec![0u8; len];
+            self.read_28(sector, &mut buffer)?;
+
+            let mut rest = &buffer[..];
+            for buf in bufs.iter_mut() {
+                let (head, tail) = rest.split_at(buf.len());
+                buf.copy_from_slice(head);
+                rest = tail;
+            }
+            return Ok(());
+        }
+
+        self.retry(|ata| ata.raw_read_vectored(sector, bufs))?;
+        Ok(())
+    }
+
+    /// Read the first sector of the drive (LBA 0), holding MBR or protective MBR of GPT.
+    pub fn read_boot_sector(&mut self) -> io::Result<[u8; os::SECTOR_BYTES]> {
+        let mut sector = [0u8; os::SECTOR_BYTES];
+        self.read(0, &mut sector)?;
+        Ok(sector)
+    }
+
+    /// Read the last sector of the drive, holding the backup GPT header.
+    ///
+    /// Last LBA follows [`Device::capacity_sectors`]. With HPA set, it is the last sector visible
+    /// to the host.
+    pub fn read_backup_gpt(&mut self) -> io::Result<[u8; os::SECTOR_BYTES]> {
+        let last = self.capacity_sectors()?.checked_sub(1).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "drive reports no sectors")
+        })?;
+
+        let mut sector = [0u8; os::SECTOR_BYTES];
+        self.read(last, &mut sector)?;
+        Ok(sector)
     }
 
     /// Write sector(s) to disk.
     ///
     /// Buffer size **must** be multiple of sector size. **It bypasses all protections and
-    /// caches/buffers.**
+    /// caches/buffers.** Range is checked with [`Device::validate_range`] first, and alignment to
+    /// physical sectors as set with [`Device::set_strict_alignment`]. Drives without 48-bit
+    /// addressing are written with [`Device::write_28`].
     #[inline]
-    pub fn write(&mut self, sector: u64, buffer: &[u8]) -> io::Result<()> {
-        self.0.raw_write(sector, buffer)
+    pub fn write(&mut self, sector: impl Into<Lba>, buffer: &[u8]) -> io::Result<()> {
+        let sector = sector.into().sector();
+        let count = (buffer.len() / os::SECTOR_BYTES) as u64;
+        self.validate_range(sector, count)?;
+        self.check_alignment(sector, count)?;
+        if self.skip_dry_run(format_args!(
+            "write of {} bytes at {}",
+            buffer.len(),
+            sector
+        ))? {
+            return Ok(());
+        }
+        if !self.lba48() {
+            return self.write_28(sector, buffer);
+        }
+        self.retry(|ata| ata.raw_write(sector, buffer))?;
+        Ok(())
+    }
+
+    /// Write sector(s) to disk using `WRITE DMA FUA EXT` (ATA cmd 0x3D, documentation chapter
+    /// 7.59), which returns only after data reached the media, bypassing drive write cache.
+    ///
+    /// It gives per-write durability without a separate [`Device::flush`]. Buffer size **must**
+    /// be multiple of sector size. Drives not supporting it (identify word 84 bit 6) are reported
+    /// with an error. Alignment is checked like for [`Device::write`].
+    pub fn write_fua(&mut self, sector: impl Into<Lba>, buffer: &[u8]) -> io::Result<()> {
+        let sector = sector.into().sector();
+        let count = (buffer.len() / os::SECTOR_BYTES) as u64;
+        self.validate_range(sector, count)?;
+        self.check_alignment(sector, count)?;
+        if self
+            .ident
+            .is_some_and(|id| !id.supports_lba48() || !id.supports_fua())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "WRITE DMA FUA EXT not supported",
+            ));
+        }
+        if self.skip_dry_run(format_args!(
+            "FUA write of {} bytes at {}",
+            buffer.len(),
+            sector
+        ))? {
+            return Ok(());
+        }
+
+        let tf = TaskFile {
+            command: ATA_WRITE_DMA_FUA_EXT,
+            count: (buffer.len() / os::SECTOR_BYTES) as u16,
+            lba: sector,
+            device: ATA_DEV_LBA,
+            ext: true,
+            ..Default::default()
+        };
+
+        self.retry(|ata| ata.raw_command(&tf, Transfer::DmaOut(buffer)))?;
+        Ok(())
+    }
+
+    /// Read sector(s) from disk using 28-bit `READ DMA` (ATA cmd 0xC8, documentation of ATA8-ACS
+    /// chapter 7.24), for legacy drives without 48-bit addressing.
+    ///
+    /// Buffer size **must** be multiple of sector size. Whole range must lie below sector 2^28.
+    /// Transfers longer than 256 sectors are split into multiple commands.
+    pub fn read_28(&mut self, sector: u64, buffer: &mut [u8]) -> io::Result<()> {
+        Self::check_lba28(sector, buffer.len())?;
+
+        for (i, chunk) in buffer
+            .chunks_mut(LBA28_MAX_SECTORS * os::SECTOR_BYTES)
+            .enumerate()
+        {
+            let tf = Self::lba28_taskfile(
+                ATA_READ_DMA,
+                sector + (i * LBA28_MAX_SECTORS) as u64,
+                chunk.len(),
+            );
+            self.retry(|ata| ata.raw_command(&tf, Transfer::DmaIn(chunk)))?;
+        }
+        Ok(())
+    }
+
+    /// Write sector(s) to disk using 28-bit `WRITE DMA` (ATA cmd 0xCA, documentation of ATA8-ACS
+    /// chapter 7.67), for legacy drives without 48-bit addressing.
+    ///
+    /// Buffer size **must** be multiple of sector size. Whole range must lie below sector 2^28.
+    /// Transfers longer than 256 sectors are split into multiple commands.
+    pub fn write_28(&mut self, sector: u64, buffer: &[u8]) -> io::Result<()> {
+        Self::check_lba28(sector, buffer.len())?;
+        if self.skip_dry_run(format_args!(
+            "write_28 of {} bytes at {}",
+            buffer.len(),
+            sector
+        ))? {
+            return Ok(());
+        }
+
+        for (i, chunk) in buffer
+            .chunks(LBA28_MAX_SECTORS * os::SECTOR_BYTES)
+            .enumerate()
+        {
+            let tf = Self::lba28_taskfile(
+                ATA_WRITE_DMA,
+                sector + (i * LBA28_MAX_SECTORS) as u64,
+                chunk.len(),
+            );
+            self.retry(|ata| ata.raw_command(&tf, Transfer::DmaOut(chunk)))?;
+        }
+        Ok(())
+    }
+
+    /// Return the largest single transfer in bytes the operating system accepts for this device.
+    ///
+    /// On Linux it is `max_sectors_kb` of the block device queue, on FreeBSD it is `MAXPHYS`.
+    /// If it cannot be determined, a conservative [`STREAM_CHUNK_SECTORS`] worth of bytes is
+    /// returned.
+    pub fn max_transfer_bytes(&self) -> usize {
+        os::max_transfer_bytes(&self.path)
+            .unwrap_or(STREAM_CHUNK_SECTORS as usize * os::SECTOR_BYTES)
+    }
+
+    /// Check that `count` sectors starting at `start` fit within the drive capacity.
+    ///
+    /// Capacity is read from the identification record when device is opened. If it could not be
+    /// read then, every range is accepted and left for the drive to validate.
+    pub fn validate_range(&self, start: u64, count: u64) -> io::Result<()> {
+        let capacity = match self.ident {
+            Some(id) => id.addressable_sectors(),
+            None => return Ok(()),
+        };
+
+        match start.checked_add(count) {
+            Some(end) if end <= capacity => Ok(()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "sector {} beyond capacity {}",
+                    start.saturating_add(count.saturating_sub(1)),
+                    capacity
+                ),
+            )),
+        }
+    }
+
+    /// Read identification record again and replace the one cached when device was opened.
+    ///
+    /// Cached record is used for bounds checking ([`Device::validate_range`]) and command
+    /// selection, so it should be refreshed after the capacity changes outside of this handle,
+    /// like by another process or tool changing HPA or DCO. [`Device::set_max_address`] refreshes
+    /// it itself.
+    pub fn refresh_identify(&mut self) -> io::Result<&IdentifyDeviceData> {
+        let id = self.info()?;
+        Ok(self.ident.insert(id))
+    }
+
+    /// Return number of sectors addressable by the host.
+    ///
+    /// Identification record read when device was opened is used. If it was not available then,
+    /// it is read now and kept for later calls.
+    pub fn capacity_sectors(&mut self) -> io::Result<u64> {
+        let id = match self.ident {
+            Some(id) => id,
+            None => {
+                let id = self.info()?;
+                self.ident = Some(id);
+                id
+            }
+        };
+        Ok(id.addressable_sectors())
     }
 
     /// Get identification record from disk.
@@ -194,15 +901,647 @@ impl Device {
     pub fn info(&mut self) -> io::Result<IdentifyDeviceData> {
         let mut u_ident = MaybeUninit::<IdentifyDeviceData>::uninit();
         let ident = unsafe {
-            self.0.raw_info(u_ident.as_mut_ptr())?;
+            let ptr = u_ident.as_mut_ptr();
+            self.retry(|ata| ata.raw_info(ptr))?;
             u_ident.assume_init()
         };
 
         Ok(ident)
     }
+
+    /// Get identification record from disk as the 512 bytes received from the drive.
+    ///
+    /// Nothing is swapped or interpreted, so it is suitable for archiving the record or feeding
+    /// it to another parser. Words are little-endian and strings have pairwise swapped letters,
+    /// as described in [`IdentifyDeviceData`].
+    pub fn identify_raw(&mut self) -> io::Result<[u8; 512]> {
+        let ident = self.info()?;
+
+        // Record is copied verbatim into the words, their native bytes are the received ones
+        let mut raw = [0u8; 512];
+        for (bytes, word) in raw.chunks_exact_mut(2).zip(ident.0.iter()) {
+            bytes.copy_from_slice(&word.to_ne_bytes());
+        }
+        Ok(raw)
+    }
+
+    /// Check if the drive supports feature set or command `feature`, see [`AtaFeature`].
+    ///
+    /// Identification record read when device was opened is used, `false` is returned if it was
+    /// not available.
+    pub fn supports_feature(&self, feature: AtaFeature) -> bool {
+        self.ident.is_some_and(|id| id.supports(feature))
+    }
+
+    /// Confirm that the opened drive has the expected serial number.
+    ///
+    /// Device paths like `/dev/sda` can change between boots or hot-plugs, so it should be called
+    /// before any destructive operation. Mismatch is reported as an error.
+    pub fn verify_identity(&mut self, expected_serial: &str) -> io::Result<()> {
+        let serial = self.info()?.get_serial();
+
+        if serial != expected_serial.trim() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "drive serial {:?} does not match expected {:?}",
+                    serial, expected_serial
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Execute an arbitrary ATA command built with [`AtaCommand`].
+    ///
+    /// **It bypasses all protections.** Nothing prevents issuing a destructive command, so use
+    /// named helpers where available.
+    pub fn execute(&mut self, mut cmd: AtaCommand) -> io::Result<AtaResult> {
+        let registers = self.retry(|ata| {
+            let (tf, transfer) = cmd.prepare()?;
+            ata.raw_command(&tf, transfer)
+        })?;
+
+        Ok(AtaResult {
+            registers,
+            data: cmd.into_data(),
+        })
+    }
+
+    /// Read a page of IDENTIFY DEVICE DATA log (General Purpose Log 0x30).
+    ///
+    /// Pages contain capabilities not covered by the legacy [`IdentifyDeviceData`], like page
+    /// 0x09 describing zoned devices. Page 0x00 lists pages supported by the drive. Details are in
+    /// chapter 9.11 of the documentation.
+    pub fn identify_data_log(&mut self, page: u8) -> io::Result<[u8; 512]> {
+        let mut buffer = [0u8; 512];
+        self.read_log_ext(LOG_IDENTIFY_DEVICE_DATA, page as u16, &mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Read General Purpose Log directory (log 0x00) and return `(log address, page count)` of
+    /// every log supported by the drive.
+    ///
+    /// It should be consulted before reading optional logs, like self-test, device statistics or
+    /// SCT ones. Details are in chapter 9.2 of the documentation.
+    pub fn log_directory(&mut self) -> io::Result<Vec<(u8, u16)>> {
+        let mut buffer = [0u8; 512];
+        self.read_log_ext(LOG_DIRECTORY, 0, &mut buffer)?;
+        Ok(Self::parse_log_directory(&buffer))
+    }
+
+    /// Decode log directory, skipping version word and unsupported (zero page) logs
+    fn parse_log_directory(data: &[u8; 512]) -> Vec<(u8, u16)> {
+        data.chunks_exact(2)
+            .enumerate()
+            .skip(1)
+            .map(|(log, word)| (log as u8, u16::from_le_bytes([word[0], word[1]])))
+            .filter(|&(_, pages)| pages != 0)
+            .collect()
+    }
+
+    /// Read a buffer of any size (multiple of sector size) starting at `sector`.
+    ///
+    /// Buffer is read in chunks of [`Device::max_transfer_bytes`], so it is not limited by the
+    /// operating system like [`Device::read`] is.
+    pub fn read_all(&mut self, sector: impl Into<Lba>, buffer: &mut [u8]) -> io::Result<()> {
+        self.read_all_with_progress(sector, buffer, |_, _| {})
+    }
+
+    /// Read a buffer like [`Device::read_all`], calling `progress` with bytes done and bytes
+    /// total after each chunk.
+    pub fn read_all_with_progress<F>(
+        &mut self,
+        sector: impl Into<Lba>,
+        buffer: &mut [u8],
+        mut progress: F,
+    ) -> io::Result<()>
+    where
+        F: FnMut(u64, u64),
+    {
+        let sector = sector.into().sector();
+        self.validate_range(sector, (buffer.len() / os::SECTOR_BYTES) as u64)?;
+
+        let total = buffer.len() as u64;
+        let chunk_bytes = self.chunk_bytes();
+        let mut lba = sector;
+        let mut done = 0;
+
+        for chunk in buffer.chunks_mut(chunk_bytes) {
+            self.read(lba, chunk)?;
+            lba += (chunk.len() / os::SECTOR_BYTES) as u64;
+            done += chunk.len() as u64;
+            progress(done, total);
+        }
+        Ok(())
+    }
+
+    /// Write a buffer of any size (multiple of sector size) starting at `sector`.
+    ///
+    /// Buffer is written in chunks of [`Device::max_transfer_bytes`], each with
+    /// [`Device::write`]. If a chunk fails, the preceding ones are already written.
+    pub fn write_all(&mut self, sector: impl Into<Lba>, buffer: &[u8]) -> io::Result<()> {
+        self.write_all_with_progress(sector, buffer, |_, _| {})
+    }
+
+    /// Write a buffer like [`Device::write_all`], calling `progress` with bytes done and bytes
+    /// total after each chunk.
+    pub fn write_all_with_progress<F>(
+        &mut self,
+        sector: impl Into<Lba>,
+        buffer: &[u8],
+        mut progress: F,
+    ) -> io::Result<()>
+    where
+        F: FnMut(u64, u64),
+    {
+        let sector = sector.into().sector();
+        self.validate_range(sector, (buffer.len() / os::SECTOR_BYTES) as u64)?;
+
+        let total = buffer.len() as u64;
+        let chunk_bytes = self.chunk_bytes();
+        let mut lba = sector;
+        let mut done = 0;
+
+        for chunk in buffer.chunks(chunk_bytes) {
+            self.write(lba, chunk)?;
+            lba += (chunk.len() / os::SECTOR_BYTES) as u64;
+            done += chunk.len() as u64;
+            progress(done, total);
+        }
+        Ok(())
+    }
+
+    /// Read `count` sectors starting at `start`, tolerating unreadable sectors.
+    ///
+    /// Range is read in chunks of [`STREAM_CHUNK_SECTORS`]. If a chunk is rejected by the drive,
+    /// its sectors are retried one by one and each sector that still cannot be read is filled
+    /// with `fill` bytes. Returns the assembled buffer and the list of unreadable sectors. Ranges
+    /// past capacity and errors not reported by the drive, like of a closed device, are returned
+    /// as errors.
+    pub fn read_range_lenient(
+        &mut self,
+        start: u64,
+        count: u64,
+        fill: u8,
+    ) -> io::Result<(Vec<u8>, Vec<u64>)> {
+        self.validate_range(start, count)?;
+
+        let mut buffer = vec![0u8; count as usize * os::SECTOR_BYTES];
+        let mut bad = Vec::new();
+        let mut done = 0;
+
+        while done < count {
+            let chunk_sectors = (count - done).min(STREAM_CHUNK_SECTORS);
+            let offset = done as usize * os::SECTOR_BYTES;
+            let chunk = &mut buffer[offset..offset + chunk_sectors as usize * os::SECTOR_BYTES];
+
+            self.transfer_lenient(
+                start + done,
+                chunk,
+                |dev, lba, data| dev.read(lba, data),
+                |lba, sector, ok| {
+                    if !ok {
+                        sector.iter_mut().for_each(|b| *b = fill);
+                        bad.push(lba);
+                    }
+                },
+            )?;
+            done += chunk_sectors;
+        }
+
+        Ok((buffer, bad))
+    }
+
+    /// Run `transfer` over `chunk` of sectors starting at `lba`, returning whether it succeeded
+    /// at once.
+    ///
+    /// If the drive rejects the chunk (like due to an unreadable sector), `transfer` is run again
+    /// for each of its sectors and `sector` is called with the address, data and outcome of every
+    /// one. Errors not reported by the drive are returned.
+    fn transfer_lenient<T, S>(
+        &mut self,
+        lba: u64,
+        chunk: &mut [u8],
+        mut transfer: T,
+        mut sector: S,
+    ) -> io::Result<bool>
+    where
+        T: FnMut(&mut Self, u64, &mut [u8]) -> io::Result<()>,
+        S: FnMut(u64, &mut [u8], bool),
+    {
+        match transfer(self, lba, chunk) {
+            Ok(()) => return Ok(true),
+            Err(err) if !os::is_command_error(&err) => return Err(err),
+            Err(_) => {}
+        }
+
+        for (i, data) in chunk.chunks_mut(os::SECTOR_BYTES).enumerate() {
+            let lba = lba + i as u64;
+            match transfer(self, lba, data) {
+                Ok(()) => sector(lba, data, true),
+                Err(err) if os::is_command_error(&err) => sector(lba, data, false),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(false)
+    }
+
+    /// Rewrite sector `lba` in place, to clear a pending (unstable) sector.
+    ///
+    /// Sector is read first. If it is unreadable, zeros are written instead and a warning is
+    /// logged, as its contents are lost anyway. The write makes the drive either keep the sector,
+    /// when the write verifies, or reallocate it to a spare one; both clear it from the pending
+    /// sector count. Write cache is flushed afterwards, so that the drive acts on it immediately.
+    /// **Contents of an unreadable sector are replaced with zeros.**
+    pub fn reassign_sector(&mut self, lba: u64) -> io::Result<()> {
+        self.validate_range(lba, 1)?;
+
+        let mut sector = [0u8; os::SECTOR_BYTES];
+        if let Err(err) = self.read(lba, &mut sector) {
+            log::warn!(
+                "{}: sector {} unreadable ({}), rewriting with zeros",
+                self.path.display(),
+                lba,
+                err
+            );
+            sector = [0u8; os::SECTOR_BYTES];
+        }
+
+        self.write(lba, &sector)?;
+        self.flush()
+    }
+
+    /// Write `pattern` repeatedly over `count` sectors starting at `start`, collecting sectors
+    /// that failed to write.
+    ///
+    /// Pattern is tiled continuously over the whole range, it does not restart at sector
+    /// boundaries. Range is written in chunks of [`STREAM_CHUNK_SECTORS`]. If a chunk is rejected
+    /// by the drive, its sectors are written one by one and each sector that still fails is
+    /// reported. Errors not reported by the drive are returned. **Data in the range is
+    /// destroyed.**
+    pub fn write_pattern(
+        &mut self,
+        start: u64,
+        count: u64,
+        pattern: &[u8],
+    ) -> io::Result<Vec<u64>> {
+        if pattern.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "empty test pattern",
+            ));
+        }
+        self.validate_range(start, count)?;
+        if self.skip_dry_run(format_args!(
+            "pattern write of {} sectors at {}",
+            count, start
+        ))? {
+            return Ok(Vec::new());
+        }
+
+        let mut buffer = vec![0u8; STREAM_CHUNK_SECTORS as usize * os::SECTOR_BYTES];
+        let mut bad = Vec::new();
+        let mut done = 0;
+
+        while done < count {
+            let chunk_sectors = (count - done).min(STREAM_CHUNK_SECTORS);
+            let chunk = &mut buffer[..chunk_sectors as usize * os::SECTOR_BYTES];
+
+            let phase = (done as usize * os::SECTOR_BYTES) % pattern.len();
+            for (i, byte) in chunk.iter_mut().enumerate() {
+                *byte = pattern[(phase + i) % pattern.len()];
+            }
+
+            self.transfer_lenient(
+                start + done,
+                chunk,
+                |dev, lba, data| dev.write(lba, data),
+                |lba, _, ok| {
+                    if !ok {
+                        bad.push(lba);
+                    }
+                },
+            )?;
+            done += chunk_sectors;
+        }
+
+        Ok(bad)
+    }
+
+    /// Check whether a drive really stores data up to `claimed_sectors`, detecting counterfeit
+    /// drives which report a fake capacity and silently wrap writes.
+    ///
+    /// A unique pattern is written to [`PROBE_SAMPLES`] sectors spread evenly over the claimed
+    /// capacity (including the last one), then all of them are read back and verified. Original
+    /// contents are restored afterwards, in reverse order so that aliased sectors end up with
+    /// their real data. **It writes to the drive and data may be lost if interrupted.**
+    pub fn probe_real_capacity(&mut self, claimed_sectors: u64) -> io::Result<bool> {
+        if claimed_sectors == 0 {
+            return Ok(true);
+        }
+        if self.skip_dry_run(format_args!(
+            "capacity probe of {} sectors",
+            claimed_sectors
+        ))? {
+            return Ok(true);
+        }
+
+        let mut samples: Vec<u64> = (0..PROBE_SAMPLES)
+            .map(|i| (claimed_sectors - 1) * i / (PROBE_SAMPLES - 1))
+            .collect();
+        samples.dedup();
+
+        let pattern = |lba: u64| {
+            let mut sector = [0u8; os::SECTOR_BYTES];
+            for (i, chunk) in sector.chunks_exact_mut(16).enumerate() {
+                chunk[..8].copy_from_slice(&PROBE_MAGIC.to_le_bytes());
+                chunk[8..].copy_from_slice(&(lba ^ i as u64).to_le_bytes());
+            }
+            sector
+        };
+
+        let mut originals = Vec::with_capacity(samples.len());
+        for &lba in &samples {
+            let mut sector = [0u8; os::SECTOR_BYTES];
+            self.read(lba, &mut sector)?;
+            originals.push(sector);
+        }
+
+        let mut verdict = Ok(true);
+        for &lba in &samples {
+            if let Err(err) = self.write(lba, &pattern(lba)) {
+                verdict = Err(err);
+                break;
+            }
+        }
+
+        if let Ok(true) = verdict {
+            let mut sector = [0u8; os::SECTOR_BYTES];
+            for &lba in &samples {
+                match self.read(lba, &mut sector) {
+                    Ok(()) if sector == pattern(lba) => {}
+                    Ok(()) => {
+                        verdict = Ok(false);
+                        break;
+                    }
+                    Err(err) => {
+                        verdict = Err(err);
+                        break;
+                    }
+                }
+            }
+        }
+
+        for (lba, original) in samples.iter().zip(originals.iter()).rev() {
+            self.write(*lba, original)?;
+        }
+
+        verdict
+    }
+
+    /// Hash sectors `start..end` of disk.
+    ///
+    /// Sectors are streamed directly into the hasher in chunks of [`STREAM_CHUNK_SECTORS`], so
+    /// no copy of the whole range is kept in memory. Typical use is a verification hash of a
+    /// disk image, like `dev.hash_range::<sha2::Sha256>(0, id.get_sector_count())`.
+    pub fn hash_range<D: Digest>(&mut self, start: u64, end: u64) -> io::Result<Output<D>> {
+        if start > end {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "start sector past end sector",
+            ));
+        }
+
+        let mut hasher = D::new();
+        let mut buffer = vec![0u8; STREAM_CHUNK_SECTORS as usize * os::SECTOR_BYTES];
+        let mut sector = start;
+
+        while sector < end {
+            let count = (end - sector).min(STREAM_CHUNK_SECTORS);
+            let chunk = &mut buffer[..count as usize * os::SECTOR_BYTES];
+
+            self.read(sector, chunk)?;
+            hasher.update(&chunk[..]);
+            sector += count;
+        }
+
+        Ok(hasher.finalize())
+    }
+
+    /// Check ranges of a manifest of `(start, count, hash)` entries and return starts of those
+    /// whose contents no longer match.
+    ///
+    /// Each range is hashed with [`Device::hash_range`] using `D`, a digest producing 32-byte
+    /// hashes, like `dev.verify_against_manifest::<sha2::Sha256>(&manifest)`. Read errors abort
+    /// the verification.
+    pub fn verify_against_manifest<D: Digest<OutputSize = U32>>(
+        &mut self,
+        manifest: &[(u64, u64, [u8; 32])],
+    ) -> io::Result<Vec<u64>> {
+        let mut mismatched = Vec::new();
+
+        for &(start, count, ref expected) in manifest {
+            self.validate_range(start, count)?;
+            let hash = self.hash_range::<D>(start, start + count)?;
+            if hash.as_slice() != &expected[..] {
+                mismatched.push(start);
+            }
+        }
+
+        Ok(mismatched)
+    }
+
+    /// Compare `count` sectors starting at `start` with the same sectors of `other` and return
+    /// those that differ.
+    ///
+    /// Both drives are read in chunks of [`STREAM_CHUNK_SECTORS`], so no copy of the whole range
+    /// is kept in memory. Typical use is verification of a drive-to-drive copy, localized to
+    /// sectors. Read errors of either drive abort the comparison.
+    pub fn diff(&mut self, other: &mut Device, start: u64, count: u64) -> io::Result<Vec<u64>> {
+        self.validate_range(start, count)?;
+        other.validate_range(start, count)?;
+
+        let mut ours = vec![0u8; STREAM_CHUNK_SECTORS as usize * os::SECTOR_BYTES];
+        let mut theirs = ours.clone();
+        let mut differing = Vec::new();
+        let mut done = 0;
+
+        while done < count {
+            let chunk_sectors = (count - done).min(STREAM_CHUNK_SECTORS);
+            let len = chunk_sectors as usize * os::SECTOR_BYTES;
+
+            self.read(start + done, &mut ours[..len])?;
+            other.read(start + done, &mut theirs[..len])?;
+            Self::diff_sectors(&ours[..len], &theirs[..len], start + done, &mut differing);
+            done += chunk_sectors;
+        }
+
+        Ok(differing)
+    }
+
+    /// Append to `differing` numbers of sectors that differ between `ours` and `theirs`, both
+    /// starting at sector `start`
+    fn diff_sectors(ours: &[u8], theirs: &[u8], start: u64, differing: &mut Vec<u64>) {
+        let sectors = ours
+            .chunks(os::SECTOR_BYTES)
+            .zip(theirs.chunks(os::SECTOR_BYTES));
+        for (i, (a, b)) in sectors.enumerate() {
+            if a != b {
+                differing.push(start + i as u64);
+            }
+        }
+    }
+
+    /// Tell if 48-bit commands can be used, assuming they can if drive was not identified
+    fn lba48(&self) -> bool {
+        self.ident.is_none_or(|id| id.supports_lba48())
+    }
+
+    /// Fail if drive is known not to support General Purpose Logging
+    fn check_gpl(&self) -> io::Result<()> {
+        match self.ident {
+            Some(id) if !id.supports_gpl() => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "General Purpose Logging not supported",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Apply handle settings (controller retries, sense buffer size) to a newly opened handle
+    fn configure(&self, ata: &mut os::ATA) {
+        if let Some(n) = self.controller_retries {
+            ata.set_retries(n);
+        }
+        if let Some(len) = self.sense_len {
+            ata.set_sense_len(len);
+        }
+    }
+
+    /// Log a destructive operation and tell if it should be skipped because of dry-run mode.
+    /// Fails if the disk is in use and [`Device::set_refuse_in_use`] is enabled.
+    fn skip_dry_run(&self, operation: fmt::Arguments<'_>) -> io::Result<bool> {
+        if self.dry_run {
+            log::info!("{}: dry-run, skipped {}", self.path.display(), operation);
+            return Ok(true);
+        }
+        if self.refuse_in_use && self.is_in_use()? {
+            return Err(io::Error::new(
+                io::ErrorKind::ResourceBusy,
+                format!(
+                    "{}: refused {}, disk is in use",
+                    self.path.display(),
+                    operation
+                ),
+            ));
+        }
+        Ok(false)
+    }
+
+    /// Check that a transfer of `len` bytes starting at `sector` is addressable with 28 bits
+    fn check_lba28(sector: u64, len: usize) -> io::Result<()> {
+        let end = sector.saturating_add((len / os::SECTOR_BYTES) as u64);
+        if end > LBA28_LIMIT {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("sector {} beyond 28-bit addressing", end - 1),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Size in bytes of a single transfer of chunked operations, a whole number of sectors.
+    /// 28-bit commands are limited to 256 sectors.
+    fn chunk_bytes(&self) -> usize {
+        let mut sectors = (self.max_transfer_bytes() / os::SECTOR_BYTES).max(1);
+        if !self.lba48() {
+            sectors = sectors.min(256);
+        }
+        sectors * os::SECTOR_BYTES
+    }
+
+    /// Build a 28-bit transfer command. Sector count of 256 is encoded as 0.
+    fn lba28_taskfile(command: u8, sector: u64, len: usize) -> TaskFile {
+        TaskFile {
+            command,
+            count: ((len / os::SECTOR_BYTES) & 0xFF) as u16,
+            lba: sector,
+            device: ATA_DEV_LBA,
+            ext: false,
+            ..Default::default()
+        }
+    }
+
+    /// Run a low-level command, retrying transient errors as configured with
+    /// [`Device::with_retries`]
+    fn retry<T, F>(&mut self, mut f: F) -> io::Result<T>
+    where
+        F: FnMut(&mut os::ATA) -> io::Result<T>,
+    {
+        let mut attempt = 0;
+        loop {
+            match f(&mut self.ata) {
+                Err(err) if attempt < self.retries && os::is_transient(&err) => {
+                    thread::sleep(RETRY_BACKOFF * 2u32.pow(attempt.min(6)));
+                    attempt += 1;
+                }
+                ans => return ans,
+            }
+        }
+    }
+
+    /// Issue `SET FEATURES` (ATA cmd 0xEF, documentation chapter 7.45) with given subcommand in
+    /// features register and its parameter in sector count register
+    fn set_features(&mut self, subcommand: u8, count: u16) -> io::Result<()> {
+        let tf = TaskFile {
+            command: ATA_SET_FEATURES,
+            features: subcommand as u16,
+            count,
+            device: ATA_DEV_LBA,
+            ..Default::default()
+        };
+
+        self.retry(|ata| ata.raw_command(&tf, Transfer::NonData))?;
+        Ok(())
+    }
+
+    /// Read General Purpose Log pages using `READ_LOG_EXT` (ATA cmd 0x2F, documentation chapter
+    /// 7.24). Buffer size **must** be multiple of sector size.
+    fn read_log_ext(&mut self, log: u8, page: u16, buffer: &mut [u8]) -> io::Result<()> {
+        self.check_gpl()?;
+        let tf = TaskFile {
+            command: ATA_READ_LOG_EXT,
+            count: (buffer.len() / os::SECTOR_BYTES) as u16,
+            // Page number is split: bits 0..7 go to LBA 8..15, bits 8..15 go to LBA 32..39
+            lba: log as u64 | (page as u64 & 0xFF) << 8 | (page as u64 >> 8) << 32,
+            device: ATA_DEV_LBA,
+            ext: true,
+            ..Default::default()
+        };
+
+        self.retry(|ata| ata.raw_command(&tf, Transfer::PioIn(buffer)))?;
+        Ok(())
+    }
+
+    /// Write General Purpose Log pages using `WRITE_LOG_EXT` (ATA cmd 0x3F, documentation chapter
+    /// 7.62). Buffer size **must** be multiple of sector size.
+    fn write_log_ext(&mut self, log: u8, page: u16, buffer: &[u8]) -> io::Result<AtaTaskfileOut> {
+        self.check_gpl()?;
+        let tf = TaskFile {
+            command: ATA_WRITE_LOG_EXT,
+            count: (buffer.len() / os::SECTOR_BYTES) as u16,
+            lba: log as u64 | (page as u64 & 0xFF) << 8 | (page as u64 >> 8) << 32,
+            device: ATA_DEV_LBA,
+            ext: true,
+            ..Default::default()
+        };
+
+        self.retry(|ata| ata.raw_command(&tf, Transfer::PioOut(buffer)))
+    }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use std::{mem, path};
 
@@ -227,6 +1566,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn check_diff_sectors() {
+        let ours = vec![0u8; 4 * os::SECTOR_BYTES];
+        let mut theirs = ours.clone();
+        theirs[os::SECTOR_BYTES] = 1;
+        theirs[4 * os::SECTOR_BYTES - 1] = 1;
+
+        let mut differing = vec![7];
+        Device::diff_sectors(&ours, &theirs, 100, &mut differing);
+        assert_eq!(differing, vec![7, 101, 103]);
+    }
+
+    #[test]
+    fn check_log_directory() {
+        let mut data = [0u8; 512];
+        data[0] = 0x01;
+        data[2 * 0x30] = 0x09;
+        data[2 * 0xE0] = 0x01;
+
+        assert_eq!(
+            Device::parse_log_directory(&data),
+            vec![(0x30, 9), (0xE0, 1)]
+        );
+    }
+
     #[test]
     fn check_drive_id() -> io::Result<()> {
         let dp = path::Path::new(get_def_drive());
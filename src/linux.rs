@@ -1,11 +1,11 @@
 //! Biblioteka umożliwiający "surowy" dostęp do dysku.
 //!
 //! * Odczyt dowolnego sektora bez sprawdzenia poprawnego zakresu (błąd jest
-//! wtedy zgłaszany przez sam kontroler dysku) i z pominięciem wszystkich
-//! cache'y systemu operacyjnego
+//!   wtedy zgłaszany przez sam kontroler dysku) i z pominięciem wszystkich
+//!   cache'y systemu operacyjnego
 //!
 //! * Odczyt numeru seryjnego, modelu, oznaczenia firmware i raportowanej
-//! pojemności dysku
+//!   pojemności dysku
 //!
 //! Operacje wykonywane są za pośrednictwem ioctl-i `SG_IO` (odczyt sektora) i
 //! `HDIO_DRIVE_CMD` (odczyt metryki dysku)
@@ -13,15 +13,25 @@
 #![allow(dead_code)]
 #![allow(clippy::identity_op)]
 
-use std::{ffi::CString, io, path::Path, ptr};
+use std::{
+    ffi::CString,
+    fs,
+    io::{self, IoSliceMut},
+    mem,
+    path::{Path, PathBuf},
+    ptr,
+};
 
 use libc::{self, c_int, c_ulong, ioctl};
 
-use crate::RawAta;
+use crate::{
+    AtaTaskfileOut, CommandError, Direction, RawAta, SenseData, TaskFile, Transfer, Transport,
+};
 
 pub const SECTOR_BYTES: usize = 512;
 pub const MAX_TRANSFER_SECTORS: u64 = 65_536;
 pub const MAX_TRANSFER_BYTES: usize = MAX_TRANSFER_SECTORS as usize * SECTOR_BYTES;
+pub const LOCK_DIR: &str = "/run/lock";
 
 const HDIO_DRIVE_CMD: c_ulong = 0x031f;
 const SG_IO: c_ulong = 0x2285;
@@ -29,21 +39,86 @@ const SG_IO: c_ulong = 0x2285;
 const SG_ATA_16: u8 = 0x85;
 const SG_ATA_16_LEN: u8 = 16;
 const SG_ATA_LBA48: u8 = 1;
+const SG_ATA_PROTO_NON_DATA: u8 = 3 << 1;
+const SG_ATA_PROTO_PIO_IN: u8 = 4 << 1;
+const SG_ATA_PROTO_PIO_OUT: u8 = 5 << 1;
 const SG_ATA_PROTO_DMA: u8 = 6 << 1;
 
 const SG_FLAG_DIRECT_IO: u32 = 1;
+const SG_FLAG_MMAP_IO: u32 = 4;
 
+const SG_SET_FORCE_PACK_ID: c_ulong = 0x227B;
+const SG_SCSI_RESET: c_ulong = 0x2284;
+const SG_SCSI_RESET_DEVICE: c_int = 1;
+const SG_GET_RESERVED_SIZE: c_ulong = 0x2272;
+const SG_SET_RESERVED_SIZE: c_ulong = 0x2275;
+
+const SG_CDB2_TLEN_NODATA: u8 = 0 << 0;
 const SG_CDB2_TLEN_NSECT: u8 = 2 << 0;
 const SG_CDB2_TLEN_SECTORS: u8 = 1 << 2;
 const SG_CDB2_TDIR_TO_DEV: u8 = 0 << 3;
 const SG_CDB2_TDIR_FROM_DEV: u8 = 1 << 3;
+const SG_CDB2_CHECK_COND: u8 = 1 << 5;
+
+const SENSE_FIXED: u8 = 0x70;
+const SENSE_DESCRIPTOR: u8 = 0x72;
+const SENSE_KEY_NO_SENSE: u8 = 0x00;
+const SENSE_KEY_RECOVERED_ERROR: u8 = 0x01;
+const SENSE_KEY_NOT_READY: u8 = 0x02;
+const SENSE_KEY_ILLEGAL_REQUEST: u8 = 0x05;
+const SENSE_KEY_UNIT_ATTENTION: u8 = 0x06;
+const SENSE_KEY_DATA_PROTECT: u8 = 0x07;
+const SENSE_KEY_ABORTED_COMMAND: u8 = 0x0B;
+const SENSE_ASCQ_ATA_INFO_AVAILABLE: u8 = 0x1D;
+const SENSE_DESC_ATA_STATUS_RETURN: u8 = 0x09;
+
+const SENSE_DEFAULT_LEN: u8 = 32;
+
+const ATA_STATUS_ERR: u8 = 0x01;
+
+const SCSI_GENERIC_MAJOR: u32 = 21;
+
+const SG_DRIVER_SENSE: u16 = 0x08;
 
 const SG_DXFER_NONE: i32 = -1;
 const SG_DXFER_TO_DEV: i32 = -2;
 const SG_DXFER_FROM_DEV: i32 = -3;
 const SG_DXFER_TO_FROM_DEV: i32 = -4;
 
-pub(super) struct ATA(c_int);
+pub(super) struct ATA {
+    fd: c_int,
+    map: Option<SgMmap>,
+    retries: u8,
+    sense_len: u8,
+}
+
+/// Bufor rezerwowy sterownika `sg` odwzorowany w pamięci procesu
+struct SgMmap {
+    ptr: *mut u8,
+    len: usize,
+}
+
+// Odwzorowanie należy wyłącznie do jednego uchwytu
+unsafe impl Send for SgMmap {}
+
+impl Drop for SgMmap {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr as *mut libc::c_void, self.len) };
+    }
+}
+
+/// Opis bufora danych komendy `SG_IO`. Przy niezerowym `iovec_count` wskaźnik
+/// prowadzi do tablicy `sg_iovec_t`, a nie do samych danych.
+#[derive(Clone, Copy)]
+struct SgData {
+    protocol: u8,
+    tdir: u8,
+    direction: i32,
+    ptr: *mut u8,
+    len: usize,
+    iovec_count: u16,
+    flags: u32,
+}
 
 #[repr(C, packed)]
 struct Task {
@@ -54,7 +129,9 @@ struct Task {
     buffer: [u8; 512],
 }
 
-#[repr(C, packed)]
+/// Nagłówek `sg_io_hdr`. Układ musi odpowiadać strukturze jądra razem z
+/// wyrównaniem pól, jądro odczytuje i zapisuje ją w całości.
+#[repr(C)]
 struct SgTaskHdr<BT> {
     interface_id: u32,
     dxfer_direction: i32,
@@ -81,144 +158,402 @@ struct SgTaskHdr<BT> {
 }
 
 impl RawAta for ATA {
-    fn open<P>(dev: P) -> io::Result<Self>
+    fn open<P>(dev: P, exclusive: bool, direct: bool) -> io::Result<Self>
     where
         P: AsRef<Path>,
     {
         use std::os::unix::ffi::OsStrExt;
         let device = CString::new(dev.as_ref().as_os_str().as_bytes()).unwrap();
 
-        let h = unsafe { libc::open(device.as_ptr(), libc::O_DIRECT | libc::O_RDONLY) };
+        // O_EXCL na urządzeniu blokowym kończy się EBUSY, jeśli jest ono
+        // zamontowane lub używane przez jądro (RAID, LVM)
+        let mut flags = libc::O_RDONLY;
+        if exclusive {
+            flags |= libc::O_EXCL;
+        }
+        // Niektóre warstwy blokowe (kontenery, wirtualne dyski) odrzucają
+        // O_DIRECT z EINVAL
+        if direct {
+            flags |= libc::O_DIRECT;
+        }
+
+        let h = unsafe { libc::open(device.as_ptr(), flags) };
         if h < 0 {
             return Err(io::Error::last_os_error());
         }
 
-        Ok(ATA(h))
+        Ok(ATA {
+            fd: h,
+            map: None,
+            retries: 0,
+            sense_len: SENSE_DEFAULT_LEN,
+        })
     }
 
-    fn close(&mut self) {
-        unsafe {
-            libc::close(self.0);
+    fn try_clone(&self) -> io::Result<Self> {
+        // Kopia deskryptora współdzieli otwarty plik, ale komendy SG_IO są
+        // od siebie niezależne
+        let h = unsafe { libc::dup(self.fd) };
+        if h < 0 {
+            return Err(io::Error::last_os_error());
         }
+
+        Ok(ATA {
+            fd: h,
+            map: None,
+            retries: 0,
+            sense_len: SENSE_DEFAULT_LEN,
+        })
     }
 
-    fn raw_read(&mut self, sector: u64, buffer: &mut [u8]) -> io::Result<()> {
-        #![allow(unused_parens)]
-        let mut cdb = [0u8; 16];
-        let mut sb = [0u8; 32];
+    fn set_retries(&mut self, n: u8) {
+        self.retries = n;
+    }
 
-        // Wielokrotność sektora
-        assert_eq!(buffer.len() % SECTOR_BYTES, 0);
-
-        // Nie więcej niż maksymalny transfer
-        assert!(buffer.len() <= MAX_TRANSFER_BYTES);
-
-        let count = (buffer.len() / SECTOR_BYTES) as u32;
-
-        // Nawet nie PYTAJCIE o kolejność bajtów w polu zawierającym numer
-        // sektora (-_-,)
-
-        cdb[0] = SG_ATA_16;
-        cdb[1] = SG_ATA_LBA48 | SG_ATA_PROTO_DMA;
-        cdb[2] = SG_CDB2_TLEN_NSECT | SG_CDB2_TLEN_SECTORS | SG_CDB2_TDIR_FROM_DEV;
-        cdb[3] = 0; // FEAT_H
-        cdb[4] = 0; // FEAT_L
-        cdb[5] = (count >> 8) as u8; // NSect_H     = nsect08..16
-        cdb[6] = (count >> 0) as u8; // NSect_L     = nsect00..07
-        cdb[7] = (sector >> 24) as u8; // hob.lbal  = sector24..31
-        cdb[8] = (sector >> 0) as u8; // lob.lbal   = sector00..07
-        cdb[9] = (sector >> 32) as u8; // hob.lbam  = sector32..39
-        cdb[10] = (sector >> 8) as u8; // lob.lbam  = sector08..15
-        cdb[11] = (sector >> 40) as u8; // hob.lbah = sector40..47
-        cdb[12] = (sector >> 16) as u8; // lob.lbah = sector16..23
-        cdb[13] = 0b1110_0000; // LBA, DRV0
-        cdb[14] = 0x25; // READ DMA EXT/READ SECT EXT
+    fn close(&mut self) -> io::Result<()> {
+        // Uchwyt już zamknięty
+        if self.fd < 0 {
+            return Ok(());
+        }
 
-        let task = SgTaskHdr {
-            interface_id: 'S' as u32,
-            dxfer_direction: SG_DXFER_FROM_DEV,
-            cmd_len: SG_ATA_16_LEN,
-            mx_sb_len: sb.len() as u8,
+        self.map = None;
 
-            iovec_count: 0,
-            dxfer_len: 512 * count,
-            dxferp: buffer.as_mut_ptr(),
-            cmdp: &mut cdb[0] as *mut u8,
-            sbp: &mut sb[0] as *mut u8,
-            timeout: 1000, // ms
-            flags: SG_FLAG_DIRECT_IO,
-            pack_id: sector as u32,
-            usr_ptr: ptr::null_mut(),
-            status: 0,
-            masked_status: 0,
-            msg_status: 0,
-            sb_len_wr: 0,
-            host_status: 0,
-            driver_status: 0,
-            resid: 0,
-            duration: 0,
-            info: 0,
+        // Linux zwalnia deskryptor nawet przy błędzie, więc nie wolno zamykać
+        // go ponownie
+        let ans = unsafe { libc::close(self.fd) };
+        self.fd = -1;
+
+        if ans < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    fn raw_read(&mut self, sector: u64, buffer: &mut [u8]) -> io::Result<AtaTaskfileOut> {
+        let tf = TaskFile {
+            command: 0x25, // READ DMA EXT
+            count: (buffer.len() / SECTOR_BYTES) as u16,
+            lba: sector,
+            device: 0b1110_0000, // LBA, DRV0
+            ext: true,
+            ..Default::default()
         };
 
-        let ans = unsafe { ioctl(self.0, SG_IO, &task) };
+        self.raw_command(&tf, Transfer::DmaIn(buffer))
+            .map_err(|err| CommandError::wrap(err, "READ DMA EXT", &tf))
+    }
+
+    fn raw_write(&mut self, sector: u64, buffer: &[u8]) -> io::Result<AtaTaskfileOut> {
+        let tf = TaskFile {
+            command: 0x35, // WRITE DMA EXT
+            count: (buffer.len() / SECTOR_BYTES) as u16,
+            lba: sector,
+            device: 0b1110_0000, // LBA, DRV0
+            ext: true,
+            ..Default::default()
+        };
+
+        self.raw_command(&tf, Transfer::DmaOut(buffer))
+            .map_err(|err| CommandError::wrap(err, "WRITE DMA EXT", &tf))
+    }
+
+    fn raw_info(&mut self, ident: *mut super::IdentifyDeviceData) -> io::Result<AtaTaskfileOut> {
+        let mut t = Task {
+            command: 0xEC,
+            sector: 0x00,
+            feature: 0x00,
+            nsector: 0x01,
+            buffer: [0; 512],
+        };
+        let ans = unsafe { ioctl(self.fd, HDIO_DRIVE_CMD, &mut t) };
 
         if ans < 0 {
             return Err(io::Error::last_os_error());
         }
 
-        if sb[0] != 0 {
-            return Err(sg_error_to_io(sb[1]));
+        unsafe {
+            std::ptr::copy(
+                t.buffer.as_ptr() as *const super::IdentifyDeviceData,
+                ident,
+                1,
+            );
+        }
+
+        // Po wykonaniu komendy jądro umieszcza w nagłówku rejestry status, error
+        // i nsector
+        Ok(AtaTaskfileOut {
+            status: t.command,
+            error: t.sector,
+            count: t.feature as u16,
+            ..Default::default()
+        })
+    }
+
+    fn raw_read_vectored(
+        &mut self,
+        sector: u64,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> io::Result<AtaTaskfileOut> {
+        let len: usize = bufs.iter().map(|buf| buf.len()).sum();
+        let tf = TaskFile {
+            command: 0x25, // READ DMA EXT
+            count: (len / SECTOR_BYTES) as u16,
+            lba: sector,
+            device: 0b1110_0000, // LBA, DRV0
+            ext: true,
+            ..Default::default()
+        };
+
+        // `IoSliceMut` ma gwarantowany układ `struct iovec`, czyli również
+        // `sg_iovec_t`, więc tablica może trafić wprost do nagłówka
+        let data = SgData {
+            protocol: SG_ATA_PROTO_DMA,
+            tdir: SG_CDB2_TDIR_FROM_DEV,
+            direction: SG_DXFER_FROM_DEV,
+            ptr: bufs.as_mut_ptr() as *mut u8,
+            len,
+            iovec_count: bufs.len() as u16,
+            flags: SG_FLAG_DIRECT_IO,
+        };
+
+        self.sg_command(&tf, data)
+            .map_err(|err| CommandError::wrap(err, "READ DMA EXT", &tf))
+    }
+
+    fn raw_command(&mut self, tf: &TaskFile, data: Transfer<'_>) -> io::Result<AtaTaskfileOut> {
+        let (protocol, tdir, direction, dxferp, len) = match data {
+            Transfer::NonData => (
+                SG_ATA_PROTO_NON_DATA,
+                SG_CDB2_TDIR_FROM_DEV,
+                SG_DXFER_NONE,
+                ptr::null_mut(),
+                0,
+            ),
+            Transfer::PioIn(buffer) => (
+                SG_ATA_PROTO_PIO_IN,
+                SG_CDB2_TDIR_FROM_DEV,
+                SG_DXFER_FROM_DEV,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+            ),
+            Transfer::PioOut(buffer) => (
+                SG_ATA_PROTO_PIO_OUT,
+                SG_CDB2_TDIR_TO_DEV,
+                SG_DXFER_TO_DEV,
+                buffer.as_ptr() as *mut u8,
+                buffer.len(),
+            ),
+            Transfer::DmaIn(buffer) => (
+                SG_ATA_PROTO_DMA,
+                SG_CDB2_TDIR_FROM_DEV,
+                SG_DXFER_FROM_DEV,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+            ),
+            Transfer::DmaOut(buffer) => (
+                SG_ATA_PROTO_DMA,
+                SG_CDB2_TDIR_TO_DEV,
+                SG_DXFER_TO_DEV,
+                buffer.as_ptr() as *mut u8,
+                buffer.len(),
+            ),
+        };
+
+        self.sg_command(
+            tf,
+            SgData {
+                protocol,
+                tdir,
+                direction,
+                ptr: dxferp,
+                len,
+                iovec_count: 0,
+                flags: SG_FLAG_DIRECT_IO,
+            },
+        )
+    }
+}
+
+impl ATA {
+    /// Otwarcie węzła `sg` do odczytu i zapisu z buforem rezerwowym o rozmiarze
+    /// co najmniej `len` bajtów, odwzorowanym w pamięci. Jądro może przydzielić
+    /// mniejszy bufor, jego faktyczny rozmiar zwraca [`ATA::mmap_buffer`].
+    pub(super) fn open_mmap(
+        dev: &Path,
+        len: usize,
+        exclusive: bool,
+        direct: bool,
+    ) -> io::Result<Self> {
+        use std::os::unix::ffi::OsStrExt;
+        let device = CString::new(dev.as_os_str().as_bytes())?;
+
+        // Zapis do współdzielonego odwzorowania wymaga uchwytu z prawem zapisu
+        let mut flags = libc::O_RDWR;
+        if exclusive {
+            flags |= libc::O_EXCL;
+        }
+        if direct {
+            flags |= libc::O_DIRECT;
+        }
+
+        let h = unsafe { libc::open(device.as_ptr(), flags) };
+        if h < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut ata = ATA {
+            fd: h,
+            map: None,
+            retries: 0,
+            sense_len: SENSE_DEFAULT_LEN,
+        };
+
+        // Warstwa blokowa też obsługuje SG_SET/GET_RESERVED_SIZE, a mmap na
+        // urządzeniu blokowym odwzorowuje pamięć podręczną dysku od sektora 0,
+        // więc zapis do bufora nadpisałby dane. Dopuszczalne są tylko węzły `sg`.
+        let mut st: libc::stat = unsafe { mem::zeroed() };
+        if unsafe { libc::fstat(ata.fd, &mut st) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if st.st_mode & libc::S_IFMT != libc::S_IFCHR
+            || libc::major(st.st_rdev) != SCSI_GENERIC_MAJOR
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not an sg device", dev.display()),
+            ));
         }
 
-        Ok(())
+        let mut size = len.min(MAX_TRANSFER_BYTES) as c_int;
+        if unsafe { ioctl(ata.fd, SG_SET_RESERVED_SIZE, &size) } < 0
+            || unsafe { ioctl(ata.fd, SG_GET_RESERVED_SIZE, &mut size) } < 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+
+        let len = size as usize - size as usize % SECTOR_BYTES;
+        if len == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::OutOfMemory,
+                "no reserved buffer granted",
+            ));
+        }
+
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                ata.fd,
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        ata.map = Some(SgMmap {
+            ptr: ptr as *mut u8,
+            len,
+        });
+        Ok(ata)
     }
 
-    fn raw_write(&mut self, sector: u64, buffer: &[u8]) -> io::Result<()> {
-        #![allow(unused_parens)]
-        let mut cdb = [0u8; 16];
+    /// Odwzorowany bufor rezerwowy
+    pub(super) fn mmap_buffer(&mut self) -> io::Result<&mut [u8]> {
+        match &self.map {
+            Some(map) => Ok(unsafe { std::slice::from_raw_parts_mut(map.ptr, map.len) }),
+            None => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "mmap I/O not enabled",
+            )),
+        }
+    }
+
+    /// Odczyt (`READ DMA EXT`) lub zapis (`WRITE DMA EXT`) `len` bajtów przez
+    /// odwzorowany bufor rezerwowy, bez kopiowania danych przez jądro
+    pub(super) fn mmap_transfer(
+        &mut self,
+        sector: u64,
+        len: usize,
+        write: bool,
+    ) -> io::Result<AtaTaskfileOut> {
+        if len > self.mmap_buffer()?.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("transfer of {} bytes exceeds mapped buffer", len),
+            ));
+        }
+
+        let tf = TaskFile {
+            command: if write { 0x35 } else { 0x25 }, // WRITE/READ DMA EXT
+            count: (len / SECTOR_BYTES) as u16,
+            lba: sector,
+            device: 0b1110_0000, // LBA, DRV0
+            ext: true,
+            ..Default::default()
+        };
+
+        // Przy SG_FLAG_MMAP_IO wskaźnik danych jest ignorowany
+        let data = SgData {
+            protocol: SG_ATA_PROTO_DMA,
+            tdir: if write {
+                SG_CDB2_TDIR_TO_DEV
+            } else {
+                SG_CDB2_TDIR_FROM_DEV
+            },
+            direction: if write {
+                SG_DXFER_TO_DEV
+            } else {
+                SG_DXFER_FROM_DEV
+            },
+            ptr: ptr::null_mut(),
+            len,
+            iovec_count: 0,
+            flags: SG_FLAG_MMAP_IO,
+        };
+
+        self.sg_command(&tf, data)
+    }
+
+    /// Wykonanie dowolnej komendy SCSI przez `SG_IO`, bez ponowień. Zwracany
+    /// jest bufor sense (wyzerowany, jeśli komenda zakończyła się bez niego),
+    /// błędem są tylko niepowodzenia wywołania systemowego i kontrolera.
+    pub(super) fn scsi_command(
+        &mut self,
+        cdb: &[u8],
+        direction: Direction,
+        buffer: &mut [u8],
+        timeout_ms: u32,
+    ) -> io::Result<[u8; 32]> {
+        let mut cdb = cdb.to_vec();
         let mut sb = [0u8; 32];
 
-        // Wielokrotność sektora
-        assert_eq!(buffer.len() % SECTOR_BYTES, 0);
-
-        // Nie więcej niż maksymalny transfer
-        assert!(buffer.len() <= MAX_TRANSFER_BYTES);
-
-        let count = (buffer.len() / SECTOR_BYTES) as u32;
-
-        // Nawet nie PYTAJCIE o kolejność bajtów w polu zawierającym numer
-        // sektora (-_-,)
-
-        cdb[0] = SG_ATA_16;
-        cdb[1] = SG_ATA_LBA48 | SG_ATA_PROTO_DMA;
-        cdb[2] = SG_CDB2_TLEN_NSECT | SG_CDB2_TLEN_SECTORS | SG_CDB2_TDIR_TO_DEV;
-        cdb[3] = 0; // FEAT_H
-        cdb[4] = 0; // FEAT_L
-        cdb[5] = (count >> 8) as u8; // NSect_H     = nsect08..16
-        cdb[6] = (count >> 0) as u8; // NSect_L     = nsect00..07
-        cdb[7] = (sector >> 24) as u8; // hob.lbal  = sector24..31
-        cdb[8] = (sector >> 0) as u8; // lob.lbal   = sector00..07
-        cdb[9] = (sector >> 32) as u8; // hob.lbam  = sector32..39
-        cdb[10] = (sector >> 8) as u8; // lob.lbam  = sector08..15
-        cdb[11] = (sector >> 40) as u8; // hob.lbah = sector40..47
-        cdb[12] = (sector >> 16) as u8; // lob.lbah = sector16..23
-        cdb[13] = 0b1110_0000; // LBA, DRV0
-        cdb[14] = 0x35; // WRITE DMA EXT/READ SECT EXT
+        let dxfer_direction = match direction {
+            Direction::None => SG_DXFER_NONE,
+            Direction::In => SG_DXFER_FROM_DEV,
+            Direction::Out => SG_DXFER_TO_DEV,
+        };
+        let len = match direction {
+            Direction::None => 0,
+            _ => buffer.len(),
+        };
 
         let task = SgTaskHdr {
             interface_id: 'S' as u32,
-            dxfer_direction: SG_DXFER_TO_DEV,
-            cmd_len: SG_ATA_16_LEN,
+            dxfer_direction,
+            cmd_len: cdb.len() as u8,
             mx_sb_len: sb.len() as u8,
 
             iovec_count: 0,
-            dxfer_len: 512 * count,
-            dxferp: buffer.as_ptr(),
-            cmdp: &mut cdb[0] as *mut u8,
-            sbp: &mut sb[0] as *mut u8,
-            timeout: 1000, // ms
-            flags: SG_FLAG_DIRECT_IO,
-            pack_id: sector as u32,
+            dxfer_len: len as u32,
+            dxferp: buffer.as_mut_ptr(),
+            cmdp: cdb.as_mut_ptr(),
+            sbp: sb.as_mut_ptr(),
+            timeout: timeout_ms,
+            flags: 0,
+            pack_id: 0,
             usr_ptr: ptr::null_mut(),
             status: 0,
             masked_status: 0,
@@ -231,73 +566,640 @@ impl RawAta for ATA {
             info: 0,
         };
 
-        let ans = unsafe { ioctl(self.0, SG_IO, &task) };
+        let ans = unsafe { ioctl(self.fd, SG_IO, &task) };
 
         if ans < 0 {
             return Err(io::Error::last_os_error());
         }
 
-        if sb[0] != 0 {
-            return Err(sg_error_to_io(sb[1]));
+        // Błąd transportu (host) lub sterownika inny niż dostępne dane sense
+        if task.host_status != 0 || task.driver_status & !SG_DRIVER_SENSE != 0 {
+            return Err(io::Error::other(format!(
+                "SCSI command failed, host status {:#06x}, driver status {:#06x}",
+                task.host_status, task.driver_status
+            )));
         }
 
-        Ok(())
+        Ok(sb)
     }
 
-    fn raw_info(&mut self, ident: *mut super::IdentifyDeviceData) -> io::Result<()> {
-        let t = Task {
-            command: 0xEC,
-            sector: 0x00,
-            feature: 0x00,
-            nsector: 0x01,
-            buffer: [0; 512],
-        };
-        let ans = unsafe { ioctl(self.0, HDIO_DRIVE_CMD, &t) };
+    /// Wykonanie komendy ATA PASS-THROUGH (16) przez `SG_IO`, ponawianej do
+    /// `retries` razy. SG nie ma odpowiednika `retry_count` z CAM, więc
+    /// ponawiane są tu komendy odrzucone przez napęd, poza niepoprawnymi
+    /// (ILLEGAL_REQUEST) i chronionymi (DATA_PROTECT). Błędy samego wywołania
+    /// systemowego nie są ponawiane.
+    fn sg_command(&mut self, tf: &TaskFile, data: SgData) -> io::Result<AtaTaskfileOut> {
+        let mut attempt = 0;
+        loop {
+            match self.sg_command_once(tf, &data) {
+                Err(err) if attempt < self.retries && sg_retryable(&err) => attempt += 1,
+                ans => return ans,
+            }
+        }
+    }
+
+    /// Pojedyncze wykonanie komendy ATA PASS-THROUGH (16) przez `SG_IO`
+    fn sg_command_once(&mut self, tf: &TaskFile, data: &SgData) -> io::Result<AtaTaskfileOut> {
+        let mut cdb = [0u8; 16];
+        let mut sb = vec![0u8; self.sense_len as usize];
+        let task = sg_header(tf, data, &mut cdb, &mut sb);
+
+        let ans = unsafe { ioctl(self.fd, SG_IO, &task) };
 
         if ans < 0 {
             return Err(io::Error::last_os_error());
         }
 
-        unsafe {
-            std::ptr::copy(
-                t.buffer.as_ptr() as *const super::IdentifyDeviceData,
-                ident,
-                1,
-            );
+        sg_sense_result(&sb)
+    }
+
+    /// Rozmiar bufora sense komend ATA PASS-THROUGH, nie mniej niż domyślne
+    /// 32 bajty potrzebne do odczytu rejestrów wyjściowych
+    pub(super) fn set_sense_len(&mut self, len: u8) {
+        self.sense_len = len.max(SENSE_DEFAULT_LEN);
+    }
+
+    /// Przejęcie deskryptora otwartego przez wywołującego
+    pub(super) fn from_fd(fd: c_int) -> Self {
+        ATA {
+            fd,
+            map: None,
+            retries: 0,
+            sense_len: SENSE_DEFAULT_LEN,
+        }
+    }
+
+    /// Deskryptor urządzenia
+    pub(super) fn as_raw_fd(&self) -> c_int {
+        self.fd
+    }
+
+    /// Oddanie deskryptora wywołującemu, uchwyt nie zamknie go przy zwolnieniu
+    pub(super) fn release_fd(&mut self) -> c_int {
+        self.map = None;
+        mem::replace(&mut self.fd, -1)
+    }
+
+    /// Reset urządzenia przez `SG_SCSI_RESET`. Dla dysków podłączonych przez
+    /// libata jądro wykonuje reset łącza i ponownie konfiguruje napęd.
+    pub(super) fn reset(&mut self) -> io::Result<()> {
+        let kind = SG_SCSI_RESET_DEVICE;
+        if unsafe { ioctl(self.fd, SG_SCSI_RESET, &kind) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Zrzucenie buforów jądra dla urządzenia otwartego bez O_DIRECT
+    pub(super) fn sync(&self) -> io::Result<()> {
+        if unsafe { libc::fsync(self.fd) } < 0 {
+            return Err(io::Error::last_os_error());
         }
         Ok(())
     }
+
+    /// Magistrala, przez którą podłączony jest dysk, rozpoznana po przodkach
+    /// urządzenia w `/sys/devices`. Dyski podłączone przez libata (PATA i SATA)
+    /// nie są rozróżniane, wtedy zwracane jest `None`.
+    pub(super) fn transport(&self, path: &Path) -> Option<Transport> {
+        let dev = fs::canonicalize(path).ok()?;
+        let name = dev.file_name()?.to_str()?;
+
+        let class = if name.starts_with("sg") {
+            Path::new("/sys/class/scsi_generic")
+        } else {
+            Path::new("/sys/block")
+        };
+        let device = fs::canonicalize(class.join(name).join("device")).ok()?;
+
+        device
+            .components()
+            .filter_map(|c| c.as_os_str().to_str())
+            .any(|c| c.starts_with("usb"))
+            .then_some(Transport::Usb)
+    }
+}
+
+/// Wypełnienie CDB komendy ATA PASS-THROUGH (16) i zbudowanie nagłówka `sg`
+/// opisującego ją razem z buforami danych i sense
+fn sg_header(
+    tf: &TaskFile,
+    data: &SgData,
+    cdb: &mut [u8; 16],
+    sb: &mut [u8],
+) -> SgTaskHdr<*mut u8> {
+    #![allow(unused_parens)]
+    let SgData {
+        protocol,
+        tdir,
+        direction: dxfer_direction,
+        ptr: dxferp,
+        len: dxfer_len,
+        iovec_count,
+        flags,
+    } = *data;
+
+    // Wielokrotność sektora
+    assert_eq!(dxfer_len % SECTOR_BYTES, 0);
+
+    // Nie więcej niż maksymalny transfer
+    assert!(dxfer_len <= MAX_TRANSFER_BYTES);
+
+    let tlen = if dxfer_len == 0 {
+        SG_CDB2_TLEN_NODATA
+    } else {
+        SG_CDB2_TLEN_NSECT | SG_CDB2_TLEN_SECTORS
+    };
+
+    // Komendy 28-bitowe przenoszą bity 24..27 LBA w rejestrze urządzenia
+    let (ext, device) = if tf.ext {
+        (SG_ATA_LBA48, tf.device)
+    } else {
+        (0, tf.device | ((tf.lba >> 24) & 0x0F) as u8)
+    };
+
+    // Rozmiar bloku komend READ/WRITE MULTIPLE podawany jest jako log2
+    let multiple = match tf.multiple {
+        0 => 0,
+        n => (n.trailing_zeros() as u8) << 5,
+    };
+
+    cdb[0] = SG_ATA_16;
+    cdb[1] = multiple | ext | protocol;
+    cdb[2] = tlen | tdir | SG_CDB2_CHECK_COND;
+    cdb[3] = (tf.features >> 8) as u8; // FEAT_H
+    cdb[4] = (tf.features >> 0) as u8; // FEAT_L
+    cdb[5] = (tf.count >> 8) as u8; // NSect_H
+    cdb[6] = (tf.count >> 0) as u8; // NSect_L
+    cdb[7] = (tf.lba >> 24) as u8; // hob.lbal
+    cdb[8] = (tf.lba >> 0) as u8; // lob.lbal
+    cdb[9] = (tf.lba >> 32) as u8; // hob.lbam
+    cdb[10] = (tf.lba >> 8) as u8; // lob.lbam
+    cdb[11] = (tf.lba >> 40) as u8; // hob.lbah
+    cdb[12] = (tf.lba >> 16) as u8; // lob.lbah
+    cdb[13] = device;
+    cdb[14] = tf.command;
+
+    SgTaskHdr {
+        interface_id: 'S' as u32,
+        dxfer_direction,
+        cmd_len: SG_ATA_16_LEN,
+        mx_sb_len: sb.len() as u8,
+
+        iovec_count,
+        dxfer_len: dxfer_len as u32,
+        dxferp,
+        cmdp: cdb.as_mut_ptr(),
+        sbp: sb.as_mut_ptr(),
+        timeout: tf.timeout.map_or(1000, |t| t.as_millis() as u32), // ms
+        flags,
+        pack_id: tf.lba as u32,
+        usr_ptr: ptr::null_mut(),
+        status: 0,
+        masked_status: 0,
+        msg_status: 0,
+        sb_len_wr: 0,
+        host_status: 0,
+        driver_status: 0,
+        resid: 0,
+        duration: 0,
+        info: 0,
+    }
+}
+
+/// Rejestry wyjściowe lub błąd komendy odczytane z danych sense
+fn sg_sense_result(sb: &[u8]) -> io::Result<AtaTaskfileOut> {
+    // Przy ustawionym CK_COND napęd zawsze zwraca rejestry w danych sense,
+    // z kluczem RECOVERED_ERROR i ASC/ASCQ 0x00/0x1D
+    let key = match sb[0] {
+        // Translator zignorował CK_COND, rejestry niedostępne
+        0 => return Ok(AtaTaskfileOut::default()),
+        SENSE_DESCRIPTOR => sb[1] & 0x0F,
+        SENSE_FIXED => sb[2] & 0x0F,
+        _ => return Err(sg_sense_error(sb)),
+    };
+    let (asc, ascq) = match sb[0] {
+        SENSE_DESCRIPTOR => (sb[2], sb[3]),
+        _ => (sb[12], sb[13]),
+    };
+
+    let out = sg_sense_to_taskfile(sb);
+
+    match out {
+        Some(out) if out.status & ATA_STATUS_ERR == 0 => {
+            if key == SENSE_KEY_NO_SENSE
+                || (key == SENSE_KEY_RECOVERED_ERROR
+                    && asc == 0
+                    && ascq == SENSE_ASCQ_ATA_INFO_AVAILABLE)
+            {
+                Ok(out)
+            } else {
+                Err(sg_sense_error(sb))
+            }
+        }
+        // Błąd zgłoszony przez napęd (UNC, ABRT) przychodzi jako MEDIUM_ERROR
+        // lub ABORTED_COMMAND, pełne dane sense trafiają do błędu
+        Some(out) => {
+            log::debug!(
+                "ATA command failed, status {:#04x}, error {:#04x}",
+                out.status,
+                out.error
+            );
+            Err(sg_sense_error(sb))
+        }
+        None => Err(sg_sense_error(sb)),
+    }
+}
+
+/// Komenda `sg` wysyłana asynchronicznie przez zapis nagłówka do węzła `sg`
+/// i odbierana przez jego odczyt. Nagłówek wskazuje na CDB, bufor sense i dane
+/// trzymane w tej samej strukturze, więc musi ona pozostać na stercie aż do
+/// zakończenia komendy.
+pub(super) struct SgRequest {
+    tf: TaskFile,
+    hdr: SgTaskHdr<*mut u8>,
+    cdb: [u8; 16],
+    sb: [u8; 32],
+    data: Vec<u8>,
+}
+
+// Wskaźniki nagłówka prowadzą wyłącznie do pól tej samej struktury
+unsafe impl Send for SgRequest {}
+
+impl SgRequest {
+    /// Odczyt (`READ DMA EXT`) lub zapis (`WRITE DMA EXT`) całego bufora `data`
+    /// od sektora `sector`, oznaczony identyfikatorem `pack_id`
+    pub(super) fn transfer(sector: u64, data: Vec<u8>, write: bool, pack_id: u32) -> Box<Self> {
+        let tf = TaskFile {
+            command: if write { 0x35 } else { 0x25 }, // WRITE/READ DMA EXT
+            count: (data.len() / SECTOR_BYTES) as u16,
+            lba: sector,
+            device: 0b1110_0000, // LBA, DRV0
+            ext: true,
+            ..Default::default()
+        };
+
+        let mut req = Box::new(SgRequest {
+            tf,
+            hdr: unsafe { mem::zeroed() },
+            cdb: [0; 16],
+            sb: [0; 32],
+            data,
+        });
+
+        let data = SgData {
+            protocol: SG_ATA_PROTO_DMA,
+            tdir: if write {
+                SG_CDB2_TDIR_TO_DEV
+            } else {
+                SG_CDB2_TDIR_FROM_DEV
+            },
+            direction: if write {
+                SG_DXFER_TO_DEV
+            } else {
+                SG_DXFER_FROM_DEV
+            },
+            ptr: req.data.as_mut_ptr(),
+            len: req.data.len(),
+            iovec_count: 0,
+            flags: 0,
+        };
+
+        let req_mut = &mut *req;
+        req_mut.hdr = sg_header(&tf, &data, &mut req_mut.cdb, &mut req_mut.sb);
+        req_mut.hdr.pack_id = pack_id;
+        req
+    }
+
+    /// Adres i rozmiar nagłówka, zapisywanego do węzła `sg` przy wysłaniu
+    /// komendy i nadpisywanego przy jej odebraniu
+    pub(super) fn header(&mut self) -> (*mut u8, usize) {
+        (
+            &mut self.hdr as *mut SgTaskHdr<*mut u8> as *mut u8,
+            mem::size_of::<SgTaskHdr<*mut u8>>(),
+        )
+    }
+
+    /// Wynik odebranej komendy
+    pub(super) fn result(&self) -> io::Result<AtaTaskfileOut> {
+        let name = match self.tf.command {
+            0x35 => "WRITE DMA EXT",
+            _ => "READ DMA EXT",
+        };
+        sg_sense_result(&self.sb).map_err(|err| CommandError::wrap(err, name, &self.tf))
+    }
+
+    /// Bufor danych zakończonej komendy
+    pub(super) fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// Otwarcie węzła `sg` dla komend asynchronicznych, bez blokowania przy
+/// odczycie niezakończonych. Zapis nagłówka do urządzenia blokowego trafiłby
+/// na dysk, więc inne urządzenia są odrzucane. Odczyt zwraca wyłącznie komendę
+/// o identyfikatorze podanym w nagłówku.
+pub(super) fn open_sg_async(dev: &Path) -> io::Result<c_int> {
+    use std::os::unix::ffi::OsStrExt;
+    let device = CString::new(dev.as_os_str().as_bytes())?;
+
+    let fd = unsafe { libc::open(device.as_ptr(), libc::O_RDWR | libc::O_NONBLOCK) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut st: libc::stat = unsafe { mem::zeroed() };
+    let err = if unsafe { libc::fstat(fd, &mut st) } < 0 {
+        Some(io::Error::last_os_error())
+    } else if st.st_mode & libc::S_IFMT != libc::S_IFCHR
+        || libc::major(st.st_rdev) != SCSI_GENERIC_MAJOR
+    {
+        Some(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not an sg device", dev.display()),
+        ))
+    } else if unsafe { ioctl(fd, SG_SET_FORCE_PACK_ID, &1 as *const c_int) } < 0 {
+        Some(io::Error::last_os_error())
+    } else {
+        None
+    };
+
+    match err {
+        Some(err) => {
+            unsafe { libc::close(fd) };
+            Err(err)
+        }
+        None => Ok(fd),
+    }
+}
+
+/// Odczyt rejestrów wyjściowych ATA z danych sense (SAT-4, rozdziały 12.2.2.6 i 12.2.2.7)
+fn sg_sense_to_taskfile(sb: &[u8]) -> Option<AtaTaskfileOut> {
+    match sb[0] {
+        SENSE_DESCRIPTOR => {
+            let len = (8 + sb[7] as usize).min(sb.len());
+            let mut pos = 8;
+
+            while pos + 2 <= len {
+                let desc = &sb[pos..len];
+                if desc[0] == SENSE_DESC_ATA_STATUS_RETURN && desc.len() >= 14 {
+                    return Some(AtaTaskfileOut {
+                        error: desc[3],
+                        count: (desc[4] as u16) << 8 | desc[5] as u16,
+                        lba: (desc[7] as u64) << 0
+                            | (desc[9] as u64) << 8
+                            | (desc[11] as u64) << 16
+                            | (desc[6] as u64) << 24
+                            | (desc[8] as u64) << 32
+                            | (desc[10] as u64) << 40,
+                        device: desc[12],
+                        status: desc[13],
+                    });
+                }
+                pos += 2 + desc[1] as usize;
+            }
+            None
+        }
+        // Format stały zawiera tylko młodsze połówki rejestrów
+        SENSE_FIXED => Some(AtaTaskfileOut {
+            error: sb[3],
+            status: sb[4],
+            device: sb[5],
+            count: sb[6] as u16,
+            lba: sb[9] as u64 | (sb[10] as u64) << 8 | (sb[11] as u64) << 16,
+        }),
+        _ => None,
+    }
+}
+
+/// Maksymalny rozmiar pojedynczego transferu akceptowany przez jądro, odczytany
+/// z `/sys/block/<dev>/queue/max_sectors_kb`
+pub(super) fn max_transfer_bytes(path: &Path) -> Option<usize> {
+    let dev = fs::canonicalize(path).ok()?;
+    let name = dev.file_name()?.to_str()?;
+
+    let queue = Path::new("/sys/block").join(name).join("queue");
+    let kb = fs::read_to_string(queue.join("max_sectors_kb")).ok()?;
+    let bytes = kb.trim().parse::<usize>().ok()? * 1024;
+
+    Some(bytes.min(MAX_TRANSFER_BYTES))
+}
+
+/// Parametry discard wymuszane przez jądro, odczytane z
+/// `/sys/block/<dev>/queue/discard_granularity` i `discard_max_bytes`.
+/// Zerowy `discard_max_bytes` oznacza brak obsługi discard.
+pub(super) fn discard_limits(path: &Path) -> Option<(u64, u64)> {
+    let dev = fs::canonicalize(path).ok()?;
+    let name = dev.file_name()?.to_str()?;
+
+    let queue = Path::new("/sys/block").join(name).join("queue");
+    let read = |file: &str| -> Option<u64> {
+        fs::read_to_string(queue.join(file))
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+    };
+
+    match (read("discard_granularity")?, read("discard_max_bytes")?) {
+        (_, 0) => None,
+        limits => Some(limits),
+    }
+}
+
+/// Ścieżki całych dysków fizycznych wymienionych w `/sys/block`. Partycje
+/// są tam tylko podkatalogami dysków, a urządzenia wirtualne (loop, ram,
+/// zram, dm, md) nie mają dowiązania `device`, więc są pomijane.
+pub(super) fn disk_paths() -> io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir("/sys/block")? {
+        let entry = entry?;
+        if !entry.path().join("device").exists() {
+            continue;
+        }
+        if let Ok(name) = entry.file_name().into_string() {
+            paths.push(Path::new("/dev").join(name));
+        }
+    }
+
+    paths.sort();
+    Ok(paths)
+}
+
+/// Czy dysk albo któraś z jego partycji jest zamontowana, używana jako swap
+/// lub należy do innego urządzenia blokowego (md, LVM, dm-crypt)
+pub(super) fn is_in_use(path: &Path) -> io::Result<bool> {
+    let dev = fs::canonicalize(path)?;
+    let name = dev
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid device path"))?;
+
+    // Węzeł `sg` trzeba przetłumaczyć na nazwę dysku blokowego
+    let disk = if name.starts_with("sg") {
+        let block = Path::new("/sys/class/scsi_generic")
+            .join(name)
+            .join("device/block");
+        match fs::read_dir(block)?.next() {
+            Some(entry) => entry?.file_name().into_string().unwrap_or_default(),
+            None => return Ok(false),
+        }
+    } else {
+        name.to_owned()
+    };
+
+    // Sam dysk i wszystkie jego partycje (podkatalogi `sdX1`, `sdX2`...)
+    let sys = Path::new("/sys/block").join(&disk);
+    let mut names = vec![disk.clone()];
+    names.extend(
+        fs::read_dir(&sys)?
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|entry| entry.starts_with(&disk) && entry != &disk),
+    );
+
+    for part in &names {
+        let holders = if part == &disk {
+            sys.join("holders")
+        } else {
+            sys.join(part).join("holders")
+        };
+        if fs::read_dir(holders).is_ok_and(|mut dir| dir.next().is_some()) {
+            return Ok(true);
+        }
+    }
+
+    // Pierwsza kolumna `/proc/mounts` i `/proc/swaps` to urządzenie źródłowe
+    let devices: Vec<PathBuf> = names
+        .iter()
+        .map(|part| Path::new("/dev").join(part))
+        .collect();
+    for table in &["/proc/mounts", "/proc/swaps"] {
+        let content = match fs::read_to_string(table) {
+            Ok(content) => content,
+            Err(_) => continue,
+        };
+        let used = content
+            .lines()
+            .filter_map(|line| line.split_whitespace().next())
+            .filter(|source| source.starts_with('/'))
+            .filter_map(|source| fs::canonicalize(source).ok())
+            .any(|source| devices.contains(&source));
+        if used {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Błąd komendy odrzuconej przez napęd lub translator SAT, niosący pełne
+/// dane sense
+fn sg_sense_error(sb: &[u8]) -> io::Error {
+    match SenseData::parse(sb) {
+        Some(sense) => io::Error::other(sense),
+        None => io::Error::other(format!("unknown sense data format {:#04x}", sb[0])),
+    }
+}
+
+/// Czy komendę odrzuconą z tym błędem warto wykonać ponownie w ramach
+/// ponowień kontrolera. Ponawiane są też błędy nośnika, poza komendami
+/// niepoprawnymi i odrzuconymi przez ochronę danych.
+fn sg_retryable(err: &io::Error) -> bool {
+    match err.get_ref().and_then(|e| e.downcast_ref::<SenseData>()) {
+        Some(sense) => !matches!(
+            sense.key,
+            SENSE_KEY_ILLEGAL_REQUEST | SENSE_KEY_DATA_PROTECT
+        ),
+        None => err.raw_os_error().is_none(),
+    }
+}
+
+/// Czy błąd zgłosił napęd dla samej komendy (jak nieczytelny sektor), a nie
+/// system operacyjny lub biblioteka. Napęd zgłasza go zawsze danymi sense.
+pub(super) fn is_command_error(err: &io::Error) -> bool {
+    SenseData::from_error(err).is_some()
 }
 
-fn sg_error_to_io(err: u8) -> io::Error {
-    assert!(err <= 15);
-    io::Error::new(
-        io::ErrorKind::Other,
-        match err {
-            0 => "NO_SENSE",
-            1 => "RECOVERED_ERROR",
-            2 => "NOT_READY",
-            3 => "MEDIUM_ERROR",
-            4 => "HARDWARE_ERROR",
-            5 => "ILLEGAL_REQUEST",
-            6 => "UNIT_ATTENTION",
-            7 => "DATA_PROTECT",
-            8 => "BLANK_CHECK",
-            9 => "VENDOR_SPECIFIC",
-            10 => "COPY_ABORTED",
-            11 => "ABORTED_COMMAND",
-            12 => "OTHER",
-            13 => "VOLUME_OVERFLOW",
-            14 => "MISCOMPARE",
-            15 => "COMPLETE",
-            _ => unimplemented!("Shouldn't be here"),
-        },
-    )
+/// Czy błąd jest przejściowy i warto ponowić komendę (NOT_READY, UNIT_ATTENTION,
+/// ABORTED_COMMAND, przerwane wywołanie systemowe)
+pub(super) fn is_transient(err: &io::Error) -> bool {
+    let err = CommandError::peel(err);
+    match err.get_ref().and_then(|e| e.downcast_ref::<SenseData>()) {
+        Some(sense) => matches!(
+            sense.key,
+            SENSE_KEY_NOT_READY | SENSE_KEY_UNIT_ATTENTION | SENSE_KEY_ABORTED_COMMAND
+        ),
+        None => matches!(
+            err.kind(),
+            io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock
+        ),
+    }
 }
 
 impl Drop for ATA {
     /// Zamknięcie uchwytu do napędu
     fn drop(&mut self) {
-        self.close();
+        let _ = self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SENSE_KEY_MEDIUM_ERROR: u8 = 0x03;
+
+    #[test]
+    fn check_sg_header_layout() {
+        // sizeof(struct sg_io_hdr) na architekturach 64-bitowych
+        #[cfg(target_pointer_width = "64")]
+        assert_eq!(mem::size_of::<SgTaskHdr<*mut u8>>(), 88);
+    }
+
+    #[test]
+    fn check_error_classification() {
+        // Deskryptor ATA Status Return ze statusem ERR, jak po UNC lub ABRT
+        let failed = |key: u8| {
+            let mut sb = [0u8; 32];
+            sb[..4].copy_from_slice(&[SENSE_DESCRIPTOR, key, 0x00, 0x00]);
+            sb[7] = 14;
+            sb[8] = SENSE_DESC_ATA_STATUS_RETURN;
+            sb[9] = 12;
+            sb[11] = 0x40;
+            sb[21] = ATA_STATUS_ERR | 0x40;
+            sg_sense_result(&sb).unwrap_err()
+        };
+
+        let medium = failed(SENSE_KEY_MEDIUM_ERROR);
+        let sense = SenseData::from_error(&medium).unwrap();
+        assert_eq!(sense.key, SENSE_KEY_MEDIUM_ERROR);
+        assert_eq!(sense.descriptors()[0].0, SENSE_DESC_ATA_STATUS_RETURN);
+        assert!(sg_retryable(&medium));
+        assert!(!is_transient(&medium));
+        assert!(is_command_error(&medium));
+        assert!(!is_command_error(&io::Error::from_raw_os_error(
+            libc::ENODEV
+        )));
+
+        let protected = failed(SENSE_KEY_DATA_PROTECT);
+        assert!(!sg_retryable(&protected));
+        assert!(!is_transient(&protected));
+
+        let aborted = failed(SENSE_KEY_ABORTED_COMMAND);
+        assert!(sg_retryable(&aborted));
+        assert!(is_transient(&aborted));
+    }
+
+    #[test]
+    fn check_fixed_sense_registers() {
+        let mut sb = [0u8; 32];
+        sb[0] = SENSE_FIXED;
+        sb[3..7].copy_from_slice(&[0x04, 0x51, 0x40, 0x0A]);
+        sb[9..12].copy_from_slice(&[0x01, 0x02, 0x03]);
+
+        let out = sg_sense_to_taskfile(&sb).unwrap();
+        assert_eq!((out.error, out.status, out.device), (0x04, 0x51, 0x40));
+        assert_eq!(out.count, 0x0A);
+        assert_eq!(out.lba, 0x03_0201);
     }
 }
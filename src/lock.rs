@@ -0,0 +1,93 @@
+//! Advisory locking of physical drives, keyed on the drive identity rather than the device path.
+
+use std::{fs::OpenOptions, io, os::unix::io::AsRawFd, path::PathBuf};
+
+use crate::{os, Device, IdentifyDeviceData};
+
+impl Device {
+    /// Take an advisory lock of the physical drive, held until [`Device::unlock_drive`] or until
+    /// the device is dropped.
+    ///
+    /// Lock is an exclusive `flock` of a lock file in `/run/lock` (Linux) or `/var/run` (FreeBSD)
+    /// named after the WWN of the drive or, if not reported, its model and serial number. The
+    /// same drive is therefore locked even if reached through different paths, like multipath or
+    /// `sg` nodes. It only guards against other processes using this lock, like other instances
+    /// of a destructive tool. A drive locked elsewhere is reported with
+    /// [`io::ErrorKind::ResourceBusy`].
+    pub fn lock_drive(&mut self) -> io::Result<()> {
+        if self.lock.is_some() {
+            return Ok(());
+        }
+
+        let path = lock_path(&self.info()?);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } < 0 {
+            let err = io::Error::last_os_error();
+            return Err(match err.kind() {
+                io::ErrorKind::WouldBlock => io::Error::new(
+                    io::ErrorKind::ResourceBusy,
+                    format!(
+                        "{}: drive locked by another process ({})",
+                        self.path.display(),
+                        path.display()
+                    ),
+                ),
+                _ => err,
+            });
+        }
+
+        self.lock = Some(file);
+        Ok(())
+    }
+
+    /// Release the lock taken with [`Device::lock_drive`]. Lock file itself is left in place, as
+    /// removing it would race with other processes opening it.
+    pub fn unlock_drive(&mut self) {
+        // Closing the file releases the lock
+        self.lock = None;
+    }
+
+    /// Check if this device holds the lock of [`Device::lock_drive`]
+    pub fn is_drive_locked(&self) -> bool {
+        self.lock.is_some()
+    }
+}
+
+/// Path of the lock file of the drive identified by `id`
+fn lock_path(id: &IdentifyDeviceData) -> PathBuf {
+    let name = match id.get_wwn() {
+        Some(wwn) => format!("{:016x}", wwn),
+        None => format!("{}-{}", id.get_model(), id.get_serial()),
+    };
+    PathBuf::from(os::LOCK_DIR).join(format!("pakr-rawata-{}.lock", sanitize(&name)))
+}
+
+/// Replace characters not safe in file names with `_`
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '.' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_lock_names() {
+        assert_eq!(
+            sanitize("WDC WD40EFRX/68N-WD-WCC7K1"),
+            "WDC_WD40EFRX_68N-WD-WCC7K1"
+        );
+        assert_eq!(sanitize("../x"), ".._x");
+    }
+}
@@ -0,0 +1,141 @@
+//! Writing identical data to a pair of drives in one pass, like pre-seeding a RAID1 mirror.
+
+use std::{error::Error, fmt, io};
+
+use crate::{os, Device, Lba};
+
+/// Error of a [`MirrorDevice`] operation, telling which of the drives failed.
+///
+/// It is carried inside [`io::Error`] of the same kind as the first failure.
+#[derive(Debug)]
+pub struct MirrorError {
+    /// Error of the primary drive, `None` if it succeeded
+    pub primary: Option<io::Error>,
+    /// Error of the secondary drive, `None` if it succeeded
+    pub secondary: Option<io::Error>,
+}
+
+impl fmt::Display for MirrorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.primary, &self.secondary) {
+            (Some(primary), Some(secondary)) => write!(
+                f,
+                "both drives failed: primary: {}, secondary: {}",
+                primary, secondary
+            ),
+            (Some(primary), None) => write!(f, "primary drive failed: {}", primary),
+            (None, Some(secondary)) => write!(f, "secondary drive failed: {}", secondary),
+            (None, None) => f.write_str("no drive failed"),
+        }
+    }
+}
+
+impl Error for MirrorError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match (&self.primary, &self.secondary) {
+            (Some(err), _) | (None, Some(err)) => Some(err),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Pair of [`Device`]s receiving every modification at the same LBA.
+///
+/// Each write, TRIM and flush is issued to both drives, even if the first one fails, so they stay
+/// in lockstep. Ranges are validated against both drives first, so that neither gets a write the
+/// other would reject. Failures are reported with [`MirrorError`]. Reads are served by the primary
+/// drive, falling back to the secondary one.
+pub struct MirrorDevice {
+    primary: Device,
+    secondary: Device,
+}
+
+impl MirrorDevice {
+    /// Pair `primary` and `secondary` drives
+    pub fn new(primary: Device, secondary: Device) -> Self {
+        MirrorDevice { primary, secondary }
+    }
+
+    /// Read sector(s) from the primary drive, or from the secondary one if it fails.
+    ///
+    /// Buffer size **must** be multiple of sector size.
+    pub fn read(&mut self, sector: impl Into<Lba>, buffer: &mut [u8]) -> io::Result<()> {
+        let sector = sector.into().sector();
+        match self.primary.read(sector, buffer) {
+            Ok(()) => Ok(()),
+            Err(primary) => self.secondary.read(sector, buffer).map_err(|secondary| {
+                mirror_error(Some(primary), Some(secondary)).expect("both failed")
+            }),
+        }
+    }
+
+    /// Write sector(s) to both drives with [`Device::write`].
+    ///
+    /// Buffer size **must** be multiple of sector size.
+    pub fn write(&mut self, sector: impl Into<Lba>, buffer: &[u8]) -> io::Result<()> {
+        let sector = sector.into().sector();
+        self.validate_range(sector, (buffer.len() / os::SECTOR_BYTES) as u64)?;
+        self.both(|device| device.write(sector, buffer))
+    }
+
+    /// Trim sectors on both drives with [`Device::trim`]
+    pub fn trim(&mut self, start: impl Into<Lba>, count: u64) -> io::Result<()> {
+        let start = start.into().sector();
+        self.validate_range(start, count)?;
+        self.both(|device| device.trim(start, count))
+    }
+
+    /// Flush write cache of both drives with [`Device::flush`]
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.both(Device::flush)
+    }
+
+    /// Return the wrapped drives, primary first
+    pub fn into_inner(self) -> (Device, Device) {
+        (self.primary, self.secondary)
+    }
+
+    /// Check a range against capacity of both drives
+    fn validate_range(&self, start: u64, count: u64) -> io::Result<()> {
+        let primary = self.primary.validate_range(start, count).err();
+        let secondary = self.secondary.validate_range(start, count).err();
+        mirror_error(primary, secondary).map_or(Ok(()), Err)
+    }
+
+    /// Run `f` on both drives, the secondary one regardless of the primary result
+    fn both<F>(&mut self, mut f: F) -> io::Result<()>
+    where
+        F: FnMut(&mut Device) -> io::Result<()>,
+    {
+        let primary = f(&mut self.primary).err();
+        let secondary = f(&mut self.secondary).err();
+        mirror_error(primary, secondary).map_or(Ok(()), Err)
+    }
+}
+
+/// Combine errors of both drives, `None` if neither failed
+fn mirror_error(primary: Option<io::Error>, secondary: Option<io::Error>) -> Option<io::Error> {
+    let kind = primary.as_ref().or(secondary.as_ref())?.kind();
+    Some(io::Error::new(kind, MirrorError { primary, secondary }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_mirror_error() {
+        assert!(mirror_error(None, None).is_none());
+
+        let err = mirror_error(None, Some(io::Error::from(io::ErrorKind::TimedOut))).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        let mirror = err
+            .get_ref()
+            .unwrap()
+            .downcast_ref::<MirrorError>()
+            .unwrap();
+        assert!(mirror.primary.is_none() && mirror.secondary.is_some());
+        assert!(err.to_string().starts_with("secondary drive failed"));
+    }
+}
@@ -0,0 +1,76 @@
+//! Memory-mapped transfers through the reserved buffer of the Linux `sg` driver
+//! (`SG_FLAG_MMAP_IO`), avoiding the copy between user and kernel space.
+
+use std::io;
+
+use crate::{os, Device};
+
+impl Device {
+    /// Reopen the device for memory-mapped transfers with a buffer of up to `bytes` bytes.
+    ///
+    /// The device **must** be opened with an `sg` node (like `/dev/sg0`), any other path is
+    /// refused with [`io::ErrorKind::InvalidInput`]. Mapping a block device would map its page
+    /// cache, so writes to the buffer would overwrite the disk from sector 0. It is opened again
+    /// for reading and writing, keeping exclusive and `O_DIRECT` flags of the original open, any
+    /// previous handle is closed. Kernel may grant a smaller buffer, its actual size is
+    /// returned. Transfers through the mapping are done with [`Device::read_mmap`] and
+    /// [`Device::write_mmap`], other commands work as usual.
+    pub fn enable_mmap_io(&mut self, bytes: usize) -> io::Result<usize> {
+        let mut ata = os::ATA::open_mmap(&self.path, bytes, self.exclusive, self.direct)?;
+        let granted = ata.mmap_buffer()?.len();
+        self.configure(&mut ata);
+
+        let _ = self.close();
+        self.ata = ata;
+        Ok(granted)
+    }
+
+    /// Read `count` sectors into the mapped buffer and return a view of them.
+    ///
+    /// Transfer **must** fit in the buffer granted by [`Device::enable_mmap_io`]. The view is
+    /// valid until the next transfer.
+    pub fn read_mmap(&mut self, sector: u64, count: u64) -> io::Result<&[u8]> {
+        let len = self.check_mmap(sector, count)?;
+
+        self.retry(|ata| ata.mmap_transfer(sector, len, false))?;
+        Ok(&self.ata.mmap_buffer()?[..len])
+    }
+
+    /// Write `count` sectors from the mapped buffer, after `fill` has filled it in place.
+    ///
+    /// Transfer **must** fit in the buffer granted by [`Device::enable_mmap_io`]. In dry-run mode
+    /// `fill` is not called.
+    pub fn write_mmap<F>(&mut self, sector: u64, count: u64, fill: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut [u8]),
+    {
+        let len = self.check_mmap(sector, count)?;
+        if self.skip_dry_run(format_args!("mmap write of {} bytes at {}", len, sector))? {
+            return Ok(());
+        }
+
+        fill(&mut self.ata.mmap_buffer()?[..len]);
+        self.retry(|ata| ata.mmap_transfer(sector, len, true))?;
+        Ok(())
+    }
+
+    /// Validate a mapped transfer, returning its length in bytes
+    fn check_mmap(&mut self, sector: u64, count: u64) -> io::Result<usize> {
+        self.validate_range(sector, count)?;
+        if !self.lba48() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "mmap I/O requires 48-bit addressing",
+            ));
+        }
+
+        let len = count as usize * os::SECTOR_BYTES;
+        if len > self.ata.mmap_buffer()?.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("transfer of {} bytes exceeds mapped buffer", len),
+            ));
+        }
+        Ok(len)
+    }
+}
@@ -0,0 +1,102 @@
+//! `READ/WRITE MULTIPLE` transfers, moving a block of sectors per interrupt using PIO. Some
+//! embedded controllers handle them more reliably than DMA transfers.
+
+use std::io;
+
+use crate::{os, Device, RawAta, TaskFile, Transfer, ATA_DEV_LBA};
+
+const ATA_READ_MULTIPLE_EXT: u8 = 0x29;
+const ATA_WRITE_MULTIPLE_EXT: u8 = 0x39;
+const ATA_SET_MULTIPLE_MODE: u8 = 0xC6;
+
+impl Device {
+    /// Set number of sectors transferred per interrupt by `READ/WRITE MULTIPLE` using `SET
+    /// MULTIPLE MODE` (ATA cmd 0xC6, documentation of ATA8-ACS chapter 7.47).
+    ///
+    /// `sectors` **must** be a power of 2 not larger than
+    /// [`IdentifyDeviceData::get_max_sectors_per_interrupt`][max]. Cached identification is
+    /// refreshed afterwards, so that the new setting is used by [`Device::read_multiple`] and
+    /// [`Device::write_multiple`].
+    ///
+    /// [max]: crate::IdentifyDeviceData::get_max_sectors_per_interrupt
+    pub fn set_multiple_mode(&mut self, sectors: u8) -> io::Result<()> {
+        if !sectors.is_power_of_two() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("multiple mode of {} sectors is not a power of 2", sectors),
+            ));
+        }
+
+        let tf = TaskFile {
+            command: ATA_SET_MULTIPLE_MODE,
+            count: sectors as u16,
+            device: ATA_DEV_LBA,
+            ..Default::default()
+        };
+
+        self.retry(|ata| ata.raw_command(&tf, Transfer::NonData))?;
+        self.ident = self.info().ok();
+        Ok(())
+    }
+
+    /// Read sector(s) from disk using `READ MULTIPLE EXT` (ATA cmd 0x29, documentation chapter
+    /// 7.19), with block size set by [`Device::set_multiple_mode`].
+    ///
+    /// Buffer size **must** be multiple of sector size. Range is checked with
+    /// [`Device::validate_range`] first.
+    pub fn read_multiple(&mut self, sector: u64, buffer: &mut [u8]) -> io::Result<()> {
+        let tf = self.multiple_taskfile(ATA_READ_MULTIPLE_EXT, sector, buffer.len())?;
+
+        self.retry(|ata| ata.raw_command(&tf, Transfer::PioIn(buffer)))?;
+        Ok(())
+    }
+
+    /// Write sector(s) to disk using `WRITE MULTIPLE EXT` (ATA cmd 0x39, documentation chapter
+    /// 7.61), with block size set by [`Device::set_multiple_mode`].
+    ///
+    /// Buffer size **must** be multiple of sector size. Range is checked with
+    /// [`Device::validate_range`] first.
+    pub fn write_multiple(&mut self, sector: u64, buffer: &[u8]) -> io::Result<()> {
+        let tf = self.multiple_taskfile(ATA_WRITE_MULTIPLE_EXT, sector, buffer.len())?;
+        if self.skip_dry_run(format_args!(
+            "write_multiple of {} bytes at {}",
+            buffer.len(),
+            sector
+        ))? {
+            return Ok(());
+        }
+
+        self.retry(|ata| ata.raw_command(&tf, Transfer::PioOut(buffer)))?;
+        Ok(())
+    }
+
+    /// Validate a `READ/WRITE MULTIPLE EXT` transfer of `len` bytes and build its command
+    fn multiple_taskfile(&mut self, command: u8, sector: u64, len: usize) -> io::Result<TaskFile> {
+        let count = (len / os::SECTOR_BYTES) as u64;
+        self.validate_range(sector, count)?;
+        if !self.lba48() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "READ/WRITE MULTIPLE EXT requires 48-bit addressing",
+            ));
+        }
+
+        let id = match self.ident {
+            Some(id) => id,
+            None => self.info()?,
+        };
+        let multiple = id
+            .get_multiple_setting()
+            .ok_or_else(|| io::Error::other("multiple mode not set"))?;
+
+        Ok(TaskFile {
+            command,
+            count: count as u16,
+            lba: sector,
+            device: ATA_DEV_LBA,
+            ext: true,
+            multiple,
+            ..Default::default()
+        })
+    }
+}
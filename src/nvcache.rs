@@ -0,0 +1,68 @@
+//! NV Cache feature set (ATA cmd 0xB6, documentation of ATA8-ACS chapter 7.21).
+//!
+//! Feature set is obsolete since ACS-2, but still implemented by some hybrid drives.
+
+use std::{io, time::Duration};
+
+use crate::{Device, RawAta, TaskFile, Transfer, ATA_DEV_LBA};
+
+const ATA_NV_CACHE: u8 = 0xB6;
+
+const NV_SET_POWER_MODE: u16 = 0x00;
+const NV_RETURN_FROM_POWER_MODE: u16 = 0x01;
+const NV_FLUSH: u16 = 0x14;
+const NV_ENABLE: u16 = 0x15;
+const NV_DISABLE: u16 = 0x16;
+
+/// Flushing may require spinning-up the media and writing the whole cache
+const NV_FLUSH_TIMEOUT: Duration = Duration::from_secs(60);
+
+impl Device {
+    /// Flush the whole NV Cache to the media.
+    pub fn nv_cache_flush(&mut self) -> io::Result<()> {
+        self.nv_cache_command(NV_FLUSH, 0, Some(NV_FLUSH_TIMEOUT))
+    }
+
+    /// Enable NV Cache.
+    pub fn nv_cache_enable(&mut self) -> io::Result<()> {
+        self.nv_cache_command(NV_ENABLE, 0, None)
+    }
+
+    /// Disable NV Cache.
+    pub fn nv_cache_disable(&mut self) -> io::Result<()> {
+        self.nv_cache_command(NV_DISABLE, 0, None)
+    }
+
+    /// Enter NV Cache Power Mode, allowing the drive to spin down and serve requests from the NV
+    /// Cache. Media is kept spinning for at least `min_high_power_secs` after each access.
+    pub fn nv_cache_spin_down_enable(&mut self, min_high_power_secs: u16) -> io::Result<()> {
+        self.nv_cache_command(NV_SET_POWER_MODE, min_high_power_secs, None)
+    }
+
+    /// Leave NV Cache Power Mode, so the media is no longer spun down by the drive on its own.
+    pub fn nv_cache_spin_down_disable(&mut self) -> io::Result<()> {
+        self.nv_cache_command(NV_RETURN_FROM_POWER_MODE, 0, None)
+    }
+
+    /// Issue a single non-data NV Cache subcommand
+    fn nv_cache_command(
+        &mut self,
+        feature: u16,
+        count: u16,
+        timeout: Option<Duration>,
+    ) -> io::Result<()> {
+        let tf = TaskFile {
+            command: ATA_NV_CACHE,
+            features: feature,
+            count,
+            lba: 0,
+            device: ATA_DEV_LBA,
+            ext: true,
+            timeout,
+            ..Default::default()
+        };
+
+        self.retry(|ata| ata.raw_command(&tf, Transfer::NonData))?;
+        Ok(())
+    }
+}
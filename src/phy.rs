@@ -0,0 +1,122 @@
+//! SATA PHY event counters (documentation of SATA 3.x chapter 13.7.5), tracking link errors
+//! like CRC errors of a marginal cable or backplane.
+
+use std::io;
+
+use crate::{os, Device};
+
+const LOG_SATA_PHY_EVENT_COUNTERS: u8 = 0x11;
+
+/// Bits of a counter identifier word: vendor specific flag, size of the value in words, and ID
+const PHY_EVENT_VENDOR: u16 = 1 << 15;
+const PHY_EVENT_SIZE_SHIFT: u16 = 12;
+const PHY_EVENT_ID_MASK: u16 = 0x0FFF;
+
+/// Counters start after a reserved double word
+const PHY_EVENT_FIRST: usize = 4;
+/// Last double word holds the checksum
+const PHY_EVENT_END: usize = os::SECTOR_BYTES - 4;
+
+impl Device {
+    /// Read SATA PHY event counters from General Purpose Log 0x11 as `(id, value)` pairs.
+    ///
+    /// Identifier keeps the vendor specific flag (bit 15), see [`phy_event_name`] for the
+    /// standard ones. Counters saturate at their maximum value. Drives not supporting the log
+    /// (identify word 76 bit 10) are reported with an error. Log checksum is verified.
+    pub fn phy_event_counters(&mut self) -> io::Result<Vec<(u16, u64)>> {
+        if self
+            .ident
+            .is_some_and(|id| !id.supports_phy_event_counters())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "SATA PHY event counters not supported",
+            ));
+        }
+
+        let mut log = [0u8; os::SECTOR_BYTES];
+        self.read_log_ext(LOG_SATA_PHY_EVENT_COUNTERS, 0, &mut log)?;
+        if log.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)) != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid checksum of SATA PHY event counters log",
+            ));
+        }
+
+        Ok(parse_phy_events(&log))
+    }
+}
+
+/// Return description of a standard SATA PHY event counter, `None` if unknown or vendor
+/// specific
+pub fn phy_event_name(id: u16) -> Option<&'static str> {
+    Some(match id {
+        0x001 => "Command failed due to ICRC error",
+        0x002 => "R_ERR response for data FIS",
+        0x003 => "R_ERR response for device-to-host data FIS",
+        0x004 => "R_ERR response for host-to-device data FIS",
+        0x005 => "R_ERR response for non-data FIS",
+        0x006 => "R_ERR response for device-to-host non-data FIS",
+        0x007 => "R_ERR response for host-to-device non-data FIS",
+        0x008 => "Device-to-host non-data FIS retries",
+        0x009 => "Transition from drive PhyRdy to drive PhyNRdy",
+        0x00A => "Device-to-host register FISes sent due to a COMRESET",
+        0x00B => "CRC errors within host-to-device FIS",
+        0x00D => "Non-CRC errors within host-to-device FIS",
+        0x00F => "R_ERR response for host-to-device data FIS due to CRC errors",
+        0x010 => "R_ERR response for host-to-device data FIS due to non-CRC errors",
+        0x012 => "R_ERR response for host-to-device non-data FIS due to CRC errors",
+        0x013 => "R_ERR response for host-to-device non-data FIS due to non-CRC errors",
+        _ => return None,
+    })
+}
+
+/// Decode counters of the log, up to the zero identifier ending the list
+fn parse_phy_events(log: &[u8; os::SECTOR_BYTES]) -> Vec<(u16, u64)> {
+    let mut counters = Vec::new();
+    let mut pos = PHY_EVENT_FIRST;
+
+    while pos + 2 <= PHY_EVENT_END {
+        let word = u16::from_le_bytes([log[pos], log[pos + 1]]);
+        if word == 0 {
+            break;
+        }
+        let size = (((word >> PHY_EVENT_SIZE_SHIFT) & 0x7) as usize * 2).min(8);
+        pos += 2;
+        if pos + size > PHY_EVENT_END {
+            break;
+        }
+
+        let mut value = [0u8; 8];
+        value[..size].copy_from_slice(&log[pos..pos + size]);
+        counters.push((
+            word & (PHY_EVENT_VENDOR | PHY_EVENT_ID_MASK),
+            u64::from_le_bytes(value),
+        ));
+        pos += size;
+    }
+    counters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_phy_events() {
+        let mut log = [0u8; os::SECTOR_BYTES];
+        log[4..8].copy_from_slice(&[0x01, 0x10, 0x05, 0x00]);
+        log[8..14].copy_from_slice(&[0x0B, 0x20, 0x00, 0x00, 0x01, 0x00]);
+        log[14..20].copy_from_slice(&[0x01, 0xA0, 0x07, 0x00, 0x00, 0x00]);
+
+        assert_eq!(
+            parse_phy_events(&log),
+            [(0x001, 5), (0x00B, 0x1_0000), (0x8001, 7)]
+        );
+        assert_eq!(
+            phy_event_name(0x00B),
+            Some("CRC errors within host-to-device FIS")
+        );
+        assert_eq!(phy_event_name(0x8001), None);
+    }
+}
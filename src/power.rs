@@ -0,0 +1,232 @@
+//! Power management feature set.
+
+use std::{
+    io, thread,
+    time::{Duration, Instant},
+};
+
+use crate::{Device, RawAta, TaskFile, Transfer, ATA_DEV_LBA, ATA_SET_FEATURES};
+
+const ATA_READ_VERIFY_SECTORS: u8 = 0x40;
+const ATA_READ_VERIFY_SECTORS_EXT: u8 = 0x42;
+const ATA_STANDBY: u8 = 0xE2;
+const ATA_IDLE: u8 = 0xE3;
+const ATA_CHECK_POWER_MODE: u8 = 0xE5;
+
+const SETFEATURES_PUIS_ON: u8 = 0x06;
+const SETFEATURES_PUIS_SPINUP: u8 = 0x07;
+const SETFEATURES_PUIS_OFF: u8 = 0x86;
+
+/// Spinning up a drive from standby takes up to several seconds, more for large drives
+const SPIN_UP_TIMEOUT: Duration = Duration::from_secs(30);
+/// Delay between power mode checks while waiting for the drive to spin up
+const SPIN_UP_POLL: Duration = Duration::from_millis(250);
+
+/// Power mode reported by `CHECK POWER MODE`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PowerMode {
+    /// Standby, media is not spinning
+    Standby,
+    /// Idle, media may be spinning with reduced power
+    Idle,
+    /// Active or Idle
+    Active,
+    /// Other state, like NV Cache power modes or vendor specific ones
+    Other(u8),
+}
+
+impl From<u8> for PowerMode {
+    /// Decode sector count register returned by `CHECK POWER MODE`
+    fn from(count: u8) -> Self {
+        match count {
+            0x00 => PowerMode::Standby,
+            0x80 => PowerMode::Idle,
+            0xFF => PowerMode::Active,
+            other => PowerMode::Other(other),
+        }
+    }
+}
+
+/// Standby timer period, encoded in sector count register of `IDLE` and `STANDBY` commands.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StandbyPeriod {
+    /// Standby timer disabled
+    Disabled,
+    /// Timer period, rounded up to the nearest one drive can represent: multiple of 5 seconds up
+    /// to 20 minutes, 21 minutes, 21 minutes 15 seconds, then multiple of 30 minutes up to 5.5
+    /// hours. Longer periods are capped at 5.5 hours.
+    Period(Duration),
+    /// Vendor specific period between 8 and 12 hours
+    VendorSpecific,
+}
+
+impl StandbyPeriod {
+    /// Encode period as a sector count register value
+    pub fn to_count(self) -> u8 {
+        let period = match self {
+            StandbyPeriod::Disabled => return 0,
+            StandbyPeriod::VendorSpecific => return 253,
+            StandbyPeriod::Period(period) => period,
+        };
+
+        // Round up to whole seconds, zero-length period would disable timer
+        let secs = (period.as_secs() + (period.subsec_nanos() > 0) as u64).max(1);
+
+        match secs {
+            0..=1200 => secs.div_ceil(5) as u8,
+            1201..=1260 => 252,
+            1261..=1275 => 255,
+            _ => (240 + secs.div_ceil(1800)).min(251) as u8,
+        }
+    }
+}
+
+impl Device {
+    /// Set standby timer using `IDLE` (ATA cmd 0xE3).
+    ///
+    /// Drive enters Idle mode and will spin down to Standby after `period` of inactivity.
+    pub fn set_standby_timer(&mut self, period: StandbyPeriod) -> io::Result<()> {
+        self.power_command(ATA_IDLE, period.to_count())
+    }
+
+    /// Spin down immediately and set standby timer using `STANDBY` (ATA cmd 0xE2).
+    pub fn standby(&mut self, period: StandbyPeriod) -> io::Result<()> {
+        self.power_command(ATA_STANDBY, period.to_count())
+    }
+
+    /// Return current power mode using `CHECK POWER MODE` (ATA cmd 0xE5). It does not wake the
+    /// drive up.
+    pub fn check_power_mode(&mut self) -> io::Result<PowerMode> {
+        let tf = TaskFile {
+            command: ATA_CHECK_POWER_MODE,
+            ..Default::default()
+        };
+
+        let out = self.retry(|ata| ata.raw_command(&tf, Transfer::NonData))?;
+        Ok(PowerMode::from(out.count as u8))
+    }
+
+    /// Wake the drive up and wait until it is ready.
+    ///
+    /// Sector 0 is verified with `READ VERIFY SECTOR(S) EXT` (ATA cmd 0x42, or 0x40 for drives
+    /// without 48-bit addressing), which spins the media up without transferring any data. Then
+    /// power mode is polled with [`Device::check_power_mode`] until drive reports
+    /// [`PowerMode::Active`] or [`PowerMode::Idle`], for at most 30 seconds.
+    pub fn spin_up(&mut self) -> io::Result<()> {
+        let deadline = Instant::now() + SPIN_UP_TIMEOUT;
+        let lba48 = self.lba48();
+        let tf = TaskFile {
+            command: if lba48 {
+                ATA_READ_VERIFY_SECTORS_EXT
+            } else {
+                ATA_READ_VERIFY_SECTORS
+            },
+            count: 1,
+            device: ATA_DEV_LBA,
+            ext: lba48,
+            timeout: Some(SPIN_UP_TIMEOUT),
+            ..Default::default()
+        };
+        self.retry(|ata| ata.raw_command(&tf, Transfer::NonData))?;
+
+        loop {
+            if let PowerMode::Active | PowerMode::Idle = self.check_power_mode()? {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "drive did not spin up",
+                ));
+            }
+            thread::sleep(SPIN_UP_POLL);
+        }
+    }
+
+    /// Enable or disable Power-Up In Standby using `SET FEATURES` (ATA cmd 0xEF, subcommand 0x06
+    /// or 0x86).
+    ///
+    /// When enabled, the drive stays in Standby after power-up until spun up by a media access
+    /// or, if it requires so (see [`IdentifyDeviceData::puis_requires_spin_up`][spin]), by
+    /// [`Device::puis_spin_up`]. It allows staggering spin-up of many drives. Setting is kept
+    /// over power cycles. Drives not supporting the feature set (identify word 83 bit 5) are
+    /// reported with an error. Cached identification is refreshed afterwards.
+    ///
+    /// [spin]: crate::IdentifyDeviceData::puis_requires_spin_up
+    pub fn set_puis(&mut self, enable: bool) -> io::Result<()> {
+        if self.ident.is_some_and(|id| !id.supports_puis()) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Power-Up In Standby not supported",
+            ));
+        }
+
+        let subcommand = if enable {
+            SETFEATURES_PUIS_ON
+        } else {
+            SETFEATURES_PUIS_OFF
+        };
+        self.set_features(subcommand, 0)?;
+
+        self.ident = self.info().ok();
+        Ok(())
+    }
+
+    /// Spin up a drive powered up in Standby using `SET FEATURES` (ATA cmd 0xEF, subcommand 0x07).
+    ///
+    /// The command completes once the drive is spun up, which may take many seconds; it is given
+    /// 30 seconds.
+    pub fn puis_spin_up(&mut self) -> io::Result<()> {
+        let tf = TaskFile {
+            command: ATA_SET_FEATURES,
+            features: SETFEATURES_PUIS_SPINUP as u16,
+            device: ATA_DEV_LBA,
+            timeout: Some(SPIN_UP_TIMEOUT),
+            ..Default::default()
+        };
+
+        self.retry(|ata| ata.raw_command(&tf, Transfer::NonData))?;
+        Ok(())
+    }
+
+    /// Issue a single non-data power management command
+    fn power_command(&mut self, command: u8, count: u8) -> io::Result<()> {
+        let tf = TaskFile {
+            command,
+            count: count as u16,
+            ..Default::default()
+        };
+
+        self.retry(|ata| ata.raw_command(&tf, Transfer::NonData))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_standby_encoding() {
+        let period = |secs| StandbyPeriod::Period(Duration::from_secs(secs)).to_count();
+
+        assert_eq!(StandbyPeriod::Disabled.to_count(), 0);
+        assert_eq!(period(0), 1);
+        assert_eq!(period(5), 1);
+        assert_eq!(period(6), 2);
+        assert_eq!(period(1200), 240);
+        assert_eq!(period(1260), 252);
+        assert_eq!(period(1275), 255);
+        assert_eq!(period(1800), 241);
+        assert_eq!(period(3600), 242);
+        assert_eq!(period(100_000), 251);
+    }
+
+    #[test]
+    fn check_power_mode_decoding() {
+        assert_eq!(PowerMode::from(0x00), PowerMode::Standby);
+        assert_eq!(PowerMode::from(0x80), PowerMode::Idle);
+        assert_eq!(PowerMode::from(0xFF), PowerMode::Active);
+        assert_eq!(PowerMode::from(0x40), PowerMode::Other(0x40));
+    }
+}
@@ -0,0 +1,152 @@
+//! Sanitize Device feature set (documentation of ACS-3 chapter 4.17).
+
+use std::{
+    io, thread,
+    time::{Duration, Instant},
+};
+
+use crate::{AtaTaskfileOut, Device, RawAta, TaskFile, Transfer, ATA_DEV_LBA};
+
+const ATA_SANITIZE_DEVICE: u8 = 0xB4;
+
+const SANITIZE_STATUS_EXT: u16 = 0x0000;
+const SANITIZE_CRYPTO_SCRAMBLE_EXT: u16 = 0x0011;
+const SANITIZE_BLOCK_ERASE_EXT: u16 = 0x0012;
+
+/// Signatures required in the LBA field, "CrypEx" and "BkEx" in ASCII
+const CRYPTO_SCRAMBLE_KEY: u64 = 0x4372_7970_4578;
+const BLOCK_ERASE_KEY: u64 = 0x0000_426B_4578;
+
+/// Bits of the count field returned by `SANITIZE STATUS EXT`
+const STATUS_COMPLETED: u16 = 1 << 15;
+const STATUS_IN_PROGRESS: u16 = 1 << 14;
+const STATUS_FROZEN: u16 = 1 << 13;
+
+/// Block erase of a large rotating drive may take many hours
+const SANITIZE_TIMEOUT: Duration = Duration::from_secs(24 * 3600);
+/// Delay between status checks while a sanitize operation runs
+const SANITIZE_POLL: Duration = Duration::from_secs(1);
+
+/// State of the Sanitize Device feature set reported by `SANITIZE STATUS EXT`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SanitizeStatus {
+    /// Sanitize operation is running in the background
+    pub in_progress: bool,
+    /// Last sanitize operation completed successfully
+    pub completed: bool,
+    /// Sanitize commands are frozen until the next power cycle
+    pub frozen: bool,
+    /// Progress of the running operation, from 0 to 0xFFFF
+    pub progress: u16,
+}
+
+impl SanitizeStatus {
+    /// Decode count (status bits) and LBA (progress) fields of `SANITIZE STATUS EXT`
+    fn from_registers(count: u16, lba: u64) -> Self {
+        SanitizeStatus {
+            in_progress: count & STATUS_IN_PROGRESS != 0,
+            completed: count & STATUS_COMPLETED != 0,
+            frozen: count & STATUS_FROZEN != 0,
+            progress: lba as u16,
+        }
+    }
+}
+
+impl Device {
+    /// Return state of Sanitize Device feature set using `SANITIZE STATUS EXT` (ATA cmd 0xB4,
+    /// subcommand 0x0000).
+    pub fn sanitize_status(&mut self) -> io::Result<SanitizeStatus> {
+        let out = self.sanitize_command(SANITIZE_STATUS_EXT, 0)?;
+        Ok(SanitizeStatus::from_registers(out.count, out.lba))
+    }
+
+    /// Change the encryption key of a self-encrypting drive using `CRYPTO SCRAMBLE EXT` (ATA
+    /// cmd 0xB4, subcommand 0x0011) and wait until it completes.
+    ///
+    /// Drives not supporting it (identify word 59 bit 13) are reported with an error. **All
+    /// data on the drive, including reallocated sectors, becomes unreadable.**
+    pub fn sanitize_crypto_scramble(&mut self) -> io::Result<()> {
+        if self.ident.is_some_and(|id| !id.supports_crypto_scramble()) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "CRYPTO SCRAMBLE EXT not supported",
+            ));
+        }
+        if self.skip_dry_run(format_args!("sanitize crypto scramble"))? {
+            return Ok(());
+        }
+
+        self.sanitize_command(SANITIZE_CRYPTO_SCRAMBLE_EXT, CRYPTO_SCRAMBLE_KEY)?;
+        self.wait_for_sanitize()
+    }
+
+    /// Erase all user data areas using `BLOCK ERASE EXT` (ATA cmd 0xB4, subcommand 0x0012) and
+    /// wait until it completes, for at most 24 hours.
+    ///
+    /// Drives not supporting it (identify word 59 bit 15) are reported with an error. **All
+    /// data on the drive, including reallocated sectors, is destroyed.**
+    pub fn sanitize_block_erase(&mut self) -> io::Result<()> {
+        if self.ident.is_some_and(|id| !id.supports_block_erase()) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "BLOCK ERASE EXT not supported",
+            ));
+        }
+        if self.skip_dry_run(format_args!("sanitize block erase"))? {
+            return Ok(());
+        }
+
+        self.sanitize_command(SANITIZE_BLOCK_ERASE_EXT, BLOCK_ERASE_KEY)?;
+        self.wait_for_sanitize()
+    }
+
+    /// Poll sanitize status until the running operation ends, reporting a failed one as an error
+    fn wait_for_sanitize(&mut self) -> io::Result<()> {
+        let deadline = Instant::now() + SANITIZE_TIMEOUT;
+        loop {
+            let status = self.sanitize_status()?;
+            if !status.in_progress {
+                if !status.completed {
+                    return Err(io::Error::other("sanitize operation failed"));
+                }
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "sanitize operation did not complete",
+                ));
+            }
+            thread::sleep(SANITIZE_POLL);
+        }
+    }
+
+    /// Issue a single `SANITIZE DEVICE` subcommand
+    fn sanitize_command(&mut self, subcommand: u16, lba: u64) -> io::Result<AtaTaskfileOut> {
+        let tf = TaskFile {
+            command: ATA_SANITIZE_DEVICE,
+            features: subcommand,
+            lba,
+            device: ATA_DEV_LBA,
+            ext: true,
+            ..Default::default()
+        };
+
+        self.retry(|ata| ata.raw_command(&tf, Transfer::NonData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_sanitize_status() {
+        let status = SanitizeStatus::from_registers(0x4000, 0x8000);
+        assert!(status.in_progress && !status.completed && !status.frozen);
+        assert_eq!(status.progress, 0x8000);
+
+        let status = SanitizeStatus::from_registers(0xA000, 0);
+        assert!(!status.in_progress && status.completed && status.frozen);
+    }
+}
@@ -0,0 +1,99 @@
+//! Arbitrary SCSI commands, sent without ATA pass-through wrapping: through `SG_IO` on Linux
+//! and `XPT_SCSI_IO` CCBs of CAM on FreeBSD.
+
+use std::{convert::TryInto, io};
+
+use crate::{Device, Direction, SenseData};
+
+/// Longest CDB accepted by `SG_IO` and by CCBs of CAM
+const MAX_CDB_LEN: usize = 16;
+
+const SCSI_SERVICE_ACTION_IN_16: u8 = 0x9E;
+const SAI_READ_CAPACITY_16: u8 = 0x10;
+/// Length of `READ CAPACITY (16)` parameter data
+const READ_CAPACITY_16_LEN: usize = 32;
+const READ_CAPACITY_TIMEOUT_MS: u32 = 10_000;
+
+/// Sense keys not reporting a failure: NO SENSE and RECOVERED ERROR
+const SENSE_KEY_RECOVERED: u8 = 1;
+
+impl Device {
+    /// Issue an arbitrary SCSI command `cdb` and return the sense buffer, all zeros if the
+    /// command completed without one.
+    ///
+    /// It is sent through `SG_IO` on Linux and as an `XPT_SCSI_IO` CCB on FreeBSD. It reaches the
+    /// SCSI layer itself, like a USB-SATA bridge answering `INQUIRY` or its vendor commands,
+    /// also when the bridge rejects ATA pass-through. `buffer` is read from or written to
+    /// according to `data`, whole. Only failures of the system call or the transport are
+    /// reported as errors, the caller interprets the sense data. **It bypasses all
+    /// protections**, including dry-run mode.
+    pub fn scsi_command(
+        &mut self,
+        cdb: &[u8],
+        data: Direction,
+        buffer: &mut [u8],
+        timeout_ms: u32,
+    ) -> io::Result<[u8; 32]> {
+        if cdb.is_empty() || cdb.len() > MAX_CDB_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid CDB length {}", cdb.len()),
+            ));
+        }
+
+        self.ata.scsi_command(cdb, data, buffer, timeout_ms)
+    }
+
+    /// Return last LBA and logical block size in bytes using SCSI `READ CAPACITY (16)`.
+    ///
+    /// It is answered by the SCSI layer, like a USB-SATA bridge or the kernel SAT translator, so
+    /// it often works where ATA pass-through of `IDENTIFY DEVICE` is blocked. Capacity is the
+    /// last LBA plus one; with HPA set it is what the host sees.
+    pub fn read_capacity(&mut self) -> io::Result<(u64, u32)> {
+        let mut cdb = [0u8; 16];
+        cdb[0] = SCSI_SERVICE_ACTION_IN_16;
+        cdb[1] = SAI_READ_CAPACITY_16;
+        cdb[10..14].copy_from_slice(&(READ_CAPACITY_16_LEN as u32).to_be_bytes());
+
+        let mut data = [0u8; READ_CAPACITY_16_LEN];
+        self.scsi_checked(&cdb, Direction::In, &mut data, READ_CAPACITY_TIMEOUT_MS)?;
+        Ok(parse_read_capacity(&data))
+    }
+
+    /// Issue a SCSI command like [`Device::scsi_command`], reporting sense data of a failure as
+    /// an error carrying [`SenseData`]
+    pub(crate) fn scsi_checked(
+        &mut self,
+        cdb: &[u8],
+        data: Direction,
+        buffer: &mut [u8],
+        timeout_ms: u32,
+    ) -> io::Result<()> {
+        let sb = self.scsi_command(cdb, data, buffer, timeout_ms)?;
+        match SenseData::parse(&sb) {
+            Some(sense) if sense.key > SENSE_KEY_RECOVERED => Err(io::Error::other(sense)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Decode last LBA (bytes 0..8) and block length (bytes 8..12) of `READ CAPACITY (16)` data
+fn parse_read_capacity(data: &[u8; READ_CAPACITY_16_LEN]) -> (u64, u32) {
+    (
+        u64::from_be_bytes(data[0..8].try_into().expect("8 bytes")),
+        u32::from_be_bytes(data[8..12].try_into().expect("4 bytes")),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_read_capacity() {
+        let mut data = [0u8; READ_CAPACITY_16_LEN];
+        data[..8].copy_from_slice(&0x1D1C_0BEAFu64.to_be_bytes());
+        data[8..12].copy_from_slice(&512u32.to_be_bytes());
+        assert_eq!(parse_read_capacity(&data), (0x1D1C_0BEAF, 512));
+    }
+}
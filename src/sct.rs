@@ -0,0 +1,188 @@
+//! SMART Command Transport (SCT, documentation chapter 8).
+//!
+//! SCT commands are sent as a key sector written to log 0xE0, their data (if any) is transferred
+//! by reading or writing log 0xE1 (SCT Data Transfer).
+
+use std::{io, time::Duration};
+
+use crate::{AtaTaskfileOut, Device};
+
+const LOG_SCT_COMMAND_STATUS: u8 = 0xE0;
+const LOG_SCT_DATA_TRANSFER: u8 = 0xE1;
+
+const SCT_ACTION_ERC: u16 = 0x0003;
+const SCT_ACTION_DATA_TABLE: u16 = 0x0005;
+
+const SCT_FUNCTION_SET: u16 = 0x0001;
+const SCT_FUNCTION_GET: u16 = 0x0002;
+const SCT_FUNCTION_READ_TABLE: u16 = 0x0001;
+
+const SCT_ERC_READ: u16 = 0x0001;
+const SCT_ERC_WRITE: u16 = 0x0002;
+
+const SCT_TABLE_TEMPERATURE_HISTORY: u16 = 0x0002;
+
+/// Offsets of temperatures in SCT status
+const SCT_STATUS_CURRENT_TEMP: usize = 200;
+const SCT_STATUS_MAX_TEMP_LIFETIME: usize = 204;
+
+/// Temperature value meaning "no measurement"
+const SCT_TEMP_INVALID: i8 = -128;
+
+/// Offsets of fields of temperature history table
+const TEMP_TABLE_INTERVAL: usize = 4;
+const TEMP_TABLE_CB_SIZE: usize = 30;
+const TEMP_TABLE_CB_INDEX: usize = 32;
+const TEMP_TABLE_CB: usize = 34;
+
+/// Drive temperature history read with [`Device::sct_temperature_history`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TempHistory {
+    /// Current temperature in Celsius
+    pub current: Option<i8>,
+    /// Highest temperature over the drive lifetime in Celsius
+    pub worst: Option<i8>,
+    /// Time between history samples
+    pub interval: Duration,
+    /// History samples in Celsius, oldest first. `None` marks samples not measured, like when
+    /// drive was powered off.
+    pub samples: Vec<Option<i8>>,
+}
+
+impl TempHistory {
+    /// Decode temperature history table and SCT status
+    fn parse(status: &[u8; 512], table: &[u8; 512]) -> TempHistory {
+        let temp = |value: u8| match value as i8 {
+            SCT_TEMP_INVALID => None,
+            value => Some(value),
+        };
+        let le16 = |offset: usize| u16::from_le_bytes([table[offset], table[offset + 1]]);
+
+        let size = (le16(TEMP_TABLE_CB_SIZE) as usize).min(table.len() - TEMP_TABLE_CB);
+        let buffer = &table[TEMP_TABLE_CB..TEMP_TABLE_CB + size];
+        let current = temp(status[SCT_STATUS_CURRENT_TEMP]);
+        let worst = temp(status[SCT_STATUS_MAX_TEMP_LIFETIME]);
+        let interval = Duration::from_secs(60 * le16(TEMP_TABLE_INTERVAL) as u64);
+
+        if size == 0 {
+            return TempHistory {
+                current,
+                worst,
+                interval,
+                samples: Vec::new(),
+            };
+        }
+
+        // Index points to the most recent sample, so the oldest one follows it
+        let newest = (le16(TEMP_TABLE_CB_INDEX) as usize).min(size - 1);
+        let samples = buffer[newest + 1..]
+            .iter()
+            .chain(buffer[..size.min(newest + 1)].iter())
+            .map(|&value| temp(value))
+            .collect();
+
+        TempHistory {
+            current,
+            worst,
+            interval,
+            samples,
+        }
+    }
+}
+
+impl Device {
+    /// Get SCT Error Recovery Control timeouts as `(read_ms, write_ms)`.
+    ///
+    /// It is the maximal time drive spends on internal error recovery before reporting a failed
+    /// read or write (also known as TLER). Value of `0` means recovery time is not limited.
+    pub fn get_sct_erc(&mut self) -> io::Result<(u16, u16)> {
+        let read = self.sct_erc_get(SCT_ERC_READ)?;
+        let write = self.sct_erc_get(SCT_ERC_WRITE)?;
+        Ok((read, write))
+    }
+
+    /// Set SCT Error Recovery Control timeouts.
+    ///
+    /// Drive accepts timeouts in 100ms units, so values are rounded down to a multiple of 100ms.
+    /// Value of `0` disables the limit. Setting is usually lost on power cycle.
+    pub fn set_sct_erc(&mut self, read_ms: u16, write_ms: u16) -> io::Result<()> {
+        self.sct_command(&[
+            SCT_ACTION_ERC,
+            SCT_FUNCTION_SET,
+            SCT_ERC_READ,
+            read_ms / 100,
+        ])?;
+        self.sct_command(&[
+            SCT_ACTION_ERC,
+            SCT_FUNCTION_SET,
+            SCT_ERC_WRITE,
+            write_ms / 100,
+        ])?;
+        Ok(())
+    }
+
+    /// Read current and worst temperature from SCT status and the temperature history from SCT
+    /// data table 0x0002 (documentation chapter 8.3.5).
+    pub fn sct_temperature_history(&mut self) -> io::Result<TempHistory> {
+        let mut status = [0u8; 512];
+        self.read_log_ext(LOG_SCT_COMMAND_STATUS, 0, &mut status)?;
+
+        self.sct_command(&[
+            SCT_ACTION_DATA_TABLE,
+            SCT_FUNCTION_READ_TABLE,
+            SCT_TABLE_TEMPERATURE_HISTORY,
+        ])?;
+        let mut table = [0u8; 512];
+        self.read_log_ext(LOG_SCT_DATA_TRANSFER, 0, &mut table)?;
+
+        Ok(TempHistory::parse(&status, &table))
+    }
+
+    /// Read one SCT ERC timer in milliseconds. Drive returns it in `count` (bits 0..7) and `lba`
+    /// (bits 8..15) output registers.
+    fn sct_erc_get(&mut self, selection: u16) -> io::Result<u16> {
+        let out = self.sct_command(&[SCT_ACTION_ERC, SCT_FUNCTION_GET, selection])?;
+        let value = (out.count & 0xFF) | (out.lba as u16 & 0xFF) << 8;
+        Ok(value.saturating_mul(100))
+    }
+
+    /// Issue a single SCT command. Key sector is made of given words, padded with zeros.
+    fn sct_command(&mut self, words: &[u16]) -> io::Result<AtaTaskfileOut> {
+        let mut key = [0u8; 512];
+        for (i, word) in words.iter().enumerate() {
+            key[2 * i..2 * i + 2].copy_from_slice(&word.to_le_bytes());
+        }
+
+        self.write_log_ext(LOG_SCT_COMMAND_STATUS, 0, &key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_temperature_history() {
+        let mut status = [0u8; 512];
+        status[SCT_STATUS_CURRENT_TEMP] = 35;
+        status[SCT_STATUS_MAX_TEMP_LIFETIME] = 52;
+
+        let mut table = [0u8; 512];
+        table[TEMP_TABLE_INTERVAL] = 1;
+        table[TEMP_TABLE_CB_SIZE] = 4;
+        table[TEMP_TABLE_CB_INDEX] = 1;
+        table[TEMP_TABLE_CB..TEMP_TABLE_CB + 4].copy_from_slice(&[31, 32, 0x80, 30]);
+
+        let history = TempHistory::parse(&status, &table);
+
+        assert_eq!(history.current, Some(35));
+        assert_eq!(history.worst, Some(52));
+        assert_eq!(history.interval, Duration::from_secs(60));
+        assert_eq!(history.samples, vec![None, Some(30), Some(31), Some(32)]);
+
+        table[TEMP_TABLE_CB_SIZE] = 0;
+        let history = TempHistory::parse(&status, &table);
+        assert_eq!(history.current, Some(35));
+        assert!(history.samples.is_empty());
+    }
+}
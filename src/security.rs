@@ -0,0 +1,139 @@
+//! Security feature set (documentation chapter 4.18).
+
+use std::{io, time::Duration};
+
+use crate::{os, Device, RawAta, TaskFile, Transfer};
+
+const ATA_SECURITY_SET_PASSWORD: u8 = 0xF1;
+const ATA_SECURITY_ERASE_PREPARE: u8 = 0xF3;
+const ATA_SECURITY_ERASE_UNIT: u8 = 0xF4;
+const ATA_SECURITY_DISABLE_PASSWORD: u8 = 0xF6;
+
+/// Bit of the first word of `SECURITY ERASE UNIT` data. Bit 0 (identifier) stays cleared,
+/// selecting the user password.
+const ERASE_ENHANCED: u16 = 1 << 1;
+
+/// Erase time assumed when drive does not report it
+const ERASE_TIMEOUT_UNKNOWN: Duration = Duration::from_secs(24 * 3600);
+
+impl Device {
+    /// Check with a fresh identification record if Security feature set is frozen.
+    ///
+    /// Many BIOSes issue `SECURITY FREEZE LOCK` at boot, which can only be cleared by a power
+    /// cycle of the drive alone, like unplugging and plugging it again while the system runs (or
+    /// suspending the system). Erasing a frozen drive fails with a plain command abort.
+    pub fn is_security_frozen(&mut self) -> io::Result<bool> {
+        Ok(self.info()?.is_security_frozen())
+    }
+
+    /// Set user password using `SECURITY SET PASSWORD` (ATA cmd 0xF1, documentation chapter
+    /// 7.38), with high master password capability.
+    ///
+    /// It enables Security feature set: after the next power cycle the drive is locked until
+    /// unlocked with `password`. It is a prerequisite of [`Device::secure_erase`], which disables
+    /// it again on success. Frozen drives abort it.
+    pub fn security_set_password(&mut self, password: &[u8; 32]) -> io::Result<()> {
+        if self.skip_dry_run(format_args!("security password change"))? {
+            return Ok(());
+        }
+
+        // Word 0 cleared: user password, high master password capability
+        let mut data = [0u8; os::SECTOR_BYTES];
+        data[2..34].copy_from_slice(password);
+
+        let tf = TaskFile {
+            command: ATA_SECURITY_SET_PASSWORD,
+            count: 1,
+            ..Default::default()
+        };
+
+        self.ata.raw_command(&tf, Transfer::PioOut(&data))?;
+        self.ident = self.info().ok();
+        Ok(())
+    }
+
+    /// Remove user password using `SECURITY DISABLE PASSWORD` (ATA cmd 0xF6, documentation
+    /// chapter 7.34), disabling Security feature set set up with
+    /// [`Device::security_set_password`]. `password` must match the user password.
+    pub fn security_disable_password(&mut self, password: &[u8; 32]) -> io::Result<()> {
+        if self.skip_dry_run(format_args!("security password removal"))? {
+            return Ok(());
+        }
+
+        // Word 0 cleared: compare with user password
+        let mut data = [0u8; os::SECTOR_BYTES];
+        data[2..34].copy_from_slice(password);
+
+        let tf = TaskFile {
+            command: ATA_SECURITY_DISABLE_PASSWORD,
+            count: 1,
+            ..Default::default()
+        };
+
+        self.ata.raw_command(&tf, Transfer::PioOut(&data))?;
+        self.ident = self.info().ok();
+        Ok(())
+    }
+
+    /// Issue `SECURITY ERASE PREPARE` (ATA cmd 0xF3, documentation chapter 7.35).
+    ///
+    /// It **must** be immediately followed by `SECURITY ERASE UNIT`, otherwise the drive aborts
+    /// the erase. Use [`Device::secure_erase`], which issues both in the correct order.
+    pub fn security_erase_prepare(&mut self) -> io::Result<()> {
+        let tf = TaskFile {
+            command: ATA_SECURITY_ERASE_PREPARE,
+            ..Default::default()
+        };
+
+        self.ata.raw_command(&tf, Transfer::NonData)?;
+        Ok(())
+    }
+
+    /// Erase the whole drive using `SECURITY ERASE PREPARE` (ATA cmd 0xF3) followed by `SECURITY
+    /// ERASE UNIT` (ATA cmd 0xF4, documentation chapter 7.36).
+    ///
+    /// User password **must** be set beforehand, it is passed as `password`. `enhanced` selects
+    /// the enhanced erase, which also overwrites reallocated sectors. Command timeout is twice the
+    /// time estimated by the drive (identify word 89 or 90), or 24 hours if not reported. Neither
+    /// command is retried, so that nothing is issued between them. Frozen drives are reported
+    /// with an error before anything is sent, see [`Device::is_security_frozen`]. **All data on
+    /// the drive is destroyed.**
+    pub fn secure_erase(&mut self, password: &[u8; 32], enhanced: bool) -> io::Result<()> {
+        if self.skip_dry_run(format_args!("security erase (enhanced: {})", enhanced))? {
+            return Ok(());
+        }
+
+        if self.is_security_frozen()? {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "security is frozen, power cycle the drive to clear it",
+            ));
+        }
+
+        let estimate = match self.ident {
+            Some(id) if enhanced => id.get_enhanced_erase_time(),
+            Some(id) => id.get_erase_time(),
+            None => Duration::ZERO,
+        };
+        let timeout = match estimate {
+            Duration::ZERO => ERASE_TIMEOUT_UNKNOWN,
+            estimate => estimate * 2,
+        };
+
+        let mut data = [0u8; os::SECTOR_BYTES];
+        let control: u16 = if enhanced { ERASE_ENHANCED } else { 0 };
+        data[0..2].copy_from_slice(&control.to_le_bytes());
+        data[2..34].copy_from_slice(password);
+
+        let tf = TaskFile {
+            command: ATA_SECURITY_ERASE_UNIT,
+            count: 1,
+            timeout: Some(timeout),
+            ..Default::default()
+        };
+
+        self.security_erase_prepare()?;
+        self.ata.raw_command(&tf, Transfer::PioOut(&data))?;
+        Ok(())
+    }
+}
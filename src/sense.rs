@@ -0,0 +1,257 @@
+//! SCSI sense data returned by SAT translators for failed ATA PASS-THROUGH commands, and Sense
+//! Data Reporting feature set of the drive itself (documentation of ACS-3 chapter 4.19).
+
+use std::{error::Error, fmt, io};
+
+use crate::{CommandError, Device, RawAta, TaskFile, Transfer, ATA_DEV_LBA};
+
+const ATA_REQUEST_SENSE_DATA_EXT: u8 = 0x0B;
+const SETFEATURES_SENSE_DATA: u8 = 0xC3;
+
+const SENSE_FIXED_CURRENT: u8 = 0x70;
+const SENSE_FIXED_DEFERRED: u8 = 0x71;
+const SENSE_DESCRIPTOR_CURRENT: u8 = 0x72;
+const SENSE_DESCRIPTOR_DEFERRED: u8 = 0x73;
+
+/// Sense data of a failed command, in fixed or descriptor format (SPC-4 chapter 4.5).
+///
+/// On Linux it is carried inside [`io::Error`] of commands rejected by the drive or the
+/// translator, possibly wrapped in [`CommandError`]. Use [`SenseData::from_error`] to get it.
+/// Size of the captured data is set with
+/// [`Device::set_sense_buffer_len`](crate::Device::set_sense_buffer_len).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SenseData {
+    /// Sense key (like `3` for MEDIUM ERROR)
+    pub key: u8,
+    /// Additional sense code
+    pub asc: u8,
+    /// Additional sense code qualifier
+    pub ascq: u8,
+    /// Sense data is in descriptor format, otherwise in fixed format
+    pub descriptor: bool,
+    /// Complete sense data as returned, up to the additional length it reports
+    pub raw: Vec<u8>,
+}
+
+impl SenseData {
+    /// Decode sense data in `sb`, `None` if its response code is neither fixed nor descriptor
+    /// format
+    pub fn parse(sb: &[u8]) -> Option<Self> {
+        let (descriptor, key, asc, ascq, len) = match *sb.first()? & 0x7F {
+            SENSE_FIXED_CURRENT | SENSE_FIXED_DEFERRED => (
+                false,
+                *sb.get(2)? & 0x0F,
+                sb.get(12).copied().unwrap_or(0),
+                sb.get(13).copied().unwrap_or(0),
+                8 + sb.get(7).copied().unwrap_or(0) as usize,
+            ),
+            SENSE_DESCRIPTOR_CURRENT | SENSE_DESCRIPTOR_DEFERRED => (
+                true,
+                *sb.get(1)? & 0x0F,
+                sb.get(2).copied().unwrap_or(0),
+                sb.get(3).copied().unwrap_or(0),
+                8 + sb.get(7).copied().unwrap_or(0) as usize,
+            ),
+            _ => return None,
+        };
+
+        Some(SenseData {
+            key,
+            asc,
+            ascq,
+            descriptor,
+            raw: sb[..len.min(sb.len())].to_vec(),
+        })
+    }
+
+    /// Build fixed format sense data of sense key, ASC and ASCQ
+    fn from_codes(key: u8, asc: u8, ascq: u8) -> Self {
+        let mut raw = vec![0u8; 18];
+        raw[0] = SENSE_FIXED_CURRENT;
+        raw[2] = key;
+        raw[7] = 10;
+        raw[12] = asc;
+        raw[13] = ascq;
+
+        SenseData {
+            key,
+            asc,
+            ascq,
+            descriptor: false,
+            raw,
+        }
+    }
+
+    /// Return sense data carried by `err`, also when wrapped in [`CommandError`]
+    pub fn from_error(err: &io::Error) -> Option<&SenseData> {
+        CommandError::peel(err)
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<SenseData>())
+    }
+
+    /// Return descriptors of descriptor format sense data as `(type, payload)`, none for fixed
+    /// format
+    pub fn descriptors(&self) -> Vec<(u8, &[u8])> {
+        let mut descriptors = Vec::new();
+        if !self.descriptor {
+            return descriptors;
+        }
+
+        let mut pos = 8;
+        while pos + 2 <= self.raw.len() {
+            let end = (pos + 2 + self.raw[pos + 1] as usize).min(self.raw.len());
+            descriptors.push((self.raw[pos], &self.raw[pos + 2..end]));
+            pos = end;
+        }
+        descriptors
+    }
+
+    /// Return name of the sense key
+    pub fn key_name(&self) -> &'static str {
+        match self.key {
+            0 => "NO_SENSE",
+            1 => "RECOVERED_ERROR",
+            2 => "NOT_READY",
+            3 => "MEDIUM_ERROR",
+            4 => "HARDWARE_ERROR",
+            5 => "ILLEGAL_REQUEST",
+            6 => "UNIT_ATTENTION",
+            7 => "DATA_PROTECT",
+            8 => "BLANK_CHECK",
+            9 => "VENDOR_SPECIFIC",
+            10 => "COPY_ABORTED",
+            11 => "ABORTED_COMMAND",
+            12 => "OTHER",
+            13 => "VOLUME_OVERFLOW",
+            14 => "MISCOMPARE",
+            _ => "COMPLETE",
+        }
+    }
+}
+
+impl fmt::Display for SenseData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (ASC/ASCQ {:#04x}/{:#04x})",
+            self.key_name(),
+            self.asc,
+            self.ascq
+        )
+    }
+}
+
+impl Error for SenseData {}
+
+impl Device {
+    /// Enable or disable Sense Data Reporting feature set using `SET FEATURES` (ATA cmd 0xEF,
+    /// subcommand 0xC3).
+    ///
+    /// When enabled, a failed command sets the SENSE DATA AVAILABLE status bit and the drive
+    /// keeps sense data describing the failure, which translators like libata fetch into
+    /// [`SenseData`] of the error and which can be read with [`Device::request_sense_data`].
+    /// Drives not supporting the feature set (identify word 119 bit 6) are reported with an
+    /// error. Cached identification is refreshed afterwards.
+    pub fn enable_sense_data_reporting(&mut self, enable: bool) -> io::Result<()> {
+        if self
+            .ident
+            .is_some_and(|id| !id.supports_sense_data_reporting())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Sense Data Reporting not supported",
+            ));
+        }
+
+        self.set_features(SETFEATURES_SENSE_DATA, enable as u16)?;
+        self.ident = self.info().ok();
+        Ok(())
+    }
+
+    /// Read sense data of the last failed command using `REQUEST SENSE DATA EXT` (ATA cmd 0x0B),
+    /// `None` if there is none.
+    ///
+    /// Sense key, ASC and ASCQ are returned in the LBA field and presented as fixed format
+    /// sense data.
+    pub fn request_sense_data(&mut self) -> io::Result<Option<SenseData>> {
+        let tf = TaskFile {
+            command: ATA_REQUEST_SENSE_DATA_EXT,
+            device: ATA_DEV_LBA,
+            ext: true,
+            ..Default::default()
+        };
+
+        let out = self.retry(|ata| ata.raw_command(&tf, Transfer::NonData))?;
+        Ok(sense_from_lba(out.lba))
+    }
+}
+
+/// Decode sense key (bits 19..16), ASC (bits 15..8) and ASCQ (bits 7..0) returned by `REQUEST
+/// SENSE DATA EXT`
+fn sense_from_lba(lba: u64) -> Option<SenseData> {
+    let key = ((lba >> 16) & 0x0F) as u8;
+    let asc = (lba >> 8) as u8;
+    let ascq = lba as u8;
+
+    match (key, asc, ascq) {
+        (0, 0, 0) => None,
+        _ => Some(SenseData::from_codes(key, asc, ascq)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_fixed_sense() {
+        let mut sb = [0u8; 32];
+        sb[0] = 0x70;
+        sb[2] = 0x03;
+        sb[7] = 10;
+        sb[12] = 0x11;
+        sb[13] = 0x04;
+
+        let sense = SenseData::parse(&sb).unwrap();
+        assert_eq!((sense.key, sense.asc, sense.ascq), (3, 0x11, 0x04));
+        assert_eq!(sense.raw.len(), 18);
+        assert!(sense.descriptors().is_empty());
+        assert_eq!(sense.to_string(), "MEDIUM_ERROR (ASC/ASCQ 0x11/0x04)");
+        assert_eq!(SenseData::parse(&[0x00; 32]), None);
+    }
+
+    #[test]
+    fn check_requested_sense() {
+        assert_eq!(sense_from_lba(0), None);
+
+        let sense = sense_from_lba(0x03_1104).unwrap();
+        assert_eq!(SenseData::parse(&sense.raw), Some(sense.clone()));
+        assert_eq!(sense.to_string(), "MEDIUM_ERROR (ASC/ASCQ 0x11/0x04)");
+    }
+
+    #[test]
+    fn check_descriptor_sense() {
+        let mut sb = [0u8; 64];
+        sb[..4].copy_from_slice(&[0x72, 0x0B, 0x00, 0x00]);
+        sb[7] = 14 + 2;
+        sb[8] = 0x09;
+        sb[9] = 12;
+        sb[21] = 0x51;
+        sb[22] = 0x02;
+        sb[23] = 0x00;
+
+        let sense = SenseData::parse(&sb).unwrap();
+        assert_eq!(sense.key, 11);
+        assert_eq!(sense.raw.len(), 24);
+        assert_eq!(sense.descriptors().len(), 2);
+        assert_eq!(sense.descriptors()[0].0, 0x09);
+        assert_eq!(sense.descriptors()[0].1[11], 0x51);
+
+        let err = CommandError::wrap(
+            io::Error::other(sense.clone()),
+            "READ DMA EXT",
+            &Default::default(),
+        );
+        assert_eq!(SenseData::from_error(&err), Some(&sense));
+    }
+}
@@ -0,0 +1,265 @@
+//! SMART feature set (ATA cmd 0xB0, documentation chapter 7.44).
+
+use std::io;
+
+use crate::{Device, RawAta, TaskFile, Transfer};
+
+const ATA_SMART: u8 = 0xB0;
+
+const SMART_READ_DATA: u16 = 0xD0;
+const SMART_READ_THRESHOLDS: u16 = 0xD1;
+const SMART_ATTRIBUTE_AUTOSAVE: u16 = 0xD2;
+const SMART_RETURN_STATUS: u16 = 0xDA;
+const SMART_AUTO_OFFLINE: u16 = 0xDB;
+
+/// Sector count values enabling `SMART ATTRIBUTE AUTOSAVE` and automatic off-line data
+/// collection, zero disables both
+const SMART_AUTOSAVE_ENABLE: u16 = 0xF1;
+const SMART_AUTO_OFFLINE_ENABLE: u16 = 0xF8;
+
+/// Signature placed in LBA mid/high registers of every SMART command
+const SMART_SIGNATURE: u64 = 0xC2_4F00;
+/// LBA mid/high registers returned by `SMART RETURN STATUS` when a threshold is exceeded
+const SMART_THRESHOLD_EXCEEDED: u64 = 0x2C_F400;
+
+/// Offset of the vendor specific attribute table in SMART data
+const SMART_ATTR_OFFSET: usize = 2;
+/// Size of a single attribute entry
+const SMART_ATTR_SIZE: usize = 12;
+/// Number of attribute entries
+const SMART_ATTR_COUNT: usize = 30;
+
+const SMART_ATTR_REALLOCATED_SECTORS: u8 = 5;
+const SMART_ATTR_POWER_ON_HOURS: u8 = 9;
+const SMART_ATTR_PENDING_SECTORS: u8 = 197;
+const SMART_ATTR_UNCORRECTABLE_SECTORS: u8 = 198;
+
+/// Single entry of the vendor specific SMART attribute table.
+///
+/// Meaning of the values (and layout of the raw value) is vendor specific and is left to the
+/// caller.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SmartAttribute {
+    /// Attribute ID
+    pub id: u8,
+    /// Status flags, bit 0 marks pre-failure attributes
+    pub flags: u16,
+    /// Current normalized value
+    pub current: u8,
+    /// Worst normalized value ever seen
+    pub worst: u8,
+    /// 48-bit raw value
+    pub raw: u64,
+}
+
+/// Quick health summary returned by [`Device::health_summary`].
+///
+/// Counts are lower 32 bits of raw values of the attributes used by virtually all vendors, `None`
+/// if drive does not report the attribute.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HealthSummary {
+    /// Overall verdict of `SMART RETURN STATUS`, `false` if any threshold is exceeded, `None` if
+    /// it could not be determined
+    pub passed: Option<bool>,
+    /// Reallocated sector count (attribute 5)
+    pub reallocated: Option<u64>,
+    /// Current pending sector count (attribute 197)
+    pub pending: Option<u64>,
+    /// Offline uncorrectable sector count (attribute 198)
+    pub uncorrectable: Option<u64>,
+}
+
+impl SmartAttribute {
+    /// Decode attribute table of SMART data page, skipping unused (ID 0) entries
+    fn parse_table(data: &[u8; 512]) -> Vec<SmartAttribute> {
+        data[SMART_ATTR_OFFSET..]
+            .chunks_exact(SMART_ATTR_SIZE)
+            .take(SMART_ATTR_COUNT)
+            .filter(|attr| attr[0] != 0)
+            .map(|attr| {
+                let mut raw = [0u8; 8];
+                raw[..6].copy_from_slice(&attr[5..11]);
+
+                SmartAttribute {
+                    id: attr[0],
+                    flags: u16::from_le_bytes([attr[1], attr[2]]),
+                    current: attr[3],
+                    worst: attr[4],
+                    raw: u64::from_le_bytes(raw),
+                }
+            })
+            .collect()
+    }
+}
+
+impl Device {
+    /// Read SMART data page using `SMART READ DATA` (ATA cmd 0xB0, feature 0xD0).
+    ///
+    /// Page starts with the vendor specific attribute table, details are in chapter 7.44.6 of the
+    /// documentation. SMART must be enabled on the drive.
+    pub fn smart_read_data(&mut self) -> io::Result<[u8; 512]> {
+        let mut buffer = [0u8; 512];
+        self.smart_command(SMART_READ_DATA, &mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Read and decode the vendor specific SMART attribute table.
+    pub fn smart_attributes(&mut self) -> io::Result<Vec<SmartAttribute>> {
+        let data = self.smart_read_data()?;
+        Ok(SmartAttribute::parse_table(&data))
+    }
+
+    /// Read SMART attribute failure thresholds using `SMART READ THRESHOLDS` (ATA cmd 0xB0,
+    /// feature 0xD1), returned as `(id, threshold)` pairs.
+    ///
+    /// Page layout mirrors the attribute table, so thresholds can be matched with
+    /// [`Device::smart_attributes`] by ID. This command is obsolete in recent ATA revisions, but
+    /// still widely implemented.
+    pub fn smart_read_thresholds(&mut self) -> io::Result<Vec<(u8, u8)>> {
+        let mut data = [0u8; 512];
+        self.smart_command(SMART_READ_THRESHOLDS, &mut data)?;
+
+        let thresholds = data[SMART_ATTR_OFFSET..]
+            .chunks_exact(SMART_ATTR_SIZE)
+            .take(SMART_ATTR_COUNT)
+            .filter(|entry| entry[0] != 0)
+            .map(|entry| (entry[0], entry[1]))
+            .collect();
+
+        Ok(thresholds)
+    }
+
+    /// Return power-on hours reported in SMART attribute 9 or `None` if drive does not report it.
+    ///
+    /// Lower 32 bits of the raw value are used, as some vendors keep minutes or milliseconds in
+    /// the remaining upper bits.
+    pub fn power_on_hours(&mut self) -> io::Result<Option<u64>> {
+        let hours = self
+            .smart_attributes()?
+            .iter()
+            .find(|attr| attr.id == SMART_ATTR_POWER_ON_HOURS)
+            .map(|attr| attr.raw & 0xFFFF_FFFF);
+
+        Ok(hours)
+    }
+
+    /// Return overall health verdict using `SMART RETURN STATUS` (ATA cmd 0xB0, feature 0xDA):
+    /// `false` if any pre-failure threshold is exceeded, `None` if the drive returned neither
+    /// signature (like when output registers are not available).
+    pub fn smart_return_status(&mut self) -> io::Result<Option<bool>> {
+        let tf = TaskFile {
+            command: ATA_SMART,
+            features: SMART_RETURN_STATUS,
+            lba: SMART_SIGNATURE,
+            ..Default::default()
+        };
+
+        let out = self.retry(|ata| ata.raw_command(&tf, Transfer::NonData))?;
+        Ok(match out.lba & 0xFF_FF00 {
+            SMART_SIGNATURE => Some(true),
+            SMART_THRESHOLD_EXCEEDED => Some(false),
+            _ => None,
+        })
+    }
+
+    /// Combine [`Device::smart_return_status`] and counts of reallocated, pending and
+    /// uncorrectable sectors into a single health summary.
+    pub fn health_summary(&mut self) -> io::Result<HealthSummary> {
+        let passed = self.smart_return_status()?;
+        let attrs = self.smart_attributes()?;
+        let raw = |id| {
+            attrs
+                .iter()
+                .find(|attr| attr.id == id)
+                .map(|attr| attr.raw & 0xFFFF_FFFF)
+        };
+
+        Ok(HealthSummary {
+            passed,
+            reallocated: raw(SMART_ATTR_REALLOCATED_SECTORS),
+            pending: raw(SMART_ATTR_PENDING_SECTORS),
+            uncorrectable: raw(SMART_ATTR_UNCORRECTABLE_SECTORS),
+        })
+    }
+
+    /// Enable or disable automatic saving of attribute values to the media using `SMART ENABLE/
+    /// DISABLE ATTRIBUTE AUTOSAVE` (ATA cmd 0xB0, feature 0xD2), so that counters survive
+    /// power loss.
+    pub fn smart_autosave(&mut self, enable: bool) -> io::Result<()> {
+        let count = if enable { SMART_AUTOSAVE_ENABLE } else { 0 };
+        self.smart_non_data(SMART_ATTRIBUTE_AUTOSAVE, count)
+    }
+
+    /// Enable or disable periodic off-line data collection using `SMART ENABLE/DISABLE
+    /// AUTOMATIC OFF-LINE` (ATA cmd 0xB0, feature 0xDB). This subcommand is vendor specific in
+    /// recent ATA revisions, but still widely implemented.
+    pub fn smart_auto_offline(&mut self, enable: bool) -> io::Result<()> {
+        let count = if enable { SMART_AUTO_OFFLINE_ENABLE } else { 0 };
+        self.smart_non_data(SMART_AUTO_OFFLINE, count)
+    }
+
+    /// Issue a single non-data SMART subcommand with parameter in sector count
+    fn smart_non_data(&mut self, feature: u16, count: u16) -> io::Result<()> {
+        let tf = TaskFile {
+            command: ATA_SMART,
+            features: feature,
+            count,
+            lba: SMART_SIGNATURE,
+            ..Default::default()
+        };
+
+        self.retry(|ata| ata.raw_command(&tf, Transfer::NonData))?;
+        Ok(())
+    }
+
+    /// Issue a single SMART subcommand. Empty buffer means a non-data subcommand, otherwise a
+    /// single sector is read.
+    fn smart_command(&mut self, feature: u16, buffer: &mut [u8]) -> io::Result<()> {
+        let count = if buffer.is_empty() { 0 } else { 1 };
+
+        let tf = TaskFile {
+            command: ATA_SMART,
+            features: feature,
+            count,
+            lba: SMART_SIGNATURE,
+            ..Default::default()
+        };
+
+        self.retry(|ata| {
+            let data = if buffer.is_empty() {
+                Transfer::NonData
+            } else {
+                Transfer::PioIn(buffer)
+            };
+            ata.raw_command(&tf, data)
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_attribute_table() {
+        let mut data = [0u8; 512];
+        data[2..14].copy_from_slice(&[9, 0x32, 0x00, 99, 98, 0x10, 0x27, 0, 0, 0x01, 0x02, 0]);
+        data[26..38].copy_from_slice(&[5, 0x33, 0x00, 100, 100, 0, 0, 0, 0, 0, 0, 0]);
+
+        let attrs = SmartAttribute::parse_table(&data);
+
+        assert_eq!(attrs.len(), 2);
+        assert_eq!(
+            attrs[0],
+            SmartAttribute {
+                id: 9,
+                flags: 0x32,
+                current: 99,
+                worst: 98,
+                raw: 0x0201_0000_2710,
+            }
+        );
+        assert_eq!(attrs[1].id, 5);
+    }
+}
@@ -0,0 +1,119 @@
+//! Streaming feature set (documentation of ATA8-ACS chapter 4.17).
+//!
+//! Streaming commands bound the time a command may take, at the cost of data integrity. They fit
+//! recording of audio/video, where a late sector is worse than a damaged one.
+
+use std::io;
+
+use crate::{os, Device, RawAta, TaskFile, Transfer, ATA_DEV_LBA};
+
+const ATA_READ_STREAM_DMA_EXT: u8 = 0x2A;
+const ATA_WRITE_STREAM_DMA_EXT: u8 = 0x3A;
+
+/// Feature bits of streaming commands: Read/Write Continuous, stream ID mask
+const STREAM_CONTINUOUS: u16 = 1 << 6;
+const STREAM_ID_MASK: u16 = 0x07;
+
+impl Device {
+    /// Read sector(s) from disk using `READ STREAM DMA EXT` (ATA cmd 0x2A, documentation of
+    /// ATA8-ACS chapter 7.33).
+    ///
+    /// `stream_id` (0..7) selects a stream configured by the drive, `time_limit` is the command
+    /// completion time limit in units of stream granularity (identify words 98..99), `0` means
+    /// the default one. If `continuous` is set, the drive completes the command within the limit
+    /// even if some data could not be read correctly. Buffer size **must** be multiple of sector
+    /// size.
+    pub fn read_stream(
+        &mut self,
+        stream_id: u8,
+        time_limit: u8,
+        continuous: bool,
+        sector: u64,
+        buffer: &mut [u8],
+    ) -> io::Result<()> {
+        self.validate_range(sector, (buffer.len() / os::SECTOR_BYTES) as u64)?;
+        self.check_streaming()?;
+        let tf = Self::stream_taskfile(
+            ATA_READ_STREAM_DMA_EXT,
+            stream_id,
+            time_limit,
+            continuous,
+            sector,
+            buffer.len(),
+        );
+
+        self.retry(|ata| ata.raw_command(&tf, Transfer::DmaIn(buffer)))?;
+        Ok(())
+    }
+
+    /// Write sector(s) to disk using `WRITE STREAM DMA EXT` (ATA cmd 0x3A, documentation of
+    /// ATA8-ACS chapter 7.68).
+    ///
+    /// Parameters are the same as of [`Device::read_stream`]. If `continuous` is set, the drive
+    /// completes the command within the limit even if some data could not be written.
+    pub fn write_stream(
+        &mut self,
+        stream_id: u8,
+        time_limit: u8,
+        continuous: bool,
+        sector: u64,
+        buffer: &[u8],
+    ) -> io::Result<()> {
+        self.validate_range(sector, (buffer.len() / os::SECTOR_BYTES) as u64)?;
+        self.check_streaming()?;
+        if self.skip_dry_run(format_args!(
+            "stream write of {} bytes at {}",
+            buffer.len(),
+            sector
+        ))? {
+            return Ok(());
+        }
+        let tf = Self::stream_taskfile(
+            ATA_WRITE_STREAM_DMA_EXT,
+            stream_id,
+            time_limit,
+            continuous,
+            sector,
+            buffer.len(),
+        );
+
+        self.retry(|ata| ata.raw_command(&tf, Transfer::DmaOut(buffer)))?;
+        Ok(())
+    }
+
+    /// Fail if drive is known not to support Streaming feature set
+    fn check_streaming(&self) -> io::Result<()> {
+        match self.ident {
+            Some(id) if !id.supports_streaming() => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Streaming feature set not supported",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Build a streaming transfer command. Time limit goes to the upper byte of features.
+    fn stream_taskfile(
+        command: u8,
+        stream_id: u8,
+        time_limit: u8,
+        continuous: bool,
+        sector: u64,
+        len: usize,
+    ) -> TaskFile {
+        let mut features = (time_limit as u16) << 8 | stream_id as u16 & STREAM_ID_MASK;
+        if continuous {
+            features |= STREAM_CONTINUOUS;
+        }
+
+        TaskFile {
+            command,
+            features,
+            count: (len / os::SECTOR_BYTES) as u16,
+            lba: sector,
+            device: ATA_DEV_LBA,
+            ext: true,
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,47 @@
+//! Classification of the bus the drive is attached with.
+
+use std::io;
+
+use crate::{Device, TransferMode};
+
+/// Bus the drive is attached with, as returned by [`Device::transport`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transport {
+    /// Parallel ATA
+    Pata,
+    /// Serial ATA, including SATA drives attached to SAS controllers
+    Sata,
+    /// USB bridge translating ATA pass-through commands, often rejecting DMA protocol
+    Usb,
+    /// Neither operating system nor the drive told
+    Unknown,
+}
+
+impl Device {
+    /// Classify the bus the drive is attached with.
+    ///
+    /// Operating system is asked first: on Linux the ancestry of the device in `/sys` reveals USB
+    /// bridges, on FreeBSD the transport of the CAM path is queried. If it does not tell, the drive
+    /// is considered SATA if it reports Serial ATA capabilities (see
+    /// [`IdentifyDeviceData::is_sata`](crate::IdentifyDeviceData::is_sata)), PATA otherwise.
+    pub fn transport(&mut self) -> Transport {
+        if let Some(transport) = self.ata.transport(&self.path) {
+            return transport;
+        }
+
+        match self.ident.or_else(|| self.info().ok()) {
+            Some(id) if id.is_sata() => Transport::Sata,
+            Some(_) => Transport::Pata,
+            None => Transport::Unknown,
+        }
+    }
+
+    /// Report the transfer mode currently negotiated by the drive, like `UltraDma(6)`.
+    ///
+    /// Identification is read again, as the mode may change after a bus reset or `SET FEATURES`.
+    /// See [`IdentifyDeviceData::get_transfer_mode`](crate::IdentifyDeviceData::get_transfer_mode)
+    /// for decoding details.
+    pub fn current_transfer_mode(&mut self) -> io::Result<TransferMode> {
+        Ok(self.info()?.get_transfer_mode())
+    }
+}
@@ -0,0 +1,219 @@
+//! TRIM of unused sectors using `DATA SET MANAGEMENT` (documentation chapter 7.5).
+
+use std::io;
+
+use crate::{os, Device, Lba, RawAta, TaskFile, Transfer, ATA_DEV_LBA};
+
+const ATA_DATA_SET_MANAGEMENT: u8 = 0x06;
+
+/// Feature bit of `DATA SET MANAGEMENT` selecting TRIM
+const DSM_TRIM: u16 = 1 << 0;
+
+/// Longest range described by a single LBA range entry
+const DSM_RANGE_MAX_SECTORS: u64 = 0xFFFF;
+
+/// Number of 8-byte LBA range entries in a 512-byte block
+const DSM_ENTRIES_PER_BLOCK: usize = os::SECTOR_BYTES / 8;
+
+impl Device {
+    /// Mark `count` sectors starting at `start` as unused using `DATA SET MANAGEMENT` (ATA cmd
+    /// 0x06) with TRIM bit set.
+    ///
+    /// Range is split into as many commands as needed, each carrying at most
+    /// [`IdentifyDeviceData::get_max_dsm_blocks`](crate::IdentifyDeviceData::get_max_dsm_blocks)
+    /// blocks of range entries. Drives not supporting TRIM (identify word 169 bit 0) are reported
+    /// with an error. **Data in trimmed sectors is lost.**
+    pub fn trim(&mut self, start: impl Into<Lba>, count: u64) -> io::Result<()> {
+        let start = start.into().sector();
+        self.validate_range(start, count)?;
+        self.trim_ranges(&[(start, count)])
+    }
+
+    /// Return discard granularity in bytes enforced by the operating system, `None` if it does
+    /// not support discard on this device or cannot tell.
+    ///
+    /// On Linux it is `discard_granularity` of the block device queue, reported only when
+    /// `discard_max_bytes` is non-zero. Kernel limits may differ from the drive capabilities
+    /// reported by [`IdentifyDeviceData::supports_trim`](crate::IdentifyDeviceData::supports_trim).
+    /// On FreeBSD it is not available.
+    pub fn discard_granularity(&self) -> Option<u64> {
+        os::discard_limits(&self.path).map(|(granularity, _)| granularity)
+    }
+
+    /// Return the largest discard in bytes the operating system accepts for this device, `None`
+    /// like for [`Device::discard_granularity`]. On Linux it is `discard_max_bytes` of the block
+    /// device queue.
+    pub fn discard_max_bytes(&self) -> Option<u64> {
+        os::discard_limits(&self.path).map(|(_, max_bytes)| max_bytes)
+    }
+
+    /// Make `count` sectors starting at `start` read as zeros, using the fastest correct method.
+    ///
+    /// Drives guaranteeing zeros after TRIM (see
+    /// [`IdentifyDeviceData::supports_trim_read_zero`][rzat]) are trimmed, all others are written
+    /// with zero-filled chunks of [`Device::max_transfer_bytes`]. **Data in the range is
+    /// destroyed.**
+    ///
+    /// [rzat]: crate::IdentifyDeviceData::supports_trim_read_zero
+    pub fn zero_range(&mut self, start: u64, count: u64) -> io::Result<()> {
+        self.validate_range(start, count)?;
+
+        if let Some(id) = self.ident {
+            if id.supports_trim_read_zero() && id.supports_lba48() {
+                return self.trim_ranges(&[(start, count)]);
+            }
+        }
+        if self.skip_dry_run(format_args!("zeroing of {} sectors at {}", count, start))? {
+            return Ok(());
+        }
+
+        let buffer = vec![0u8; self.chunk_bytes()];
+        let chunk_sectors = (buffer.len() / os::SECTOR_BYTES) as u64;
+        let mut done = 0;
+
+        while done < count {
+            let sectors = (count - done).min(chunk_sectors);
+            self.write(start + done, &buffer[..sectors as usize * os::SECTOR_BYTES])?;
+            done += sectors;
+        }
+        Ok(())
+    }
+
+    /// Trim all sectors of the drive except `(start, count)` ranges of `used_ranges`, like those
+    /// occupied by a filesystem.
+    ///
+    /// Used ranges may be unordered and overlapping, parts past the capacity are ignored. Free
+    /// ranges are batched like in [`Device::trim`]. **Data outside of used ranges is lost.**
+    pub fn trim_except(&mut self, used_ranges: &[(u64, u64)]) -> io::Result<()> {
+        let capacity = self.capacity_sectors()?;
+        let free = free_ranges(used_ranges, capacity);
+        if free.is_empty() {
+            return Ok(());
+        }
+        self.trim_ranges(&free)
+    }
+
+    /// Trim all `(start, count)` ranges, batched into as few commands as possible
+    pub(crate) fn trim_ranges(&mut self, ranges: &[(u64, u64)]) -> io::Result<()> {
+        let max_blocks = match self.ident {
+            Some(id) if !id.supports_trim() || !id.supports_lba48() => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "TRIM not supported",
+                ))
+            }
+            Some(id) => id.get_max_dsm_blocks().max(1) as usize,
+            None => 1,
+        };
+        let sectors: u64 = ranges.iter().map(|&(_, count)| count).sum();
+        if self.skip_dry_run(format_args!("TRIM of {} sectors", sectors))? {
+            return Ok(());
+        }
+
+        for payload in dsm_payloads(ranges, max_blocks) {
+            let tf = TaskFile {
+                command: ATA_DATA_SET_MANAGEMENT,
+                features: DSM_TRIM,
+                count: (payload.len() / os::SECTOR_BYTES) as u16,
+                device: ATA_DEV_LBA,
+                ext: true,
+                ..Default::default()
+            };
+
+            self.retry(|ata| ata.raw_command(&tf, Transfer::DmaOut(&payload)))?;
+        }
+        Ok(())
+    }
+}
+
+/// Return `(start, count)` ranges of `0..capacity` not covered by any of `used` ranges, in order
+fn free_ranges(used: &[(u64, u64)], capacity: u64) -> Vec<(u64, u64)> {
+    let mut used: Vec<(u64, u64)> = used
+        .iter()
+        .filter(|&&(_, count)| count != 0)
+        .map(|&(start, count)| (start, start.saturating_add(count)))
+        .collect();
+    used.sort_unstable();
+
+    let mut free = Vec::new();
+    let mut next = 0;
+    for (start, end) in used {
+        if start >= capacity {
+            break;
+        }
+        if start > next {
+            free.push((next, start - next));
+        }
+        next = next.max(end);
+    }
+    if next < capacity {
+        free.push((next, capacity - next));
+    }
+    free
+}
+
+/// Encode `(start, count)` ranges as LBA range entries (48-bit LBA, 16-bit count), split into
+/// payloads of at most `max_blocks` blocks. Unused entries of the last block stay zero.
+fn dsm_payloads(ranges: &[(u64, u64)], max_blocks: usize) -> Vec<Vec<u8>> {
+    let entries = ranges.iter().flat_map(|&(start, count)| {
+        (0..count)
+            .step_by(DSM_RANGE_MAX_SECTORS as usize)
+            .map(move |offset| {
+                let len = (count - offset).min(DSM_RANGE_MAX_SECTORS);
+                (start + offset) | len << 48
+            })
+    });
+
+    let per_payload = max_blocks * DSM_ENTRIES_PER_BLOCK;
+    let mut payloads: Vec<Vec<u8>> = Vec::new();
+    for (i, entry) in entries.enumerate() {
+        if i % per_payload == 0 {
+            payloads.push(Vec::new());
+        }
+        let payload = payloads.last_mut().expect("payload started");
+        let offset = i % per_payload * 8;
+        if offset == payload.len() {
+            payload.resize(payload.len() + os::SECTOR_BYTES, 0);
+        }
+        payload[offset..offset + 8].copy_from_slice(&entry.to_le_bytes());
+    }
+    payloads
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    #[test]
+    fn check_free_ranges() {
+        assert_eq!(free_ranges(&[], 100), vec![(0, 100)]);
+        assert_eq!(
+            free_ranges(&[(50, 10), (0, 10), (5, 10), (90, 20), (70, 0)], 100),
+            vec![(15, 35), (60, 30)]
+        );
+        assert_eq!(free_ranges(&[(0, 100)], 100), vec![]);
+        assert_eq!(free_ranges(&[(200, 10)], 100), vec![(0, 100)]);
+    }
+
+    #[test]
+    fn check_dsm_payloads() {
+        let payloads = dsm_payloads(&[(0x1000, 0x10000), (0x20000, 1)], 1);
+        assert_eq!(payloads.len(), 1);
+        assert_eq!(payloads[0].len(), os::SECTOR_BYTES);
+
+        let entry =
+            |i: usize| u64::from_le_bytes(payloads[0][i * 8..i * 8 + 8].try_into().unwrap());
+        assert_eq!(entry(0), 0x1000 | 0xFFFF << 48);
+        assert_eq!(entry(1), 0x10FFF | 1 << 48);
+        assert_eq!(entry(2), 0x20000 | 1 << 48);
+        assert_eq!(entry(3), 0);
+
+        let ranges: Vec<_> = (0..65).map(|i| (i * 2, 1)).collect();
+        let payloads = dsm_payloads(&ranges, 1);
+        assert_eq!(payloads.len(), 2);
+        assert_eq!(payloads[1].len(), os::SECTOR_BYTES);
+
+        assert_eq!(dsm_payloads(&ranges, 2)[0].len(), 2 * os::SECTOR_BYTES);
+    }
+}
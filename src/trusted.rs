@@ -0,0 +1,92 @@
+//! Trusted Computing feature set (documentation chapter 4.21), carrying security protocol
+//! payloads like TCG Opal ones.
+
+use std::io;
+
+use crate::{os, AtaCommand, Device, Direction, Protocol};
+
+const ATA_TRUSTED_RECEIVE: u8 = 0x5C;
+const ATA_TRUSTED_SEND: u8 = 0x5E;
+
+impl Device {
+    /// Send security protocol payload using `TRUSTED SEND` (ATA cmd 0x5E, documentation chapter
+    /// 7.53).
+    ///
+    /// `protocol` is the security protocol (like 0x01 for TCG), `comid` the protocol specific
+    /// field (TCG ComID). Data is padded with zeros to a multiple of sector size. Drives not
+    /// supporting Trusted Computing (see
+    /// [`IdentifyDeviceData::supports_tcg`](crate::IdentifyDeviceData::supports_tcg)) are
+    /// reported with an error. Payloads may change drive state irreversibly (like TCG Revert
+    /// erasing all data), so it is skipped in dry-run mode.
+    pub fn trusted_send(&mut self, protocol: u8, comid: u16, data: &[u8]) -> io::Result<()> {
+        self.check_tcg()?;
+        if self.skip_dry_run(format_args!(
+            "trusted send of {} bytes, protocol {:#04x}, ComID {:#06x}",
+            data.len(),
+            protocol,
+            comid
+        ))? {
+            return Ok(());
+        }
+
+        let sectors = data.len().div_ceil(os::SECTOR_BYTES);
+        let mut buffer = data.to_vec();
+        buffer.resize(sectors * os::SECTOR_BYTES, 0);
+
+        let cmd = Self::trusted_command(ATA_TRUSTED_SEND, protocol, comid, sectors);
+        let cmd = match sectors {
+            0 => cmd,
+            _ => cmd
+                .protocol(Protocol::Pio)
+                .direction(Direction::Out)
+                .data(buffer),
+        };
+
+        self.execute(cmd)?;
+        Ok(())
+    }
+
+    /// Receive security protocol payload of `len` bytes using `TRUSTED RECEIVE` (ATA cmd 0x5C,
+    /// documentation chapter 7.51).
+    ///
+    /// Parameters are the same as of [`Device::trusted_send`]. Whole sectors are transferred,
+    /// excess bytes are dropped. With `protocol` 0x00 and `comid` 0x0000 it lists security
+    /// protocols supported by the drive.
+    pub fn trusted_receive(&mut self, protocol: u8, comid: u16, len: usize) -> io::Result<Vec<u8>> {
+        self.check_tcg()?;
+
+        let sectors = len.div_ceil(os::SECTOR_BYTES);
+        let cmd = Self::trusted_command(ATA_TRUSTED_RECEIVE, protocol, comid, sectors);
+        let cmd = match sectors {
+            0 => cmd,
+            _ => cmd
+                .protocol(Protocol::Pio)
+                .direction(Direction::In)
+                .data(vec![0; sectors * os::SECTOR_BYTES]),
+        };
+
+        let mut data = self.execute(cmd)?.data;
+        data.truncate(len);
+        Ok(data)
+    }
+
+    /// Fail if drive is known not to support Trusted Computing feature set
+    fn check_tcg(&self) -> io::Result<()> {
+        match self.ident {
+            Some(id) if !id.supports_tcg() => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Trusted Computing feature set not supported",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Build a non-data trusted command. Transfer length in sectors is split between sector
+    /// count (bits 7..0) and LBA low (bits 15..8), protocol specific field goes to LBA mid/high.
+    fn trusted_command(command: u8, protocol: u8, comid: u16, sectors: usize) -> AtaCommand {
+        AtaCommand::new(command)
+            .feature(protocol as u16)
+            .count((sectors & 0xFF) as u16)
+            .lba((sectors >> 8 & 0xFF) as u64 | (comid as u64) << 8)
+    }
+}
@@ -0,0 +1,658 @@
+//! Asynchronous transfers on Linux, submitting commands to the `sg` driver through io_uring.
+//!
+//! A command is sent by writing its `sg` header to the device node and received by reading the
+//! header back. Both are queued as linked io_uring requests, so no thread blocks while the
+//! command is in flight. Completions of all commands of a device are reaped by a single
+//! background thread, waking the futures awaiting them.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    io, mem,
+    os::raw::{c_int, c_long, c_uint, c_void},
+    path::{Path, PathBuf},
+    pin::Pin,
+    ptr,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+    thread::{self, JoinHandle},
+};
+
+use crate::{os, AtaTaskfileOut, Device, IdentifyDeviceData};
+
+/// Number of submission queue entries, each command takes two
+const RING_ENTRIES: u32 = 64;
+
+const IORING_OFF_SQ_RING: i64 = 0;
+const IORING_OFF_CQ_RING: i64 = 0x0800_0000;
+const IORING_OFF_SQES: i64 = 0x1000_0000;
+
+const IORING_OP_NOP: u8 = 0;
+const IORING_OP_READ: u8 = 22;
+const IORING_OP_WRITE: u8 = 23;
+
+const IOSQE_IO_LINK: u8 = 1 << 2;
+const IORING_ENTER_GETEVENTS: c_uint = 1 << 0;
+
+/// User data of the request stopping the reaper thread
+const SHUTDOWN: u64 = u64::MAX;
+
+/// User data bit marking the request sending a command, clear for the one receiving it
+const SEND: u64 = 1;
+
+#[repr(C)]
+#[derive(Default)]
+struct SqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqringOffsets,
+    cq_off: CqringOffsets,
+}
+
+/// Submission queue entry
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct Sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    rw_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    splice_fd_in: i32,
+    addr3: u64,
+    pad: u64,
+}
+
+/// Completion queue entry
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+/// Memory mapping of a part of the ring
+struct Mapping {
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl Mapping {
+    fn new(fd: c_int, len: usize, offset: i64) -> io::Result<Self> {
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_POPULATE,
+                fd,
+                offset,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mapping {
+            ptr: ptr as *mut u8,
+            len,
+        })
+    }
+
+    /// Pointer to a field at `offset` bytes
+    fn at<T>(&self, offset: u32) -> *mut T {
+        unsafe { self.ptr.add(offset as usize) as *mut T }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr as *mut c_void, self.len) };
+    }
+}
+
+/// io_uring instance. Submissions are serialized by a lock, completions are reaped by a single
+/// thread.
+struct Ring {
+    fd: c_int,
+    sq: Mapping,
+    cq: Mapping,
+    sqes: Mapping,
+    params: Params,
+    submit: Mutex<()>,
+}
+
+// Shared queues are only accessed through atomics, under the submission lock or by the single
+// reaping thread
+unsafe impl Send for Ring {}
+unsafe impl Sync for Ring {}
+
+impl Ring {
+    fn new(entries: u32) -> io::Result<Self> {
+        let mut params = Params::default();
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_io_uring_setup,
+                entries as c_long,
+                &mut params as *mut Params,
+            )
+        } as c_int;
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let sq_len = params.sq_off.array as usize + params.sq_entries as usize * 4;
+        let cq_len =
+            params.cq_off.cqes as usize + params.cq_entries as usize * mem::size_of::<Cqe>();
+        let sqes_len = params.sq_entries as usize * mem::size_of::<Sqe>();
+
+        let mappings = Mapping::new(fd, sq_len, IORING_OFF_SQ_RING).and_then(|sq| {
+            let cq = Mapping::new(fd, cq_len, IORING_OFF_CQ_RING)?;
+            let sqes = Mapping::new(fd, sqes_len, IORING_OFF_SQES)?;
+            Ok((sq, cq, sqes))
+        });
+        match mappings {
+            Ok((sq, cq, sqes)) => Ok(Ring {
+                fd,
+                sq,
+                cq,
+                sqes,
+                params,
+                submit: Mutex::new(()),
+            }),
+            Err(err) => {
+                unsafe { libc::close(fd) };
+                Err(err)
+            }
+        }
+    }
+
+    /// Queue `entries` and submit them to the kernel at once
+    fn submit(&self, entries: &[Sqe]) -> io::Result<()> {
+        let _guard = self.submit.lock().unwrap_or_else(|e| e.into_inner());
+        let off = &self.params.sq_off;
+
+        let head = unsafe { &*self.sq.at::<AtomicU32>(off.head) }.load(Ordering::Acquire);
+        let tail = unsafe { &*self.sq.at::<AtomicU32>(off.tail) };
+        let mask = unsafe { *self.sq.at::<u32>(off.ring_mask) };
+        let array = self.sq.at::<u32>(off.array);
+
+        let mut pos = tail.load(Ordering::Relaxed);
+        if pos.wrapping_sub(head) as usize + entries.len() > self.params.sq_entries as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "io_uring submission queue full",
+            ));
+        }
+
+        for entry in entries {
+            let index = pos & mask;
+            unsafe {
+                *(self.sqes.ptr as *mut Sqe).add(index as usize) = *entry;
+                *array.add(index as usize) = index;
+            }
+            pos = pos.wrapping_add(1);
+        }
+        tail.store(pos, Ordering::Release);
+
+        let ans = unsafe {
+            libc::syscall(
+                libc::SYS_io_uring_enter,
+                self.fd,
+                entries.len() as c_uint,
+                0 as c_uint,
+                0 as c_uint,
+                ptr::null::<c_void>(),
+                0usize,
+            )
+        };
+        if ans < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Block until at least one completion is available
+    fn wait(&self) -> io::Result<()> {
+        let ans = unsafe {
+            libc::syscall(
+                libc::SYS_io_uring_enter,
+                self.fd,
+                0 as c_uint,
+                1 as c_uint,
+                IORING_ENTER_GETEVENTS,
+                ptr::null::<c_void>(),
+                0usize,
+            )
+        };
+        if ans < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Pass all available completions to `f`. Only one thread may reap.
+    fn reap<F: FnMut(Cqe)>(&self, mut f: F) {
+        let off = &self.params.cq_off;
+
+        let head = unsafe { &*self.cq.at::<AtomicU32>(off.head) };
+        let tail = unsafe { &*self.cq.at::<AtomicU32>(off.tail) }.load(Ordering::Acquire);
+        let mask = unsafe { *self.cq.at::<u32>(off.ring_mask) };
+        let cqes = self.cq.at::<Cqe>(off.cqes);
+
+        let mut pos = head.load(Ordering::Relaxed);
+        while pos != tail {
+            f(unsafe { *cqes.add((pos & mask) as usize) });
+            pos = pos.wrapping_add(1);
+        }
+        head.store(pos, Ordering::Release);
+    }
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+/// Command in flight
+struct Op {
+    request: Box<os::SgRequest>,
+    waker: Option<Waker>,
+    result: Option<io::Result<AtaTaskfileOut>>,
+    /// Error of sending the command, reported instead of cancellation of receiving it
+    send_error: Option<io::Error>,
+    /// Set when the awaiting future was dropped, the command is discarded on completion
+    abandoned: bool,
+}
+
+/// State shared by [`AsyncDevice`] and its reaper thread
+struct Shared {
+    fd: c_int,
+    ring: Ring,
+    ops: Mutex<HashMap<u64, Op>>,
+    next_id: AtomicU64,
+    closing: AtomicBool,
+}
+
+impl Shared {
+    /// Record completion of a request and wake the future awaiting the command
+    fn complete(&self, cqe: Cqe) {
+        let id = cqe.user_data >> 1;
+        let mut ops = self.ops.lock().unwrap_or_else(|e| e.into_inner());
+        let op = match ops.get_mut(&id) {
+            Some(op) => op,
+            None => return,
+        };
+
+        if cqe.user_data & SEND != 0 {
+            if cqe.res < 0 {
+                op.send_error = Some(io::Error::from_raw_os_error(-cqe.res));
+            }
+            return;
+        }
+
+        op.result = Some(match cqe.res {
+            res if res < 0 => Err(op
+                .send_error
+                .take()
+                .unwrap_or_else(|| io::Error::from_raw_os_error(-res))),
+            _ => op.request.result(),
+        });
+
+        if op.abandoned {
+            ops.remove(&id);
+        } else if let Some(waker) = op.waker.take() {
+            drop(ops);
+            waker.wake();
+        }
+    }
+
+    /// Reap completions until closed and no command is in flight
+    fn run(&self) {
+        loop {
+            if let Err(err) = self.ring.wait() {
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                log::error!("io_uring wait failed: {}", err);
+                return;
+            }
+
+            self.ring.reap(|cqe| {
+                if cqe.user_data != SHUTDOWN {
+                    self.complete(cqe);
+                }
+            });
+
+            let idle = self
+                .ops
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .is_empty();
+            if idle && self.closing.load(Ordering::Acquire) {
+                return;
+            }
+        }
+    }
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+/// Future of a command submitted by [`AsyncDevice`]
+struct Completion<'a> {
+    shared: &'a Shared,
+    id: u64,
+    done: bool,
+}
+
+impl Future for Completion<'_> {
+    type Output = io::Result<Vec<u8>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut ops = self.shared.ops.lock().unwrap_or_else(|e| e.into_inner());
+        let op = match ops.get_mut(&self.id) {
+            Some(op) => op,
+            None => return Poll::Ready(Err(io::Error::other("command already completed"))),
+        };
+
+        match op.result.take() {
+            Some(result) => {
+                let op = ops.remove(&self.id).expect("command in flight");
+                drop(ops);
+                self.done = true;
+                Poll::Ready(result.map(|_| op.request.into_data()))
+            }
+            None => {
+                op.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for Completion<'_> {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+
+        // Kernel still owns the buffers until the command completes
+        let mut ops = self.shared.ops.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(op) = ops.get_mut(&self.id) {
+            match op.result {
+                Some(_) => {
+                    ops.remove(&self.id);
+                }
+                None => op.abandoned = true,
+            }
+        }
+    }
+}
+
+/// Attached ATA device transferring sectors asynchronously, through io_uring.
+///
+/// It needs an `sg` node (like `/dev/sg0`), block devices are rejected. Any number of transfers
+/// may be in flight at once, each awaited by its own future, while a single background thread
+/// reaps their completions. Buffers are owned by transfers until they complete and returned
+/// afterwards, so a dropped future never leaves the kernel writing to freed memory.
+///
+/// Only reads and writes are asynchronous, use [`Device`] for everything else.
+pub struct AsyncDevice {
+    shared: Arc<Shared>,
+    reaper: Option<JoinHandle<()>>,
+    path: PathBuf,
+    ident: Option<IdentifyDeviceData>,
+}
+
+impl AsyncDevice {
+    /// Open `sg` node pointed by a specific path.
+    ///
+    /// Drive is identified with a regular [`Device`] first, the capacity it reports is used to
+    /// validate transfers.
+    pub fn open<P: AsRef<Path>>(dev: P) -> io::Result<Self> {
+        let path = dev.as_ref().to_path_buf();
+        let ident = Device::open(&path)?.ident;
+        let fd = os::open_sg_async(&path)?;
+
+        let ring = match Ring::new(RING_ENTRIES) {
+            Ok(ring) => ring,
+            Err(err) => {
+                unsafe { libc::close(fd) };
+                return Err(err);
+            }
+        };
+        let shared = Arc::new(Shared {
+            fd,
+            ring,
+            ops: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+            closing: AtomicBool::new(false),
+        });
+
+        let reaper = {
+            let shared = Arc::clone(&shared);
+            thread::Builder::new()
+                .name("rawata-uring".into())
+                .spawn(move || shared.run())?
+        };
+
+        Ok(AsyncDevice {
+            shared,
+            reaper: Some(reaper),
+            path,
+            ident,
+        })
+    }
+
+    /// Read sectors starting at `sector` into `buffer` with `READ DMA EXT`, returning it filled.
+    ///
+    /// Buffer size **must** be multiple of sector size.
+    pub async fn read(&self, sector: u64, buffer: Vec<u8>) -> io::Result<Vec<u8>> {
+        self.check(sector, buffer.len())?;
+        self.submit(sector, buffer, false)?.await
+    }
+
+    /// Write sectors starting at `sector` from `buffer` with `WRITE DMA EXT`, returning it for
+    /// reuse.
+    ///
+    /// Buffer size **must** be multiple of sector size. **It bypasses all protections and
+    /// caches/buffers.**
+    pub async fn write(&self, sector: u64, buffer: Vec<u8>) -> io::Result<Vec<u8>> {
+        self.check(sector, buffer.len())?;
+        self.submit(sector, buffer, true)?.await
+    }
+
+    /// Return the path the device was opened with
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Validate a transfer of `len` bytes against transfer limits and drive capacity
+    fn check(&self, sector: u64, len: usize) -> io::Result<()> {
+        if len == 0 || !len.is_multiple_of(os::SECTOR_BYTES) || len > os::MAX_TRANSFER_BYTES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid transfer of {} bytes", len),
+            ));
+        }
+
+        let id = match self.ident {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+        if !id.supports_lba48() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "asynchronous transfers require 48-bit addressing",
+            ));
+        }
+
+        let count = (len / os::SECTOR_BYTES) as u64;
+        match sector.checked_add(count) {
+            Some(end) if end <= id.addressable_sectors() => Ok(()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "sector {} beyond capacity {}",
+                    sector.saturating_add(count - 1),
+                    id.addressable_sectors()
+                ),
+            )),
+        }
+    }
+
+    /// Queue sending and receiving a command, returning the future of its completion
+    fn submit(&self, sector: u64, buffer: Vec<u8>, write: bool) -> io::Result<Completion<'_>> {
+        let shared = &*self.shared;
+        let id = shared.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut request = os::SgRequest::transfer(sector, buffer, write, id as u32);
+        let (addr, len) = request.header();
+
+        let send = Sqe {
+            opcode: IORING_OP_WRITE,
+            flags: IOSQE_IO_LINK,
+            fd: shared.fd,
+            addr: addr as u64,
+            len: len as u32,
+            user_data: id << 1 | SEND,
+            ..Default::default()
+        };
+        let receive = Sqe {
+            opcode: IORING_OP_READ,
+            flags: 0,
+            user_data: id << 1,
+            ..send
+        };
+
+        shared.ops.lock().unwrap_or_else(|e| e.into_inner()).insert(
+            id,
+            Op {
+                request,
+                waker: None,
+                result: None,
+                send_error: None,
+                abandoned: false,
+            },
+        );
+
+        if let Err(err) = shared.ring.submit(&[send, receive]) {
+            shared
+                .ops
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&id);
+            return Err(err);
+        }
+
+        Ok(Completion {
+            shared,
+            id,
+            done: false,
+        })
+    }
+}
+
+impl Drop for AsyncDevice {
+    /// Wait for commands still in flight and stop the reaper thread
+    fn drop(&mut self) {
+        self.shared.closing.store(true, Ordering::Release);
+        let nop = Sqe {
+            opcode: IORING_OP_NOP,
+            user_data: SHUTDOWN,
+            ..Default::default()
+        };
+
+        if self.shared.ring.submit(&[nop]).is_ok() {
+            if let Some(reaper) = self.reaper.take() {
+                let _ = reaper.join();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_abi_sizes() {
+        assert_eq!(mem::size_of::<Sqe>(), 64);
+        assert_eq!(mem::size_of::<Cqe>(), 16);
+        assert_eq!(mem::size_of::<Params>(), 120);
+    }
+
+    #[test]
+    fn check_ring_nop() {
+        let ring = Ring::new(4).expect("io_uring not available");
+        let nop = Sqe {
+            opcode: IORING_OP_NOP,
+            user_data: 42,
+            ..Default::default()
+        };
+
+        ring.submit(&[
+            nop,
+            Sqe {
+                user_data: 43,
+                ..nop
+            },
+        ])
+        .unwrap();
+        let mut seen = Vec::new();
+        while seen.len() < 2 {
+            ring.wait().unwrap();
+            ring.reap(|cqe| seen.push((cqe.user_data, cqe.res)));
+        }
+
+        seen.sort_unstable();
+        assert_eq!(seen, vec![(42, 0), (43, 0)]);
+    }
+}
@@ -0,0 +1,227 @@
+//! Erasing the whole drive with the strongest method it supports, and verifying the result.
+
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use crate::{os, AtaFeature, Device, IdentifyDeviceData};
+
+/// Password set temporarily for `SECURITY ERASE UNIT`. If the erase fails, it is removed again;
+/// if that fails too or the erase is interrupted, the drive is left locked with it.
+pub const WIPE_PASSWORD: [u8; 32] = *b"pakr-rawata secure wipe         ";
+
+/// Number of sectors spread evenly over the drive checked after wiping, besides the last one
+const WIPE_VERIFY_SAMPLES: u64 = 256;
+
+/// Method of erasing the whole drive used by [`Device::secure_wipe`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WipeMethod {
+    /// Strongest method the drive supports, in the order of the other variants
+    Strongest,
+    /// `SANITIZE` `CRYPTO SCRAMBLE EXT`, changing the encryption key of the media
+    CryptoScramble,
+    /// `SANITIZE` `BLOCK ERASE EXT`, erasing all user data areas
+    BlockErase,
+    /// `SECURITY ERASE UNIT`, enhanced if `enhanced` (covering reallocated sectors too)
+    SecurityErase {
+        /// Use the enhanced erase
+        enhanced: bool,
+    },
+    /// Host writes over all addressable sectors, alternating 0xFF and 0x00 fill so that the last
+    /// of `passes` writes zeros
+    Overwrite {
+        /// Number of passes, at least 1
+        passes: u32,
+    },
+}
+
+impl WipeMethod {
+    /// Pick the strongest method supported by the drive identified by `id`.
+    ///
+    /// Sanitize operations are preferred, as they cover sectors not addressable by the host.
+    /// Security erase is skipped on frozen drives. Overwrite works on every drive.
+    pub fn strongest_for(id: &IdentifyDeviceData) -> Self {
+        if id.supports_crypto_scramble() {
+            WipeMethod::CryptoScramble
+        } else if id.supports_block_erase() {
+            WipeMethod::BlockErase
+        } else if id.supports(AtaFeature::Security) && !id.is_security_frozen() {
+            WipeMethod::SecurityErase {
+                enhanced: id.supports_enhanced_erase(),
+            }
+        } else {
+            WipeMethod::Overwrite { passes: 1 }
+        }
+    }
+}
+
+/// Record of a [`Device::secure_wipe`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WipeReport {
+    /// Method actually used, never [`WipeMethod::Strongest`]
+    pub method: WipeMethod,
+    /// Time the erase itself took, excluding verification
+    pub duration: Duration,
+    /// Number of sampled sectors read back
+    pub sectors_verified: u64,
+    /// Sectors which failed to be overwritten, could not be read back, or still hold the data
+    /// read before the wipe
+    pub failed_sectors: Vec<u64>,
+}
+
+impl Device {
+    /// Erase the whole drive with `method` and verify the result by sampling sectors.
+    ///
+    /// [`WipeMethod::Strongest`] picks the method with [`WipeMethod::strongest_for`]. Security
+    /// erase sets [`WIPE_PASSWORD`] as the user password first; a successful erase clears it, a
+    /// failed one is followed by `SECURITY DISABLE PASSWORD`.
+    ///
+    /// Sectors spread evenly over the drive, and the last one, are read before the wipe and
+    /// again after it. A sample is considered wiped if its content changed or it is filled with
+    /// a single byte value, like zeros left by an erase. Samples failing the check are listed in
+    /// [`WipeReport::failed_sectors`], it is up to the caller to judge them. In dry-run mode
+    /// nothing is written and an empty report is returned. **All data on the drive is
+    /// destroyed.**
+    pub fn secure_wipe(&mut self, method: WipeMethod) -> io::Result<WipeReport> {
+        let id = self.info()?;
+        self.ident = Some(id);
+        let method = match method {
+            WipeMethod::Strongest => WipeMethod::strongest_for(&id),
+            method => method,
+        };
+        if let WipeMethod::Overwrite { passes: 0 } = method {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "overwrite needs at least one pass",
+            ));
+        }
+
+        let mut report = WipeReport {
+            method,
+            duration: Duration::ZERO,
+            sectors_verified: 0,
+            failed_sectors: Vec::new(),
+        };
+        if self.skip_dry_run(format_args!("secure wipe ({:?})", method))? {
+            return Ok(report);
+        }
+
+        let samples = sample_sectors(id.addressable_sectors());
+        let before: Vec<_> = samples.iter().map(|&lba| self.read_sample(lba)).collect();
+
+        let started = Instant::now();
+        match method {
+            WipeMethod::CryptoScramble => self.sanitize_crypto_scramble()?,
+            WipeMethod::BlockErase => self.sanitize_block_erase()?,
+            WipeMethod::SecurityErase { enhanced } => {
+                self.security_set_password(&WIPE_PASSWORD)?;
+                if let Err(err) = self.secure_erase(&WIPE_PASSWORD, enhanced) {
+                    // Do not leave the drive to come up locked after a power cycle
+                    if let Err(disable) = self.security_disable_password(&WIPE_PASSWORD) {
+                        log::warn!(
+                            "{}: cannot remove wipe password: {}",
+                            self.path.display(),
+                            disable
+                        );
+                    }
+                    return Err(err);
+                }
+            }
+            WipeMethod::Overwrite { passes } => {
+                let capacity = id.addressable_sectors();
+                for pass in 0..passes {
+                    let fill = if (passes - 1 - pass) % 2 == 1 {
+                        0xFF
+                    } else {
+                        0x00
+                    };
+                    let bad = self.write_pattern(0, capacity, &[fill])?;
+                    report.failed_sectors.extend(bad);
+                }
+            }
+            WipeMethod::Strongest => unreachable!("resolved above"),
+        }
+        report.duration = started.elapsed();
+        self.ident = self.info().ok();
+
+        for (&lba, before) in samples.iter().zip(before) {
+            report.sectors_verified += 1;
+            let wiped = match self.read_sample(lba) {
+                Some(after) => is_wiped(before.as_ref(), &after),
+                None => false,
+            };
+            if !wiped {
+                report.failed_sectors.push(lba);
+            }
+        }
+        report.failed_sectors.sort_unstable();
+        report.failed_sectors.dedup();
+
+        Ok(report)
+    }
+
+    /// Read a single sector, `None` if it is unreadable
+    fn read_sample(&mut self, lba: u64) -> Option<[u8; os::SECTOR_BYTES]> {
+        let mut sector = [0u8; os::SECTOR_BYTES];
+        self.read(lba, &mut sector).ok().map(|_| sector)
+    }
+}
+
+/// Sectors checked on a drive of `capacity` sectors: spread evenly and the last one
+fn sample_sectors(capacity: u64) -> Vec<u64> {
+    if capacity == 0 {
+        return Vec::new();
+    }
+
+    let mut samples: Vec<u64> = (0..WIPE_VERIFY_SAMPLES.min(capacity))
+        .map(|i| i * capacity / WIPE_VERIFY_SAMPLES.min(capacity))
+        .collect();
+    samples.push(capacity - 1);
+    samples.dedup();
+    samples
+}
+
+/// Check if a sector reading `after` a wipe no longer holds its content from `before` it
+fn is_wiped(before: Option<&[u8; os::SECTOR_BYTES]>, after: &[u8; os::SECTOR_BYTES]) -> bool {
+    after.iter().all(|&byte| byte == after[0]) || before.is_some_and(|before| before != after)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_wipe_samples() {
+        assert!(sample_sectors(0).is_empty());
+        assert_eq!(sample_sectors(3), [0, 1, 2]);
+
+        let samples = sample_sectors(1 << 20);
+        assert_eq!(samples.len(), WIPE_VERIFY_SAMPLES as usize + 1);
+        assert_eq!(samples[1], 4096);
+        assert_eq!(samples.last(), Some(&((1 << 20) - 1)));
+    }
+
+    #[test]
+    fn check_wiped_sector() {
+        let data = [0x5Au8; os::SECTOR_BYTES];
+        let mut text = [0u8; os::SECTOR_BYTES];
+        text[..5].copy_from_slice(b"hello");
+
+        assert!(is_wiped(Some(&text), &[0u8; os::SECTOR_BYTES]));
+        assert!(is_wiped(Some(&text), &data));
+        assert!(!is_wiped(Some(&text), &text));
+        assert!(!is_wiped(None, &text));
+
+        let mut strongest = [0u16; 256];
+        strongest[59] = 0xB000;
+        assert_eq!(
+            WipeMethod::strongest_for(&IdentifyDeviceData(strongest)),
+            WipeMethod::CryptoScramble
+        );
+        assert_eq!(
+            WipeMethod::strongest_for(&IdentifyDeviceData([0u16; 256])),
+            WipeMethod::Overwrite { passes: 1 }
+        );
+    }
+}
@@ -0,0 +1,109 @@
+//! Byte stream writer over raw sectors, taking care of the final partial sector.
+
+use std::io;
+
+use crate::{os, Device, Lba, STREAM_CHUNK_SECTORS};
+
+/// Number of bytes buffered before whole sectors are written out
+const WRITER_CHUNK_BYTES: usize = STREAM_CHUNK_SECTORS as usize * os::SECTOR_BYTES;
+
+/// [`Device`] wrapper writing a byte stream of any length to consecutive sectors.
+///
+/// Data is buffered and written with [`Device::write`] in whole sectors, in chunks of
+/// [`STREAM_CHUNK_SECTORS`]. When the stream does not end on a sector boundary, the final partial
+/// sector is written by [`SectorPaddingWriter::finish`]: read, modified and written back, so the
+/// rest of the sector keeps its content, or padded with zeros if set with
+/// [`SectorPaddingWriter::set_zero_padding`].
+///
+/// [`io::Write::flush`] writes whole sectors only and flushes the drive, the partial sector stays
+/// buffered. Data not written by `finish` is lost when the writer is dropped.
+pub struct SectorPaddingWriter {
+    device: Device,
+    sector: u64,
+    buffer: Vec<u8>,
+    zero_padding: bool,
+}
+
+impl SectorPaddingWriter {
+    /// Wrap `device`, writing the stream from sector `start` on
+    pub fn new(device: Device, start: impl Into<Lba>) -> Self {
+        SectorPaddingWriter {
+            device,
+            sector: start.into().sector(),
+            buffer: Vec::with_capacity(WRITER_CHUNK_BYTES),
+            zero_padding: false,
+        }
+    }
+
+    /// Pad the final partial sector with zeros instead of preserving the rest of its content.
+    /// It saves reading the sector, which may be unreadable or not worth keeping.
+    pub fn set_zero_padding(&mut self, zero_padding: bool) {
+        self.zero_padding = zero_padding;
+    }
+
+    /// Return the sector the next whole sector of buffered data goes to
+    pub fn next_sector(&self) -> Lba {
+        Lba(self.sector)
+    }
+
+    /// Write all buffered data including the final partial sector, then flush the drive.
+    ///
+    /// Writing may continue afterwards, from the sector following the padded one.
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.write_sectors()?;
+
+        if !self.buffer.is_empty() {
+            let mut tail = [0u8; os::SECTOR_BYTES];
+            if !self.zero_padding {
+                self.device.read(self.sector, &mut tail)?;
+            }
+            tail[..self.buffer.len()].copy_from_slice(&self.buffer);
+            self.device.write(self.sector, &tail)?;
+            self.sector += 1;
+            self.buffer.clear();
+        }
+
+        self.device.flush()
+    }
+
+    /// Return the wrapped device
+    pub fn get_ref(&self) -> &Device {
+        &self.device
+    }
+
+    /// Finish the stream with [`SectorPaddingWriter::finish`] and unwrap the device
+    pub fn into_inner(mut self) -> io::Result<Device> {
+        self.finish()?;
+        Ok(self.device)
+    }
+
+    /// Write whole sectors of buffered data, keeping the partial sector buffered
+    fn write_sectors(&mut self) -> io::Result<()> {
+        let whole = self.buffer.len() - self.buffer.len() % os::SECTOR_BYTES;
+        if whole == 0 {
+            return Ok(());
+        }
+
+        self.device.write(self.sector, &self.buffer[..whole])?;
+        self.sector += (whole / os::SECTOR_BYTES) as u64;
+        self.buffer.drain(..whole);
+        Ok(())
+    }
+}
+
+impl io::Write for SectorPaddingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.buffer.len() == WRITER_CHUNK_BYTES {
+            self.write_sectors()?;
+        }
+
+        let len = buf.len().min(WRITER_CHUNK_BYTES - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_sectors()?;
+        self.device.flush()
+    }
+}
@@ -0,0 +1,232 @@
+//! Zoned ATA Commands (ZAC) for host-aware and host-managed SMR drives, described in the ZAC
+//! standard (INCITS 537).
+
+use std::io;
+
+use crate::{os, Device, RawAta, TaskFile, Transfer, ATA_DEV_LBA};
+
+const ATA_ZAC_MANAGEMENT_IN: u8 = 0x4A;
+
+const ATA_ZAC_MANAGEMENT_OUT: u8 = 0x9F;
+
+const ZAC_REPORT_ZONES_EXT: u16 = 0x00;
+const ZAC_CLOSE_ZONE_EXT: u16 = 0x01;
+const ZAC_FINISH_ZONE_EXT: u16 = 0x02;
+const ZAC_OPEN_ZONE_EXT: u16 = 0x03;
+const ZAC_RESET_WRITE_POINTER_EXT: u16 = 0x04;
+
+/// Apply zone management action to all zones instead of a single one
+const ZAC_ALL: u16 = 1 << 8;
+
+/// Number of pages read by a single `REPORT ZONES EXT`, first one holds the header and 7 zones,
+/// every next one holds 8 zones
+const REPORT_ZONES_PAGES: usize = 64;
+const ZONE_DESCRIPTOR_BYTES: usize = 64;
+
+/// Type of a zone
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ZoneType {
+    /// Conventional zone, can be written randomly
+    Conventional,
+    /// Sequential write required zone (host-managed)
+    SequentialWriteRequired,
+    /// Sequential write preferred zone (host-aware)
+    SequentialWritePreferred,
+    /// Reserved or vendor specific type
+    Other(u8),
+}
+
+/// Condition of a zone
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ZoneCondition {
+    /// Zone has no write pointer (conventional zone)
+    NotWritePointer,
+    /// Zone is empty
+    Empty,
+    /// Zone was opened implicitly by a write
+    ImplicitlyOpened,
+    /// Zone was opened explicitly
+    ExplicitlyOpened,
+    /// Zone is closed
+    Closed,
+    /// Zone is read-only
+    ReadOnly,
+    /// Zone is full
+    Full,
+    /// Zone is offline
+    Offline,
+    /// Reserved condition
+    Other(u8),
+}
+
+/// Single zone descriptor returned by [`Device::report_zones`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Zone {
+    /// Type of zone
+    pub zone_type: ZoneType,
+    /// Condition of zone
+    pub condition: ZoneCondition,
+    /// First sector of zone
+    pub start: u64,
+    /// Length of zone in sectors
+    pub length: u64,
+    /// Sector to be written next, meaningless for conventional zones
+    pub write_pointer: u64,
+}
+
+impl Zone {
+    /// Decode a single 64-byte zone descriptor
+    fn parse(desc: &[u8]) -> Zone {
+        let le64 = |offset: usize| {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&desc[offset..offset + 8]);
+            u64::from_le_bytes(bytes)
+        };
+
+        let zone_type = match desc[0] & 0x0F {
+            0x1 => ZoneType::Conventional,
+            0x2 => ZoneType::SequentialWriteRequired,
+            0x3 => ZoneType::SequentialWritePreferred,
+            other => ZoneType::Other(other),
+        };
+
+        let condition = match desc[1] >> 4 {
+            0x0 => ZoneCondition::NotWritePointer,
+            0x1 => ZoneCondition::Empty,
+            0x2 => ZoneCondition::ImplicitlyOpened,
+            0x3 => ZoneCondition::ExplicitlyOpened,
+            0x4 => ZoneCondition::Closed,
+            0xD => ZoneCondition::ReadOnly,
+            0xE => ZoneCondition::Full,
+            0xF => ZoneCondition::Offline,
+            other => ZoneCondition::Other(other),
+        };
+
+        Zone {
+            zone_type,
+            condition,
+            length: le64(8),
+            start: le64(16),
+            write_pointer: le64(24),
+        }
+    }
+}
+
+impl Device {
+    /// Report all zones starting with the one containing `start_lba`, using `REPORT ZONES EXT`
+    /// (ATA cmd 0x4A, action 0x00).
+    ///
+    /// Zones are fetched in batches, so it may issue multiple commands for drives with many
+    /// zones.
+    pub fn report_zones(&mut self, start_lba: u64) -> io::Result<Vec<Zone>> {
+        let mut zones = Vec::new();
+        let mut buffer = vec![0u8; REPORT_ZONES_PAGES * os::SECTOR_BYTES];
+        let mut locator = start_lba;
+
+        loop {
+            let tf = TaskFile {
+                command: ATA_ZAC_MANAGEMENT_IN,
+                features: ZAC_REPORT_ZONES_EXT,
+                count: REPORT_ZONES_PAGES as u16,
+                lba: locator,
+                device: ATA_DEV_LBA,
+                ext: true,
+                ..Default::default()
+            };
+            self.retry(|ata| ata.raw_command(&tf, Transfer::DmaIn(&mut buffer)))?;
+
+            // Header holds length of the whole list, which may not fit into the buffer
+            let list_len = u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
+            let available = list_len as usize / ZONE_DESCRIPTOR_BYTES;
+
+            let batch: Vec<Zone> = buffer[ZONE_DESCRIPTOR_BYTES..]
+                .chunks_exact(ZONE_DESCRIPTOR_BYTES)
+                .take(available)
+                .map(Zone::parse)
+                .collect();
+
+            let last = match batch.last() {
+                Some(last) => *last,
+                None => break,
+            };
+            let complete = batch.len() == available;
+
+            zones.extend(batch);
+
+            if complete || last.length == 0 {
+                break;
+            }
+            locator = last.start + last.length;
+        }
+
+        Ok(zones)
+    }
+
+    /// Reset write pointer of zone starting at `zone`, or of all zones if `None`, using `RESET
+    /// WRITE POINTER EXT` (ATA cmd 0x9F, action 0x04). **Data in the zone is lost.**
+    pub fn reset_write_pointer(&mut self, zone: Option<u64>) -> io::Result<()> {
+        if self.skip_dry_run(format_args!("reset write pointer of zone {:?}", zone))? {
+            return Ok(());
+        }
+        self.zone_management(ZAC_RESET_WRITE_POINTER_EXT, zone)
+    }
+
+    /// Explicitly open zone starting at `zone`, or all closed zones if `None`, using `OPEN ZONE
+    /// EXT` (ATA cmd 0x9F, action 0x03).
+    pub fn open_zone(&mut self, zone: Option<u64>) -> io::Result<()> {
+        self.zone_management(ZAC_OPEN_ZONE_EXT, zone)
+    }
+
+    /// Close zone starting at `zone`, or all open zones if `None`, using `CLOSE ZONE EXT` (ATA cmd
+    /// 0x9F, action 0x01).
+    pub fn close_zone(&mut self, zone: Option<u64>) -> io::Result<()> {
+        self.zone_management(ZAC_CLOSE_ZONE_EXT, zone)
+    }
+
+    /// Move zone starting at `zone`, or all open and closed zones if `None`, to full condition
+    /// using `FINISH ZONE EXT` (ATA cmd 0x9F, action 0x02).
+    pub fn finish_zone(&mut self, zone: Option<u64>) -> io::Result<()> {
+        self.zone_management(ZAC_FINISH_ZONE_EXT, zone)
+    }
+
+    /// Issue a single non-data `ZAC MANAGEMENT OUT` action
+    fn zone_management(&mut self, action: u16, zone: Option<u64>) -> io::Result<()> {
+        let tf = TaskFile {
+            command: ATA_ZAC_MANAGEMENT_OUT,
+            features: action | zone.map_or(ZAC_ALL, |_| 0),
+            lba: zone.unwrap_or(0),
+            device: ATA_DEV_LBA,
+            ext: true,
+            ..Default::default()
+        };
+
+        self.retry(|ata| ata.raw_command(&tf, Transfer::NonData))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_zone_descriptor() {
+        let mut desc = [0u8; 64];
+        desc[0] = 0x02;
+        desc[1] = 0x20;
+        desc[8..16].copy_from_slice(&0x8_0000u64.to_le_bytes());
+        desc[16..24].copy_from_slice(&0x10_0000u64.to_le_bytes());
+        desc[24..32].copy_from_slice(&0x10_0800u64.to_le_bytes());
+
+        assert_eq!(
+            Zone::parse(&desc),
+            Zone {
+                zone_type: ZoneType::SequentialWriteRequired,
+                condition: ZoneCondition::ImplicitlyOpened,
+                start: 0x10_0000,
+                length: 0x8_0000,
+                write_pointer: 0x10_0800,
+            }
+        );
+    }
+}